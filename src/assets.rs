@@ -0,0 +1,89 @@
+//! In-memory static assets, for applications distributed as a single binary.
+
+use hyper::header::{ContentType, ETag, EntityTag};
+use hyper::status::StatusCode as Status;
+
+use conditional;
+use request::Request;
+use response::{Action, CachePolicy, Response, Result};
+
+use std::collections::HashMap;
+use std::collections::hash_map::DefaultHasher;
+use std::hash::{Hash, Hasher};
+
+struct Asset {
+    bytes: &'static [u8],
+    content_type: String,
+    etag: EntityTag
+}
+
+/// A set of assets held in memory, indexed by path.
+///
+/// ```no_run
+/// # extern crate edge;
+/// # use edge::{Assets, Edge};
+/// # fn main() {
+/// let mut assets = Assets::new();
+/// assets.insert("css/my_app.css", b"body { margin: 0 }", "text/css");
+///
+/// let mut edge = Edge::new("0.0.0.0:3000");
+/// edge.mount_assets("/static", assets);
+/// # }
+/// ```
+pub struct Assets {
+    assets: HashMap<String, Asset>,
+    cache: Option<CachePolicy>
+}
+
+impl Assets {
+    /// Creates an empty set of assets.
+    pub fn new() -> Assets {
+        Assets {
+            assets: HashMap::new(),
+            cache: None
+        }
+    }
+
+    /// Adds an asset with the given path (relative to where the assets are mounted), bytes, and content type.
+    ///
+    /// The ETag of the asset is computed from its bytes.
+    pub fn insert(&mut self, path: &str, bytes: &'static [u8], content_type: &str) {
+        let mut hasher = DefaultHasher::new();
+        bytes.hash(&mut hasher);
+
+        self.assets.insert(path.trim_left_matches('/').to_string(), Asset {
+            bytes: bytes,
+            content_type: content_type.to_string(),
+            etag: EntityTag::new(false, format!("{:x}", hasher.finish()))
+        });
+    }
+
+    /// Sets the cache policy of responses serving these assets.
+    pub fn cache_control(&mut self, policy: CachePolicy) {
+        self.cache = Some(policy);
+    }
+
+    /// Serves the asset whose path is given by the "path" parameter of the request.
+    pub fn serve(&self, req: &Request, res: &mut Response) -> Result {
+        self.serve_path(req.param("path").unwrap_or(""), req, res)
+    }
+
+    /// Serves the asset with the given path.
+    pub fn serve_path(&self, path: &str, req: &Request, res: &mut Response) -> Result {
+        let asset = match self.assets.get(path) {
+            Some(asset) => asset,
+            None => return Err(Status::NotFound.into())
+        };
+
+        res.content_type(asset.content_type.as_str()).header(ETag(asset.etag.clone()));
+        if let Some(ref policy) = self.cache {
+            res.cache_control(policy.clone());
+        }
+
+        if conditional::not_modified(req, Some(&asset.etag), None) {
+            Ok(Action::End(Some(Status::NotModified)))
+        } else {
+            Ok(Action::Send(asset.bytes.to_vec()))
+        }
+    }
+}
@@ -1,4 +1,5 @@
 use std::io::{Error, ErrorKind, Result, Read, Write};
+use std::sync::Mutex;
 
 #[derive(Debug)]
 pub struct Buffer {
@@ -9,10 +10,25 @@ pub struct Buffer {
     ///   - false when reading a fixed buffer (Content-Length known in advance),
     ///     in which case it is only allocated once.
     ///   - true when using Transfer-Encoding: chunked, and the buffer grows dynamically
-    growable: bool
+    growable: bool,
+
+    /// initial size of a growable buffer when reading,
+    /// and maximum number of bytes written to the transport at once
+    chunk_size: usize,
+
+    /// maximum number of bytes read into a growable buffer (if any)
+    limit: Option<usize>
 }
 
-const DEFAULT_BUF_SIZE: usize = 4 * 1024;
+/// Default size of reads and writes.
+pub const DEFAULT_BUF_SIZE: usize = 4 * 1024;
+
+/// Sizes of reads and writes must be between these bounds.
+pub const MIN_BUF_SIZE: usize = 512;
+pub const MAX_BUF_SIZE: usize = 1024 * 1024;
+
+/// Vectors with a capacity larger than this are not kept in the pool.
+const MAX_POOLED_CAPACITY: usize = 64 * 1024;
 
 impl Buffer {
     /// Creates a new growable buffer
@@ -20,18 +36,22 @@ impl Buffer {
         Buffer {
             content: Vec::new(),
             pos: 0,
-            growable: true
+            growable: true,
+            chunk_size: DEFAULT_BUF_SIZE,
+            limit: None
         }
     }
 
-    /// Creates a new fixed size buffer.
-    pub fn new_fixed(capacity: usize) -> Buffer {
-        debug!("creating fixed buffer with capacity {}", capacity);
-        Buffer {
-            content: vec![0; capacity],
-            pos: 0,
-            growable: false
-        }
+    /// Sets the initial size of this buffer when reading (if growable),
+    /// and the maximum number of bytes written to the transport at once.
+    pub fn set_chunk_size(&mut self, chunk_size: usize) {
+        self.chunk_size = chunk_size;
+    }
+
+    /// Sets the maximum number of bytes read into this buffer when growable;
+    /// reading more fails with an `InvalidData` error.
+    pub fn set_limit(&mut self, limit: Option<usize>) {
+        self.limit = limit;
     }
 
     /// Updates the capacity of this buffer.
@@ -52,8 +72,8 @@ impl Buffer {
                 let mut len = self.len();
                 if self.pos == len {
                     // if buffer is full, extend it
-                    if len < DEFAULT_BUF_SIZE {
-                        len = DEFAULT_BUF_SIZE;
+                    if len < self.chunk_size {
+                        len = self.chunk_size;
                     } else {
                         len *= 2;
                     }
@@ -95,6 +115,9 @@ impl Buffer {
                     if !self.growable && self.pos == self.len() {
                         return Ok(false);
                     }
+                    if self.limit.map_or(false, |limit| self.pos > limit) {
+                        return Err(Error::new(ErrorKind::InvalidData, "payload too large"));
+                    }
                 }
                 Err(e) => {
                     return match e.kind() {
@@ -126,7 +149,8 @@ impl Buffer {
         }
 
         loop {
-            match writer.write(&self.content[self.pos..]) {
+            let end = ::std::cmp::min(self.pos + self.chunk_size, self.len());
+            match writer.write(&self.content[self.pos..end]) {
                 Ok(0) => {
                     return Err(Error::new(ErrorKind::WriteZero, "could not write to the transport"));
                 }
@@ -166,7 +190,97 @@ impl From<Vec<u8>> for Buffer {
         Buffer {
             content: content,
             pos: 0,
-            growable: true
+            growable: true,
+            chunk_size: DEFAULT_BUF_SIZE,
+            limit: None
+        }
+    }
+}
+
+/// A pool of vectors reused across requests to reduce allocations.
+///
+/// Buffers used to read request bodies are taken from the pool,
+/// and response buffers are returned to it once written.
+pub struct BufferPool {
+    max_buffers: usize,
+    buffers: Mutex<Vec<Vec<u8>>>,
+    read_size: usize,
+    write_size: usize
+}
+
+impl BufferPool {
+    /// Creates a pool keeping at most `max_buffers` vectors (0 disables pooling).
+    pub fn new(max_buffers: usize) -> BufferPool {
+        BufferPool {
+            max_buffers: max_buffers,
+            buffers: Mutex::new(Vec::new()),
+            read_size: DEFAULT_BUF_SIZE,
+            write_size: DEFAULT_BUF_SIZE
+        }
+    }
+
+    /// Sets the maximum number of vectors kept (0 disables pooling).
+    pub fn set_max_buffers(&mut self, max_buffers: usize) {
+        self.max_buffers = max_buffers;
+    }
+
+    /// Sets the size of reads (when the length of the body is unknown) and the size of writes.
+    pub fn set_sizes(&mut self, read_size: usize, write_size: usize) {
+        self.read_size = read_size;
+        self.write_size = write_size;
+    }
+
+    /// Returns the size of reads when the length of the body is unknown.
+    pub fn read_size(&self) -> usize {
+        self.read_size
+    }
+
+    /// Returns the maximum number of bytes written to the transport at once.
+    pub fn write_size(&self) -> usize {
+        self.write_size
+    }
+
+    /// Returns an empty vector, reused from the pool if possible.
+    fn get(&self) -> Vec<u8> {
+        self.buffers.lock().unwrap().pop().unwrap_or_else(Vec::new)
+    }
+
+    /// Returns the given vector to the pool after clearing it,
+    /// unless the pool is full or the vector is too large to be worth keeping.
+    pub fn put(&self, mut content: Vec<u8>) {
+        if content.capacity() == 0 || content.capacity() > ::std::cmp::max(MAX_POOLED_CAPACITY, self.read_size) {
+            return;
+        }
+
+        let mut buffers = self.buffers.lock().unwrap();
+        if buffers.len() < self.max_buffers {
+            content.clear();
+            buffers.push(content);
+        }
+    }
+
+    /// Creates a new growable buffer backed by a pooled vector.
+    pub fn buffer(&self) -> Buffer {
+        Buffer {
+            content: self.get(),
+            pos: 0,
+            growable: true,
+            chunk_size: self.read_size,
+            limit: None
+        }
+    }
+
+    /// Creates a new fixed size buffer backed by a pooled vector.
+    pub fn buffer_fixed(&self, capacity: usize) -> Buffer {
+        debug!("creating fixed buffer with capacity {}", capacity);
+        let mut content = self.get();
+        content.resize(capacity, 0);
+        Buffer {
+            content: content,
+            pos: 0,
+            growable: false,
+            chunk_size: self.read_size,
+            limit: None
         }
     }
 }
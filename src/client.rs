@@ -1,94 +1,187 @@
 //! Defines functionality for a minimalistic synchronous client.
 
-use hyper::{Client as HttpClient, Decoder, Encoder, Next};
+use hyper::{Client as HttpClient, Decoder, Encoder, Headers, Method, Next, Url};
 use hyper::client::{Handler, Request as ClientRequest, Response as ClientResponse};
 use hyper::net::HttpStream;
 
-use std::thread::{self, Thread};
+use std::fmt;
+use std::result;
+use std::sync::mpsc::{self, RecvTimeoutError, Sender};
+use std::time::Duration;
 
 use buffer::Buffer;
 
+/// Default time to wait for a response.
+pub const DEFAULT_TIMEOUT: u64 = 30;
+
 pub struct Client {
-    result: RequestResult
+    timeout: Duration,
+    response: Option<ClientResponse>
 }
 
-struct RequestResult {
-    body: Option<Vec<u8>>,
-    response: Option<ClientResponse>
+/// Reason why a request sent with `Client::try_send` got no response.
+#[derive(Debug)]
+pub enum ClientError {
+    /// The URL could not be parsed.
+    InvalidUrl(String),
+
+    /// No response was received before the timeout elapsed.
+    Timeout,
+
+    /// The server could not be reached, or closed the connection without responding.
+    NoResponse
 }
 
-impl RequestResult {
-    fn new() -> RequestResult {
-        RequestResult {
-            body: None,
-            response: None
+impl fmt::Display for ClientError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            ClientError::InvalidUrl(ref e) => write!(f, "invalid URL: {}", e),
+            ClientError::Timeout => f.write_str("timed out waiting for a response"),
+            ClientError::NoResponse => f.write_str("no response")
         }
     }
 }
 
+/// What the handler collected, sent back to the waiting client when the handler is dropped.
+struct RequestResult {
+    body: Vec<u8>,
+    response: Option<ClientResponse>
+}
+
 impl Client {
     pub fn new() -> Client {
         Client {
-            result: RequestResult::new()
+            timeout: Duration::from_secs(DEFAULT_TIMEOUT),
+            response: None
         }
     }
 
+    /// Sets how long to wait for the response to a request (default is 30 seconds).
+    pub fn timeout(&mut self, timeout: Duration) {
+        self.timeout = timeout;
+    }
+
+    /// Sends a GET request to the given URL, waits for the response and returns its body.
     pub fn request(&mut self, url: &str) -> Vec<u8> {
-        let client = HttpClient::new().unwrap();
-        let _ = client.request(url.parse().unwrap(), ClientHandler::new(&mut self.result));
+        self.send(Method::Get, url, Headers::new(), None)
+    }
+
+    /// Sends a request with the given method, headers, and body (if any) to the given URL,
+    /// waits for the response and returns its body.
+    ///
+    /// Returns an empty body if there is no response, see `try_send` to know why.
+    pub fn send(&mut self, method: Method, url: &str, headers: Headers, body: Option<Vec<u8>>) -> Vec<u8> {
+        self.try_send(method, url, headers, body).unwrap_or_else(|e| {
+            warn!("request to {} failed: {}", url, e);
+            Vec::new()
+        })
+    }
+
+    /// Sends a request with the given method, headers, and body (if any) to the given URL,
+    /// waits for the response (up to the timeout) and returns its body.
+    pub fn try_send(&mut self, method: Method, url: &str, headers: Headers, body: Option<Vec<u8>>) -> result::Result<Vec<u8>, ClientError> {
+        self.response = None;
+        let parsed = try!(url.parse::<Url>().map_err(|e| ClientError::InvalidUrl(format!("{}: {}", url, e))));
+
+        let client = match HttpClient::new() {
+            Ok(client) => client,
+            Err(e) => {
+                warn!("could not create client: {}", e);
+                return Err(ClientError::NoResponse);
+            }
+        };
 
-        // wait for request to complete
-        thread::park();
+        // the handler sends what it got when it is dropped, i.e. when the request is complete
+        let (tx, rx) = mpsc::channel();
+        let _ = client.request(parsed, ClientHandler::new(tx, method, headers, body));
+        let result = rx.recv_timeout(self.timeout);
 
-        // close client and returns request body
+        // close client, which drops the handler if the request is still pending
         client.close();
 
-        if let Some(buffer) = self.result.body.take() {
-            buffer
-        } else {
-            Vec::new()
+        match result {
+            Ok(RequestResult { body, response: Some(response) }) => {
+                self.response = Some(response);
+                Ok(body)
+            }
+            Ok(_) | Err(RecvTimeoutError::Disconnected) => Err(ClientError::NoResponse),
+            Err(RecvTimeoutError::Timeout) => Err(ClientError::Timeout)
         }
     }
 
+    /// Returns `true` if a response was received for the last request.
+    pub fn has_response(&self) -> bool {
+        self.response.is_some()
+    }
+
     pub fn status(&self) -> ::hyper::status::StatusCode {
-        *self.result.response.as_ref().unwrap().status()
+        *self.response.as_ref().unwrap().status()
+    }
+
+    /// Returns the headers of the last response.
+    pub fn headers(&self) -> &Headers {
+        self.response.as_ref().unwrap().headers()
     }
 }
 
 struct ClientHandler {
-    thread: Thread,
+    sender: Sender<RequestResult>,
     buffer: Buffer,
-    result: *mut RequestResult
+    response: Option<ClientResponse>,
+    method: Option<Method>,
+    headers: Headers,
+    body: Option<Buffer>
 }
 
-unsafe impl Send for ClientHandler {}
-
 impl ClientHandler {
-    fn new(result: &mut RequestResult) -> ClientHandler {
+    fn new(sender: Sender<RequestResult>, method: Method, headers: Headers, body: Option<Vec<u8>>) -> ClientHandler {
         ClientHandler {
-            thread: thread::current(),
+            sender: sender,
             buffer: Buffer::new(),
-            result: result as *mut RequestResult
+            response: None,
+            method: Some(method),
+            headers: headers,
+            body: body.map(Buffer::from)
         }
     }
 }
 
 impl Drop for ClientHandler {
     fn drop(&mut self) {
-        unsafe { (*self.result).body = Some(self.buffer.take()); }
-
-        // unlocks waiting thread
-        self.thread.unpark();
+        // unlocks waiting thread, unless it gave up waiting
+        let _ = self.sender.send(RequestResult {
+            body: self.buffer.take(),
+            response: self.response.take()
+        });
     }
 }
 
 impl Handler<HttpStream> for ClientHandler {
 
-    fn on_request(&mut self, _req: &mut ClientRequest) -> Next {
-        Next::read()
+    fn on_request(&mut self, req: &mut ClientRequest) -> Next {
+        use hyper::header::ContentLength;
+        if let Some(method) = self.method.take() {
+            req.set_method(method);
+        }
+        req.headers_mut().extend(self.headers.iter());
+
+        if let Some(ref body) = self.body {
+            req.headers_mut().set(ContentLength(body.len() as u64));
+            Next::write()
+        } else {
+            Next::read()
+        }
     }
 
-    fn on_request_writable(&mut self, _encoder: &mut Encoder<HttpStream>) -> Next {
+    fn on_request_writable(&mut self, encoder: &mut Encoder<HttpStream>) -> Next {
+        if let Some(ref mut body) = self.body {
+            match body.write_to(encoder) {
+                Ok(true) => return Next::write(),
+                Ok(false) => (),
+                Err(_) => return Next::remove()
+            }
+        }
+
         Next::read()
     }
 
@@ -97,7 +190,7 @@ impl Handler<HttpStream> for ClientHandler {
         if let Some(&ContentLength(len)) = res.headers().get::<ContentLength>() {
             self.buffer.set_capacity(len as usize);
         }
-        unsafe { (*self.result).response = Some(res); }
+        self.response = Some(res);
 
         Next::read()
     }
@@ -113,7 +206,7 @@ impl Handler<HttpStream> for ClientHandler {
     }
 
     fn on_error(&mut self, err: ::hyper::Error) -> Next {
-        println!("ERROR: {}", err);
+        warn!("client error: {}", err);
         Next::remove()
     }
 
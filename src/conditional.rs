@@ -0,0 +1,66 @@
+//! Conditional requests.
+//!
+//! RFC 7232 Hypertext Transfer Protocol (HTTP/1.1): Conditional Requests
+//! http://httpwg.org/specs/rfc7232.html
+
+use hyper::header::{EntityTag, HttpDate, IfMatch, IfModifiedSince, IfNoneMatch, IfUnmodifiedSince};
+use hyper::method::Method::{Get, Head};
+
+use request::Request;
+
+/// Returns `true` if one of the given entity tags matches the given one,
+/// using the weak comparison function.
+///
+/// 2.3.2 Comparison
+/// http://httpwg.org/specs/rfc7232.html#entity.tag.comparison
+///
+/// Two entity-tags are equivalent for weak comparison if their opaque-tags match
+/// character-by-character, regardless of either or both being tagged as "weak".
+pub fn weak_match(tags: &[EntityTag], etag: &EntityTag) -> bool {
+    tags.iter().any(|tag| tag.weak_eq(etag))
+}
+
+/// Returns `true` if the client already has the current representation, as described by the given
+/// validators, in which case the server should respond with 304 Not Modified.
+///
+/// 6. Precedence
+/// http://httpwg.org/specs/rfc7232.html#precedence
+///
+/// If-None-Match is evaluated with weak comparison; when it is present, If-Modified-Since is ignored.
+/// If-Modified-Since is only evaluated for GET and HEAD requests.
+pub fn not_modified(req: &Request, etag: Option<&EntityTag>, modified: Option<&HttpDate>) -> bool {
+    if let Some(if_none_match) = req.headers().get::<IfNoneMatch>() {
+        return match (if_none_match, etag) {
+            (&IfNoneMatch::Any, Some(_)) => true,
+            (&IfNoneMatch::Items(ref tags), Some(etag)) => weak_match(tags, etag),
+            (_, None) => false
+        };
+    }
+
+    let method = req.method();
+    if *method == Get || *method == Head {
+        if let (Some(&IfModifiedSince(ref since)), Some(modified)) = (req.headers().get::<IfModifiedSince>(), modified) {
+            return modified.0.to_timespec() <= since.0.to_timespec();
+        }
+    }
+
+    false
+}
+
+/// Returns `true` if the resource, last modified at the given date, was modified since the date
+/// given by If-Unmodified-Since, in which case the server should respond with 412 Precondition Failed.
+///
+/// 3.4 If-Unmodified-Since
+/// http://httpwg.org/specs/rfc7232.html#header.if-unmodified-since
+///
+/// A recipient MUST ignore If-Unmodified-Since if the request contains an If-Match header field.
+pub fn precondition_failed(req: &Request, modified: &HttpDate) -> bool {
+    if req.headers().has::<IfMatch>() {
+        return false;
+    }
+
+    match req.headers().get::<IfUnmodifiedSince>() {
+        Some(&IfUnmodifiedSince(ref since)) => modified.0.to_timespec() > since.0.to_timespec(),
+        None => false
+    }
+}
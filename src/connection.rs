@@ -0,0 +1,125 @@
+//! Connection module
+
+use hyper::net::{HttpStream, Transport};
+
+use mio::{EventSet, Evented, PollOpt, Selector, Token};
+
+use std::any::{Any, TypeId};
+use std::cell::RefCell;
+use std::collections::HashMap;
+use std::io::{self, Read, Write};
+use std::sync::{Arc, Mutex};
+
+/// State associated with a connection, shared by all the requests received on it.
+///
+/// Unlike the application structure, which is created anew for each request,
+/// values stored here survive as long as the connection is kept alive.
+/// Values are indexed by their type, so there is at most one value of each type.
+///
+/// Hyper does not hand the transport to the handler of a request, so a request finds
+/// the state of its connection through the last connection its server loop read from
+/// or wrote to. Pipelined requests share the state as long as hyper parses them right
+/// after writing the previous response on their connection.
+#[derive(Clone)]
+pub struct Connection {
+    state: Arc<Mutex<HashMap<TypeId, Box<Any + Send>>>>
+}
+
+impl Connection {
+    pub fn new() -> Connection {
+        Connection {
+            state: Arc::new(Mutex::new(HashMap::new()))
+        }
+    }
+
+    /// Stores the given value, replacing any previous value of the same type.
+    pub fn set<T: Any + Send>(&self, value: T) {
+        self.state.lock().unwrap().insert(TypeId::of::<T>(), Box::new(value));
+    }
+
+    /// Returns a copy of the value of type `T` (if any).
+    pub fn get<T: Any + Send + Clone>(&self) -> Option<T> {
+        self.state.lock().unwrap().get(&TypeId::of::<T>()).and_then(|value|
+            value.downcast_ref::<T>().cloned()
+        )
+    }
+
+    /// Removes and returns the value of type `T` (if any).
+    pub fn remove<T: Any + Send>(&self) -> Option<T> {
+        self.state.lock().unwrap().remove(&TypeId::of::<T>()).and_then(|value|
+            value.downcast::<T>().ok().map(|boxed| *boxed)
+        )
+    }
+}
+
+thread_local!(static CURRENT: RefCell<Option<Connection>> = RefCell::new(None));
+
+/// Returns the state of the connection on which the server loop of this thread last read
+/// or wrote, if it has not been returned already.
+///
+/// Handlers last only the time of a request, and hyper does not give them their transport,
+/// but they are created by the server loop on the same thread, right after it read the head
+/// of the request from its transport: this is how a handler finds the state of its connection.
+/// A request pipelined behind another is parsed from what hyper has already read, right after
+/// writing the previous response, so the state is also made current when writing.
+pub fn current() -> Option<Connection> {
+    CURRENT.with(|current| current.borrow_mut().take())
+}
+
+/// A transport that carries the state of its connection.
+pub struct Stream {
+    inner: HttpStream,
+    connection: Connection
+}
+
+impl Stream {
+    pub fn new(inner: HttpStream) -> Stream {
+        Stream {
+            inner: inner,
+            connection: Connection::new()
+        }
+    }
+
+    /// Makes the state of this connection the current one of the thread (see `current`).
+    fn make_current(&self) {
+        CURRENT.with(|current| *current.borrow_mut() = Some(self.connection.clone()));
+    }
+}
+
+impl Read for Stream {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        self.make_current();
+        self.inner.read(buf)
+    }
+}
+
+impl Write for Stream {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.make_current();
+        self.inner.write(buf)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.inner.flush()
+    }
+}
+
+impl Evented for Stream {
+    fn register(&self, selector: &mut Selector, token: Token, interest: EventSet, opts: PollOpt) -> io::Result<()> {
+        self.inner.register(selector, token, interest, opts)
+    }
+
+    fn reregister(&self, selector: &mut Selector, token: Token, interest: EventSet, opts: PollOpt) -> io::Result<()> {
+        self.inner.reregister(selector, token, interest, opts)
+    }
+
+    fn deregister(&self, selector: &mut Selector) -> io::Result<()> {
+        self.inner.deregister(selector)
+    }
+}
+
+impl Transport for Stream {
+    fn take_socket_error(&mut self) -> io::Result<()> {
+        self.inner.take_socket_error()
+    }
+}
@@ -0,0 +1,153 @@
+use hyper::header::CookiePair as Cookie;
+
+use time;
+
+use std::fmt;
+use std::time::Duration;
+
+/// Prefix of the names of cookies bound to the host that set them.
+const HOST_PREFIX: &'static str = "__Host-";
+
+/// Prefix of the names of cookies that are only set and sent over HTTPS.
+const SECURE_PREFIX: &'static str = "__Secure-";
+
+/// Value of the `SameSite` attribute of a cookie.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum SameSite {
+    Strict,
+    Lax,
+    None
+}
+
+impl fmt::Display for SameSite {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str(match *self {
+            SameSite::Strict => "Strict",
+            SameSite::Lax => "Lax",
+            SameSite::None => "None"
+        })
+    }
+}
+
+/// Builder for a cookie with all its attributes.
+///
+/// ```
+/// use edge::{CookieBuilder, SameSite};
+/// use std::time::Duration;
+///
+/// let cookie = CookieBuilder::new("session", "42")
+///     .max_age(Duration::from_secs(3600))
+///     .same_site(SameSite::Lax)
+///     .path("/")
+///     .secure()
+///     .http_only()
+///     .build();
+///
+/// let header = cookie.to_string();
+/// assert!(header.starts_with("session=42; HttpOnly; Secure; Path=/; Max-Age=3600; Expires="));
+/// assert!(header.ends_with("; SameSite=Lax"));
+/// ```
+pub struct CookieBuilder {
+    cookie: Cookie
+}
+
+impl CookieBuilder {
+    /// Creates a builder for a cookie with the given name and value, and no attributes.
+    pub fn new<N: Into<String>, V: Into<String>>(name: N, value: V) -> CookieBuilder {
+        CookieBuilder {
+            cookie: Cookie::new(name.into(), value.into())
+        }
+    }
+
+    /// Creates a builder for a cookie whose name is given the `__Host-` prefix,
+    /// with the attributes this prefix requires: `Secure`, `Path=/` and no `Domain`,
+    /// so that the cookie is bound to the host that set it.
+    ///
+    /// ```
+    /// use edge::CookieBuilder;
+    ///
+    /// let cookie = CookieBuilder::host_prefixed("session", "42").http_only().build();
+    /// assert_eq!(cookie.to_string(), "__Host-session=42; HttpOnly; Secure; Path=/");
+    ///
+    /// assert!(CookieBuilder::host_prefixed("session", "42").domain("example.com").try_build().is_err());
+    /// ```
+    pub fn host_prefixed<N: Into<String>, V: Into<String>>(name: N, value: V) -> CookieBuilder {
+        CookieBuilder::new(format!("{}{}", HOST_PREFIX, name.into()), value).secure().path("/")
+    }
+
+    /// Creates a builder for a cookie whose name is given the `__Secure-` prefix,
+    /// with the `Secure` attribute this prefix requires.
+    pub fn secure_prefixed<N: Into<String>, V: Into<String>>(name: N, value: V) -> CookieBuilder {
+        CookieBuilder::new(format!("{}{}", SECURE_PREFIX, name.into()), value).secure()
+    }
+
+    /// Sets the `Max-Age` attribute, as well as `Expires` for older clients.
+    pub fn max_age(mut self, max_age: Duration) -> CookieBuilder {
+        let seconds = max_age.as_secs();
+        self.cookie.max_age = Some(seconds);
+        self.cookie.expires = Some(time::now_utc() + time::Duration::seconds(seconds as i64));
+        self
+    }
+
+    /// Sets the `SameSite` attribute.
+    pub fn same_site(mut self, same_site: SameSite) -> CookieBuilder {
+        self.cookie.custom.insert("SameSite".to_string(), same_site.to_string());
+        self
+    }
+
+    /// Sets the `Domain` attribute.
+    pub fn domain<S: Into<String>>(mut self, domain: S) -> CookieBuilder {
+        self.cookie.domain = Some(domain.into());
+        self
+    }
+
+    /// Sets the `Path` attribute.
+    pub fn path<S: Into<String>>(mut self, path: S) -> CookieBuilder {
+        self.cookie.path = Some(path.into());
+        self
+    }
+
+    /// Sets the `Secure` attribute, so that the cookie is only sent over HTTPS.
+    pub fn secure(mut self) -> CookieBuilder {
+        self.cookie.secure = true;
+        self
+    }
+
+    /// Sets the `HttpOnly` attribute, so that the cookie is not available to scripts.
+    pub fn http_only(mut self) -> CookieBuilder {
+        self.cookie.httponly = true;
+        self
+    }
+
+    /// Returns the cookie, to be given to `Response::cookie`.
+    ///
+    /// Panics if the name of the cookie has a prefix whose requirements are not met,
+    /// see `try_build`.
+    pub fn build(self) -> Cookie {
+        match self.try_build() {
+            Ok(cookie) => cookie,
+            Err(e) => panic!("{}", e)
+        }
+    }
+
+    /// Returns the cookie, or an error if its name has a prefix whose requirements are not met
+    /// (browsers reject such cookies):
+    /// a `__Secure-` cookie must be `Secure`, and a `__Host-` cookie must also have `Path=/`
+    /// and no `Domain`.
+    pub fn try_build(self) -> Result<Cookie, String> {
+        {
+            let cookie = &self.cookie;
+            let is_host = cookie.name.starts_with(HOST_PREFIX);
+            if (is_host || cookie.name.starts_with(SECURE_PREFIX)) && !cookie.secure {
+                return Err(format!("cookie {} must be Secure", cookie.name));
+            }
+            if is_host && cookie.path.as_ref().map_or(true, |path| path != "/") {
+                return Err(format!("cookie {} must have Path=/", cookie.name));
+            }
+            if is_host && cookie.domain.is_some() {
+                return Err(format!("cookie {} must not have a Domain", cookie.name));
+            }
+        }
+        Ok(self.cookie)
+    }
+}
@@ -0,0 +1,144 @@
+//! Content Security Policy.
+//!
+//! Content Security Policy Level 3
+//! https://www.w3.org/TR/CSP3/
+
+use std::fmt;
+
+/// Builder for the Content-Security-Policy header of a response.
+///
+/// Each method adds sources (separated by spaces) to a directive.
+///
+/// ```
+/// use edge::Csp;
+///
+/// let csp = Csp::new()
+///     .default_src("'self'")
+///     .script_src("'self' https://cdn.example.com")
+///     .img_src("*")
+///     .frame_ancestors("'none'");
+/// assert_eq!(csp.to_string(),
+///     "default-src 'self'; script-src 'self' https://cdn.example.com; img-src *; frame-ancestors 'none'");
+/// ```
+#[derive(Clone, Debug, Default)]
+pub struct Csp {
+    directives: Vec<(String, Vec<String>)>,
+    report_only: bool
+}
+
+impl Csp {
+    /// Creates an empty policy.
+    pub fn new() -> Csp {
+        Csp::default()
+    }
+
+    /// Fallback for the other fetch directives.
+    pub fn default_src(self, sources: &str) -> Self {
+        self.directive("default-src", sources)
+    }
+
+    /// Sources of scripts.
+    pub fn script_src(self, sources: &str) -> Self {
+        self.directive("script-src", sources)
+    }
+
+    /// Sources of stylesheets.
+    pub fn style_src(self, sources: &str) -> Self {
+        self.directive("style-src", sources)
+    }
+
+    /// Sources of images.
+    pub fn img_src(self, sources: &str) -> Self {
+        self.directive("img-src", sources)
+    }
+
+    /// Sources of fonts.
+    pub fn font_src(self, sources: &str) -> Self {
+        self.directive("font-src", sources)
+    }
+
+    /// URLs that scripts may connect to (fetch, XMLHttpRequest, WebSocket, EventSource).
+    pub fn connect_src(self, sources: &str) -> Self {
+        self.directive("connect-src", sources)
+    }
+
+    /// Sources of plugins (`<object>` and `<embed>`), usually `'none'`.
+    pub fn object_src(self, sources: &str) -> Self {
+        self.directive("object-src", sources)
+    }
+
+    /// Pages that may embed this one in a frame, usually `'none'` or `'self'`.
+    pub fn frame_ancestors(self, sources: &str) -> Self {
+        self.directive("frame-ancestors", sources)
+    }
+
+    /// URLs that forms may be submitted to.
+    pub fn form_action(self, sources: &str) -> Self {
+        self.directive("form-action", sources)
+    }
+
+    /// URLs that may be used in the `<base>` element.
+    pub fn base_uri(self, sources: &str) -> Self {
+        self.directive("base-uri", sources)
+    }
+
+    /// URL to which browsers report violations of the policy.
+    pub fn report_uri(self, uri: &str) -> Self {
+        self.directive("report-uri", uri)
+    }
+
+    /// Adds the given sources (or values) to the directive with the given name,
+    /// for directives that have no method of their own.
+    pub fn directive(mut self, name: &str, sources: &str) -> Self {
+        let name = name.to_lowercase();
+        let position = self.directives.iter().position(|&(ref existing, _)| *existing == name);
+        let index = match position {
+            Some(index) => index,
+            None => {
+                self.directives.push((name, Vec::new()));
+                self.directives.len() - 1
+            }
+        };
+
+        {
+            let values = &mut self.directives[index].1;
+            for source in sources.split_whitespace() {
+                if !values.iter().any(|existing| existing == source) {
+                    values.push(source.to_string());
+                }
+            }
+        }
+        self
+    }
+
+    /// Only reports violations of the policy instead of enforcing it, by sending it
+    /// in the Content-Security-Policy-Report-Only header.
+    pub fn report_only(mut self) -> Self {
+        self.report_only = true;
+        self
+    }
+
+    /// Returns the name of the header in which this policy is sent.
+    pub fn header_name(&self) -> &'static str {
+        if self.report_only {
+            "Content-Security-Policy-Report-Only"
+        } else {
+            "Content-Security-Policy"
+        }
+    }
+}
+
+impl fmt::Display for Csp {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        for (i, &(ref name, ref values)) in self.directives.iter().enumerate() {
+            if i > 0 {
+                try!(f.write_str("; "));
+            }
+            try!(f.write_str(name));
+            for value in values {
+                try!(write!(f, " {}", value));
+            }
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,43 @@
+//! Typed values attached to a request.
+
+use std::any::{Any, TypeId};
+use std::collections::HashMap;
+
+/// Values attached to a request, for example by middleware for the handler.
+///
+/// Values are indexed by their type, so there is at most one value of each type;
+/// define a dedicated type (e.g. `struct CurrentUser(String)`) for each kind of value.
+#[derive(Default)]
+pub struct Extensions {
+    values: HashMap<TypeId, Box<Any + Send>>
+}
+
+impl Extensions {
+    pub fn new() -> Extensions {
+        Extensions::default()
+    }
+
+    /// Stores the given value, returning the previous value of the same type (if any).
+    pub fn insert<T: Any + Send>(&mut self, value: T) -> Option<T> {
+        self.values.insert(TypeId::of::<T>(), Box::new(value)).and_then(|previous|
+            previous.downcast::<T>().ok().map(|boxed| *boxed)
+        )
+    }
+
+    /// Returns a reference to the value of type `T` (if any).
+    pub fn get<T: Any + Send>(&self) -> Option<&T> {
+        self.values.get(&TypeId::of::<T>()).and_then(|value| value.downcast_ref::<T>())
+    }
+
+    /// Returns a mutable reference to the value of type `T` (if any).
+    pub fn get_mut<T: Any + Send>(&mut self) -> Option<&mut T> {
+        self.values.get_mut(&TypeId::of::<T>()).and_then(|value| value.downcast_mut::<T>())
+    }
+
+    /// Removes and returns the value of type `T` (if any).
+    pub fn remove<T: Any + Send>(&mut self) -> Option<T> {
+        self.values.remove(&TypeId::of::<T>()).and_then(|value|
+            value.downcast::<T>().ok().map(|boxed| *boxed)
+        )
+    }
+}
@@ -0,0 +1,141 @@
+use std::ascii::AsciiExt;
+use std::net::IpAddr;
+
+/// One element of a `Forwarded` header (RFC 7239), i.e. the information added by one proxy.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct Forwarded {
+    /// The interface where the request came in to the proxy (`by` parameter).
+    pub by: Option<String>,
+
+    /// The client that made the request to the proxy (`for` parameter).
+    pub client: Option<String>,
+
+    /// The Host request header as received by the proxy (`host` parameter).
+    pub host: Option<String>,
+
+    /// The protocol used to make the request to the proxy (`proto` parameter).
+    pub proto: Option<String>
+}
+
+impl Forwarded {
+    /// Returns the IP address of the client, if the `for` parameter contains one.
+    ///
+    /// The port (if any) is ignored, and obfuscated identifiers such as `unknown`
+    /// or `_hidden` yield `None`.
+    pub fn client_ip(&self) -> Option<IpAddr> {
+        self.client.as_ref().and_then(|node| parse_node(node))
+    }
+}
+
+/// How far the forwarding headers added by proxies in front of the server are trusted.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum ProxyTrust {
+    /// Forwarding headers are ignored.
+    Nobody,
+
+    /// All forwarding headers are trusted, the first entry identifies the client.
+    All,
+
+    /// The given number of proxies in front of the server are trusted; the client is
+    /// identified by the entry added by the outermost trusted proxy, and the entries
+    /// before it (which the client may have forged) are ignored.
+    Hops(usize)
+}
+
+impl ProxyTrust {
+    /// Returns the index of the trusted entry among the `len` entries of a forwarding header
+    /// (listed from the client to the server), or `None` if no entry is trusted.
+    pub fn select(&self, len: usize) -> Option<usize> {
+        match *self {
+            _ if len == 0 => None,
+            ProxyTrust::Nobody | ProxyTrust::Hops(0) => None,
+            ProxyTrust::All => Some(0),
+            ProxyTrust::Hops(hops) => Some(len.saturating_sub(hops))
+        }
+    }
+}
+
+/// Parses the given `Forwarded` header lines into a list of elements,
+/// in the order in which they were added (the first one is closest to the client).
+pub fn parse(lines: &[&str]) -> Vec<Forwarded> {
+    let mut elements = Vec::new();
+    for line in lines {
+        for element in split_unquoted(line, ',') {
+            let mut forwarded = Forwarded::default();
+            for pair in split_unquoted(element, ';') {
+                let mut parts = pair.splitn(2, '=');
+                let name = parts.next().unwrap_or("").trim();
+                let value = match parts.next() {
+                    Some(value) => unquote(value.trim()),
+                    None => continue
+                };
+
+                if name.eq_ignore_ascii_case("by") {
+                    forwarded.by = Some(value);
+                } else if name.eq_ignore_ascii_case("for") {
+                    forwarded.client = Some(value);
+                } else if name.eq_ignore_ascii_case("host") {
+                    forwarded.host = Some(value);
+                } else if name.eq_ignore_ascii_case("proto") {
+                    forwarded.proto = Some(value.to_ascii_lowercase());
+                }
+            }
+
+            if forwarded != Forwarded::default() {
+                elements.push(forwarded);
+            }
+        }
+    }
+    elements
+}
+
+/// Splits the given string on the given separator, except within quoted strings.
+pub fn split_unquoted(s: &str, separator: char) -> Vec<&str> {
+    let mut parts = Vec::new();
+    let mut quoted = false;
+    let mut escaped = false;
+    let mut start = 0;
+    for (i, c) in s.char_indices() {
+        if escaped {
+            escaped = false;
+        } else if c == '\\' && quoted {
+            escaped = true;
+        } else if c == '"' {
+            quoted = !quoted;
+        } else if c == separator && !quoted {
+            parts.push(s[start..i].trim());
+            start = i + 1;
+        }
+    }
+    parts.push(s[start..].trim());
+    parts.into_iter().filter(|part| !part.is_empty()).collect()
+}
+
+/// Removes the quotes around a quoted string (if any), and unescapes quoted pairs.
+pub fn unquote(value: &str) -> String {
+    if value.len() >= 2 && value.starts_with('"') && value.ends_with('"') {
+        let mut result = String::with_capacity(value.len() - 2);
+        let mut escaped = false;
+        for c in value[1..value.len() - 1].chars() {
+            if c == '\\' && !escaped {
+                escaped = true;
+            } else {
+                result.push(c);
+                escaped = false;
+            }
+        }
+        result
+    } else {
+        value.to_string()
+    }
+}
+
+/// Parses a node (RFC 7239 section 6) into an IP address, ignoring the port.
+fn parse_node(node: &str) -> Option<IpAddr> {
+    if node.starts_with('[') {
+        // IPv6 address, optionally followed by a port
+        node[1..].split(']').next().and_then(|ip| ip.parse().ok())
+    } else {
+        node.parse().ok().or_else(|| node.split(':').next().and_then(|ip| ip.parse().ok()))
+    }
+}
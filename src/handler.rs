@@ -1,12 +1,12 @@
-use handlebars::Handlebars;
+use handlebars::{Handlebars, RenderError};
 
-use hyper::{Control, Decoder, Encoder, Next};
+use hyper::{Control, Decoder, Encoder, Headers, Next};
 use hyper::HttpVersion::{Http09, Http10, Http11};
 
 use hyper::error::Error as HyperError;
-use hyper::header::{ContentLength, ContentType, Encoding, TransferEncoding};
-use hyper::method::Method::{Connect, Delete, Get, Head, Trace};
-use hyper::net::HttpStream;
+use hyper::header::{Allow, CacheControl, CacheDirective, Connection, ConnectionOption, ContentEncoding, ContentLength, ContentType, Encoding, TransferEncoding};
+use hyper::method::Method::{self, Connect, Delete, Get, Head, Options, Trace};
+use hyper::mime::{Mime, TopLevel, SubLevel};
 use hyper::server::{Handler, Request as HttpRequest, Response as HttpResponse};
 use hyper::status::StatusCode as Status;
 
@@ -14,33 +14,65 @@ use scoped_pool::Scope;
 
 use serde_json::value as json;
 
-use url::Url;
-
-use buffer::Buffer;
+use buffer::{Buffer, BufferPool};
+use connection::{self, Stream as Transport};
 use request::{self, Request};
-use response::{self, Response, Result, Action};
-use router::{Callback, RouterAny};
+use response::{self, Action, Error, Response, Result, RetryAfter};
+use recover;
+use router::Callback;
+use shutdown::InFlight;
+
+use {Dispatch, Edge, Mode};
 
 use crossbeam::sync::chase_lev::{deque, Steal, Stealer, Worker};
 
+use time;
+
+use flate2::read::{GzDecoder, ZlibDecoder};
+
 use std::any::Any;
-use std::io::{self, Write};
+use std::ascii::AsciiExt;
+use std::io::{self, Read, Write};
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+use std::sync::atomic::{AtomicBool, AtomicUsize, ATOMIC_USIZE_INIT, Ordering};
 
 enum Reply {
     Initial(Response, Option<Buffer>),
     Buffer(Buffer),
-    End
+    End,
+
+    /// the response cannot be completed, the connection must be closed
+    Abort
 }
 
 enum Body {
     Empty,
     Some(Buffer),
-    Streaming(Box<Fn(&mut Any, &mut Write)>)
+    Streaming(Box<Fn(&mut Any, &mut Write)>),
+    Deferred(Box<FnMut(response::Deferred)>)
 }
 
 struct Stream {
     worker: Worker<Reply>,
-    control: Control
+    control: Control,
+
+    /// Content-Length declared by the handler (if any)
+    expected: Option<u64>,
+    written: u64,
+
+    /// set when the client has gone away
+    cancelled: Arc<AtomicBool>,
+
+    /// set when the closure writing the stream panicked
+    aborted: bool
+}
+
+/// Sends the response and its body (if any) to the handler.
+///
+/// The response is moved, so it can only be sent once.
+fn send(worker: &mut Worker<Reply>, response: Response, body: Option<Buffer>) {
+    worker.push(Reply::Initial(response, body));
 }
 
 fn notify(control: &Control) {
@@ -51,6 +83,20 @@ fn notify(control: &Control) {
 
 impl Write for Stream {
     fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        if self.cancelled.load(Ordering::SeqCst) {
+            return Err(io::Error::new(io::ErrorKind::BrokenPipe, "client disconnected"));
+        }
+
+        if let Some(expected) = self.expected {
+            if self.written + buf.len() as u64 > expected {
+                let message = format!("cannot write {} bytes, Content-Length is {} and {} bytes were already written",
+                    buf.len(), expected, self.written);
+                error!("{}", message);
+                return Err(io::Error::new(io::ErrorKind::InvalidInput, message));
+            }
+        }
+
+        self.written += buf.len() as u64;
         self.worker.push(Reply::Buffer(buf.to_vec().into()));
         notify(&self.control);
         Ok(buf.len())
@@ -63,6 +109,24 @@ impl Write for Stream {
 
 impl Drop for Stream {
     fn drop(&mut self) {
+        if self.cancelled.load(Ordering::SeqCst) {
+            debug!("streaming response ended after {} bytes, client disconnected", self.written);
+            return;
+        }
+
+        if self.aborted {
+            // do not end the body normally, so that the client knows it is incomplete
+            self.worker.push(Reply::Abort);
+            notify(&self.control);
+            return;
+        }
+
+        if let Some(expected) = self.expected {
+            if self.written != expected {
+                error!("streaming response ended after {} bytes, but Content-Length is {}", self.written, expected);
+            }
+        }
+
         self.worker.push(Reply::End);
         notify(&self.control);
     }
@@ -72,35 +136,50 @@ impl Drop for Stream {
 /// scope outlives handler
 pub struct EdgeHandler<'handler, 'scope: 'handler> {
     scope: &'handler Scope<'scope>,
-    base_url: &'handler Url,
-    routers: &'scope [RouterAny],
+    edge: &'scope Edge,
     request: Option<Request>,
     is_head_request: bool,
+    keep_alive: bool,
+    is_http10: bool,
     buffer: Option<Buffer>,
 
-    handlebars: &'scope Handlebars,
     control: Control,
     worker: Option<Worker<Reply>>,
     stealer: Stealer<Reply>,
-    streaming: bool
+    streaming: bool,
+
+    /// state of the connection, outlives individual requests
+    connection: connection::Connection,
+
+    /// marks the current request as in flight until its response is written
+    in_flight: Option<InFlight>,
+
+    /// set when the client goes away, shared with requests
+    cancelled: Arc<AtomicBool>
 }
 
 impl<'handler, 'scope> EdgeHandler<'handler, 'scope> {
-    pub fn new(scope: &'handler Scope<'scope>, base_url: &'handler Url, routers: &'scope [RouterAny], handlebars: &'scope Handlebars, control: Control) -> EdgeHandler<'handler, 'scope> {
+    pub fn new(scope: &'handler Scope<'scope>, edge: &'scope Edge, control: Control) -> EdgeHandler<'handler, 'scope> {
         let (worker, stealer) = deque();
         EdgeHandler {
             scope: scope,
-            base_url: base_url,
-            routers: routers,
+            edge: edge,
             request: None,
             is_head_request: false,
+            keep_alive: true,
+            is_http10: false,
             buffer: None,
 
-            handlebars: handlebars,
             control: control,
             worker: Some(worker),
             stealer: stealer,
-            streaming: false
+            streaming: false,
+            connection: connection::current().unwrap_or_else(|| {
+                warn!("no connection was read or written before this request, its connection state is not shared");
+                connection::Connection::new()
+            }),
+            in_flight: None,
+            cancelled: Arc::new(AtomicBool::new(false))
         }
     }
 
@@ -108,58 +187,223 @@ impl<'handler, 'scope> EdgeHandler<'handler, 'scope> {
         let mut worker = self.worker.take().unwrap();
         let mut req = self.request.take().unwrap();
 
-        let result = self.routers.iter().filter_map(|router|
-            if let Some(callback) = router.find_callback(&mut req) {
-                Some((router, callback))
+        // 4.3.7 OPTIONS
+        // http://httpwg.org/specs/rfc7231.html#OPTIONS
+        //
+        // An OPTIONS request with an asterisk ("*") as the request-target applies to the server
+        // in general rather than to a specific resource.
+        if *req.method() == Options && req.uri().is_none() {
+            let mut response = Response::new();
+            response.header(Allow(server_methods(self.edge))).len(0);
+            send(&mut worker, response, None);
+            return Next::write();
+        }
+
+        for rewrite in &self.edge.rewrites {
+            rewrite(&mut req);
+        }
+
+        let mut result = self.edge.routers.iter().filter_map(|router|
+            if let Some((route, auto_head)) = router.find_route(&mut req, self.edge.auto_head) {
+                Some((router, route, auto_head))
             } else {
                 None
             }
         ).next();
 
-        if let Some((router, callback)) = result {
-            // add job to scoped pool
+        // no route matches, try the fallbacks registered for this method
+        if result.is_none() {
+            result = self.edge.routers.iter().filter_map(|router|
+                router.find_fallback(&mut req).map(|route| (router, route, false))
+            ).next();
+        }
+
+        if let Some((router, route, auto_head)) = result {
             let ctrl = self.control.clone();
-            let handlebars = self.handlebars;
+            let edge = self.edge;
+            let cancelled = self.cancelled.clone();
+            let run_middleware = !auto_head || edge.middleware_on_auto_head;
 
-            self.scope.execute(move || {
+            let job = move || {
+                #[cfg(feature = "tracing")]
+                let span = ::tracing::info_span!("request", method = %req.method(), path = ?req.path(), route = route.name());
+                #[cfg(feature = "tracing")]
+                let _enter = span.enter();
+
+                let start = Instant::now();
                 let mut response = Response::new();
                 let mut boxed_app = router.new_instance();
                 let app = boxed_app.as_mut();
-                let result =
-                    match *callback {
-                        Callback::Instance(ref f) => {
-                            router.run_middleware(app, &mut req, &mut response);
-                            f(app, &req, &mut response)
+                let mut idempotency_key = None;
+                let mut replay = None;
+                let backtrace = edge.mode == Mode::Development;
+                let outcome = recover::catch(backtrace, || {
+                    if !route.accepts(&req) {
+                        return Err(Status::UnsupportedMediaType.into());
+                    }
+
+                    if let Callback::Instance(_) = *route.callback() {
+                        if run_middleware {
+                            try!(router.run_middleware(app, &mut req, &mut response));
                         }
-                        Callback::Static(ref f) => f(&req, &mut response)
-                    };
+                    }
 
-                let body = process_handle_result(&mut response, result, handlebars);
-                if let Body::Some(body) = body {
-                    response.len(body.len() as u64);
-                    worker.push(Reply::Initial(response, Some(body)));
-                    notify(&ctrl);
-                } else {
-                    worker.push(Reply::Initial(response, None));
+                    // replay the response recorded for this idempotency key (if any),
+                    // once middleware had a chance to reject the request and identify the caller
+                    if let Some(ref store) = edge.idempotency {
+                        idempotency_key = store.key(&req);
+                        replay = idempotency_key.as_ref().and_then(|key| store.get(key));
+                        if replay.is_some() {
+                            return Ok(Action::End(None));
+                        }
+                    }
+
+                    match *route.callback() {
+                        Callback::Instance(ref f) => f(app, &req, &mut response),
+                        Callback::Static(ref f) => f(&req, &mut response),
+                        Callback::Service(ref f) => f(&req, &mut response)
+                    }
+                });
+
+                if let Some((mut response, body)) = replay {
+                    if let Some(ref body) = body {
+                        response.len(body.len() as u64);
+                    }
+                    send(&mut worker, response, body.map(Buffer::from));
                     notify(&ctrl);
+                    return;
+                }
+
+                let result = match outcome {
+                    Ok(result) => result,
+                    Err(panic) => {
+                        // the response may have been partially updated, start over
+                        response = Response::new();
+                        Err(panic_error(route.name(), panic, edge.mode))
+                    }
+                };
 
-                    if let Body::Streaming(closure) = body {
-                        let mut stream = Stream {
-                            worker: worker,
-                            control: ctrl
-                        };
-                        closure(app, &mut stream);
+                let mut body = process_handle_result(&mut response, &req, result, edge);
+
+                // give the handler registered for this status (if any) a chance to produce a body
+                if let Body::Empty = body {
+                    if let Some(handler) = edge.status_handlers.get(&response.status.to_u16()) {
+                        let result = handler(&req, &mut response);
+                        body = process_handle_result(&mut response, &req, result, edge);
+                    }
+                }
+
+                if edge.server_timing {
+                    let mut timings = request::take_timings(&req);
+                    timings.push(("total".to_string(), start.elapsed()));
+                    response.header_raw("Server-Timing", server_timing(&timings));
+                }
+
+                #[cfg(feature = "tracing")]
+                ::tracing::info!(status = %response.status, "handled request");
+
+                if let Some(key) = idempotency_key {
+                    let store = edge.idempotency.as_ref().unwrap();
+                    match body {
+                        Body::Empty => store.insert(key, &response, None),
+                        Body::Some(ref body) => store.insert(key, &response, Some(body.as_ref().to_vec())),
+                        Body::Streaming(_) | Body::Deferred(_) => ()
                     }
                 }
-            });
+
+                // the request body is not needed anymore, recycle it
+                if let Some(mut buffer) = request::take_body(&mut req) {
+                    edge.buffers.put(buffer.take());
+                }
+
+                match body {
+                    Body::Some(mut body) => {
+                        if let Some(range) = response::byte_range(&mut response, &req, body.as_ref()) {
+                            body = range.into();
+                        }
+
+                        response.len(body.len() as u64);
+                        send(&mut worker, response, Some(body));
+                        notify(&ctrl);
+                    }
+                    Body::Deferred(mut closure) => {
+                        // the response is sent whenever the closure (or whoever it gives it to) is done;
+                        // if the closure panics, the deferred response is dropped and a 500 is sent
+                        let deferred = response::deferred(response, Box::new(move |response, body| {
+                            send(&mut worker, response, body.map(Buffer::from));
+                            notify(&ctrl);
+                        }));
+                        if let Err(panic) = recover::catch(backtrace, || closure(deferred)) {
+                            error!("{}", panic_detail(route.name(), panic));
+                        }
+                    }
+                    body => {
+                        let expected = response.headers.get::<ContentLength>().map(|&ContentLength(len)| len);
+                        send(&mut worker, response, None);
+                        notify(&ctrl);
+
+                        if let Body::Streaming(closure) = body {
+                            let mut stream = Stream {
+                                worker: worker,
+                                control: ctrl,
+                                expected: expected,
+                                written: 0,
+                                cancelled: cancelled,
+                                aborted: false
+                            };
+                            if let Err(panic) = recover::catch(backtrace, || closure(app, &mut stream)) {
+                                error!("{}", panic_detail(route.name(), panic));
+                                stream.aborted = true;
+                            }
+                        }
+                    }
+                }
+            };
+
+            match edge.dispatch {
+                // add job to scoped pool
+                Dispatch::OnPool => self.scope.execute(job),
+                Dispatch::Inline => job()
+            }
 
             // and wait for it to notify us
             Next::wait()
         } else {
-            //warn!("route not found for path {:?}", req.path())
+            // 6.5.5 405 Method Not Allowed
+            // http://httpwg.org/specs/rfc7231.html#status.405
+            //
+            // The origin server MUST generate an Allow header field in a 405 response
+            // containing a list of the target resource's currently supported methods.
+            let mut allowed: Vec<Method> = self.edge.routers.iter().flat_map(|router| router.allowed_methods(&req)).collect();
             let mut response = Response::new();
-            response.status(Status::NotFound).content_type("text/plain");
-            worker.push(Reply::Initial(response, Some(format!("not found: {:?}", req.path()).into_bytes().into())));
+            if allowed.is_empty() {
+                //warn!("route not found for path {:?}", req.path())
+                response.status(Status::NotFound).content_type("text/plain");
+                send(&mut worker, response, Some(format!("not found: {:?}", req.path()).into_bytes().into()));
+            } else {
+                if self.edge.auto_head && allowed.contains(&Get) && !allowed.contains(&Head) {
+                    allowed.push(Head);
+                }
+                if self.edge.auto_options && !allowed.contains(&Options) {
+                    allowed.push(Options);
+                }
+
+                // 4.3.7 OPTIONS
+                // http://httpwg.org/specs/rfc7231.html#OPTIONS
+                //
+                // an OPTIONS request to a resource with no OPTIONS route is answered with its
+                // methods, without running any handler or middleware (e.g. authentication)
+                if self.edge.auto_options && *req.method() == Options {
+                    response.header(Allow(allowed)).len(0);
+                    send(&mut worker, response, None);
+                    return Next::write();
+                }
+
+                let message = format!("method {} not allowed for {:?}", req.method(), req.path());
+                response.status(Status::MethodNotAllowed).content_type("text/plain");
+                response.header(Allow(allowed));
+                send(&mut worker, response, Some(message.into_bytes().into()));
+            }
             Next::write()
         }
     }
@@ -168,10 +412,127 @@ impl<'handler, 'scope> EdgeHandler<'handler, 'scope> {
         error!("Bad Request: {}", message);
         let mut response = Response::new();
         response.status(Status::BadRequest).content_type("text/plain; charset=UTF-8");
-        self.worker.as_mut().unwrap().push(Reply::Initial(response, Some(message.to_string().into_bytes().into())));
+        let message = error_message(self.edge.mode, self.request.as_ref(), &mut response, Status::BadRequest, message);
+        send(self.worker.as_mut().unwrap(), response, Some(message.into_bytes().into()));
+        Next::write()
+    }
+
+    fn service_unavailable(&mut self, message: &str, retry_after: Option<u32>) -> Next {
+        warn!("Service Unavailable: {}", message);
+        let mut response = Response::new();
+        response.status(Status::ServiceUnavailable).content_type("text/plain; charset=UTF-8");
+        if let Some(seconds) = retry_after {
+            response.retry_after(RetryAfter::Delay(Duration::from_secs(seconds as u64)));
+        }
+        send(self.worker.as_mut().unwrap(), response, Some(message.to_string().into_bytes().into()));
         Next::write()
     }
 
+    fn reject(&mut self, status: Status, message: &str) -> Next {
+        warn!("{}: {}", status, message);
+        let mut response = Response::new();
+        response.status(status).content_type("text/plain; charset=UTF-8");
+        send(self.worker.as_mut().unwrap(), response, Some(message.to_string().into_bytes().into()));
+        Next::write()
+    }
+
+    fn payload_too_large(&mut self) -> Next {
+        warn!("Payload Too Large");
+        let mut response = Response::new();
+        response.status(Status::PayloadTooLarge).content_type("text/plain; charset=UTF-8");
+        send(self.worker.as_mut().unwrap(), response, Some(b"request body is too large".to_vec().into()));
+        Next::write()
+    }
+
+    /// Ends the current response, which is not in flight anymore.
+    fn end(&mut self) -> Next {
+        self.in_flight = None;
+        Next::end()
+    }
+
+}
+
+/// Returns the value of the Server-Timing header for the given timings,
+/// e.g. "db;dur=12.5, total;dur=20.1" (durations are in milliseconds).
+fn server_timing(timings: &[(String, Duration)]) -> String {
+    timings.iter().map(|&(ref name, duration)| {
+        let millis = duration.as_secs() as f64 * 1000.0 + duration.subsec_nanos() as f64 / 1_000_000.0;
+        format!("{};dur={:.1}", name, millis)
+    }).collect::<Vec<_>>().join(", ")
+}
+
+/// Returns the methods supported by the server in general, i.e. the methods for which
+/// at least one route is registered, as well as OPTIONS (and HEAD when GET is supported,
+/// unless automatic HEAD handling is disabled).
+fn server_methods(edge: &Edge) -> Vec<Method> {
+    let mut methods = vec![Options];
+    for method in edge.routers.iter().flat_map(|router| router.methods()) {
+        if method == Get && edge.auto_head && !methods.contains(&Head) {
+            methods.push(Head);
+        }
+
+        if !methods.contains(&method) {
+            methods.push(method);
+        }
+    }
+    methods
+}
+
+/// Returns the detail of a panic in the handler for the given route, with its backtrace (if any).
+fn panic_detail(route: &str, panic: recover::Panic) -> String {
+    match panic.backtrace {
+        Some(backtrace) => format!("handler for route {} panicked: {}\n{}", route, panic.message, backtrace),
+        None => format!("handler for route {} panicked: {}", route, panic.message)
+    }
+}
+
+/// Returns the error sent to the client when the handler for the given route panicked.
+///
+/// In development mode, the body contains the panic message and backtrace, which are logged;
+/// in production mode it is a generic message, and the panic is only logged when the error
+/// is sent (see `error_message`).
+fn panic_error(route: &str, panic: recover::Panic, mode: Mode) -> Error {
+    let detail = panic_detail(route, panic);
+    if mode == Mode::Development {
+        error!("{}", detail);
+    }
+
+    (Status::InternalServerError, detail).into()
+}
+
+/// Returns the message sent to the client for an error with the given detail.
+///
+/// In development mode, the detail is sent as is. In production mode, it is logged
+/// along with a correlation id, and the client only gets a generic message with this id
+/// (also sent in the `X-Request-Id` header), so that its report can be matched with the logs.
+fn error_message(mode: Mode, req: Option<&Request>, response: &mut Response, status: Status, detail: &str) -> String {
+    match mode {
+        Mode::Development => detail.to_string(),
+        Mode::Production => {
+            let id = correlation_id(req);
+            error!("{} (request id {}): {}", status, id, detail);
+            response.header_raw("X-Request-Id", id.clone());
+            format!("{} (request id {})", status.canonical_reason().unwrap_or("Error"), id)
+        }
+    }
+}
+
+/// Returns the id given by the client (or a proxy) in the `X-Request-Id` header if it is valid,
+/// or else a new unique id.
+fn correlation_id(req: Option<&Request>) -> String {
+    let given = req.and_then(|req| req.headers().get_raw("X-Request-Id"))
+        .and_then(|lines| lines.first())
+        .and_then(|line| ::std::str::from_utf8(line).ok())
+        .and_then(|id| if id.len() <= 64 && id.chars().all(|c| c.is_alphanumeric() || c == '-') {
+            Some(id.to_string())
+        } else {
+            None
+        });
+
+    given.unwrap_or_else(|| {
+        static NEXT_ID: AtomicUsize = ATOMIC_USIZE_INIT;
+        format!("{:x}-{:x}", time::get_time().sec, NEXT_ID.fetch_add(1, Ordering::SeqCst))
+    })
 }
 
 /// Matches the result to update the response and produce a body.
@@ -180,7 +541,7 @@ impl<'handler, 'scope> EdgeHandler<'handler, 'scope> {
 /// end/send/render/redirect depending on the type of result.
 /// Otherwise, if the result is Err, sets the status with the error message as content (if specified).
 /// as the body.
-fn process_handle_result(response: &mut Response, result: Result, handlebars: &Handlebars) -> Body {
+fn process_handle_result(response: &mut Response, req: &Request, result: Result, edge: &Edge) -> Body {
     match result {
         Ok(handler) => {
             match handler.into() {
@@ -195,15 +556,39 @@ fn process_handle_result(response: &mut Response, result: Result, handlebars: &H
                     response.location(url);
                     Body::Empty
                 }
-                Action::Render(name, json) => {
-                    let buffer = render(response, handlebars, &name, &json);
-                    Body::Some(buffer)
+                Action::Render(name, mut json) => {
+                    // add the template globals, without overwriting the data given by the handler
+                    if let json::Value::Object(ref mut map) = json {
+                        for (key, value) in &edge.template_globals {
+                            if !map.contains_key(key) {
+                                map.insert(key.clone(), value.clone());
+                            }
+                        }
+                    }
+
+                    match render(response, &edge.handlebars, &name, &json) {
+                        Ok(buffer) => {
+                            html_defaults(response, edge);
+                            Body::Some(buffer)
+                        }
+                        Err(e) => {
+                            let error = (Status::InternalServerError, format!("could not render template {}: {}", name, e)).into();
+                            process_handle_result(response, req, Err(error), edge)
+                        }
+                    }
                 }
                 Action::Send(body) => {
+                    html_defaults(response, edge);
                     Body::Some(body.into())
                 }
+                Action::Json(json) => {
+                    if !response.headers.has::<ContentType>() {
+                        response.header(ContentType::json());
+                    }
+                    Body::Some(response::json_bytes(&json, edge.json_decimals).into())
+                }
                 Action::SendFile(filename) => {
-                    if let Some(body) = response::send_file(response, filename).map(|vec| vec.into()) {
+                    if let Some(body) = response::send_file(response, req, filename).map(|vec| vec.into()) {
                         Body::Some(body)
                     } else {
                         Body::Empty
@@ -211,8 +596,27 @@ fn process_handle_result(response: &mut Response, result: Result, handlebars: &H
                 }
                 Action::Stream(closure) => {
                     response::set_streaming(response);
+
+                    // discourage proxies from buffering or transforming the stream,
+                    // unless the handler said otherwise
+                    if response.headers.get_raw("X-Accel-Buffering").is_none() {
+                        response.header_raw("X-Accel-Buffering", "no");
+                    }
+                    if !response.headers.has::<CacheControl>() {
+                        response.header(CacheControl(vec![CacheDirective::NoCache, CacheDirective::NoTransform]));
+                    }
+
                     Body::Streaming(closure)
                 }
+                Action::Defer(closure) => {
+                    Body::Deferred(closure)
+                }
+                Action::Respond(reply, body) => {
+                    response.status(reply.status);
+                    response.headers.extend(reply.headers.iter());
+                    html_defaults(response, edge);
+                    Body::Some(body.into())
+                }
             }
         }
         Err(error) => {
@@ -224,63 +628,164 @@ fn process_handle_result(response: &mut Response, result: Result, handlebars: &H
                 Some(message) => {
                     response.status(error.status);
                     response.content_type("text/plain");
-                    Body::Some((&*message).as_bytes().to_vec().into())
+
+                    // the details of server errors are only for developers
+                    let message = if error.status.is_server_error() {
+                        error_message(edge.mode, Some(req), response, error.status, &message)
+                    } else {
+                        message.into_owned()
+                    };
+                    Body::Some(message.into_bytes().into())
                 }
             }
         }
     }
 }
 
+/// Sets the default cache policy (see `Edge::html_cache_policy`) and Content Security Policy
+/// (see `Edge::default_csp`) of HTML responses, unless the handler set these headers.
+fn html_defaults(response: &mut Response, edge: &Edge) {
+    let is_html = match response.headers.get::<ContentType>() {
+        Some(&ContentType(Mime(TopLevel::Text, SubLevel::Html, _))) => true,
+        _ => false
+    };
+    if !is_html {
+        return;
+    }
+
+    if let Some(ref policy) = edge.html_cache_policy {
+        if !response.headers.has::<CacheControl>() {
+            response.cache_control(policy.clone());
+        }
+    }
+
+    if let Some(ref csp) = edge.csp {
+        let has_csp = response.headers.get_raw("Content-Security-Policy").is_some() ||
+            response.headers.get_raw("Content-Security-Policy-Report-Only").is_some();
+        if !has_csp {
+            response.csp(csp);
+        }
+    }
+}
+
 /// Renders the template with the given name using the given data.
 ///
 /// If no Content-Type header is set, the content type is set to `text/html`.
-fn render(response: &mut Response, handlebars: &Handlebars, name: &str, json: &json::Value) -> Buffer {
+fn render(response: &mut Response, handlebars: &Handlebars, name: &str, json: &json::Value) -> ::std::result::Result<Buffer, RenderError> {
     if !response.headers.has::<ContentType>() {
         response.header(ContentType::html());
     }
 
-    let result = handlebars.render(name, json);
-    result.unwrap().into_bytes().into()
+    handlebars.render(name, json).map(|html| html.into_bytes().into())
 }
 
 /// Implements Handler for our EdgeHandler.
-impl<'handler, 'scope> Handler<HttpStream> for EdgeHandler<'handler, 'scope> {
+impl<'handler, 'scope> Handler<Transport> for EdgeHandler<'handler, 'scope> {
     fn on_request(&mut self, req: HttpRequest) -> Next {
         debug!("on_request");
 
-        match request::new(&self.base_url, req) {
-            Ok(req) => {
-                let result = check_request(&req, &mut self.buffer);
+        match request::new(&self.edge.base_url, req, self.edge.path_decoding, self.edge.query_decoding) {
+            Ok(mut req) => {
+                request::set_connection(&mut req, self.connection.clone());
+                request::set_trust_proxy(&mut req, self.edge.trust_proxy);
+                request::set_secret(&mut req, self.edge.secret.clone());
+                request::set_cancelled(&mut req, self.cancelled.clone());
+                request::set_body_limit(&mut req, self.edge.max_body_size);
+                request::set_empty_json_as_null(&mut req, self.edge.empty_json_as_null);
+                let result = check_request(&req, &mut self.buffer, &self.edge.buffers);
+                let too_large = match (self.edge.max_body_size, req.headers().get::<ContentLength>()) {
+                    (Some(limit), Some(&ContentLength(len))) => len > limit as u64,
+                    _ => false
+                };
                 self.is_head_request = *req.method() == Head;
+                self.keep_alive = keep_alive(&req);
+                self.is_http10 = *req.version() == Http10 || *req.version() == Http09;
+                let uri_length = req.uri().map_or(0, |url| url.path().len() + url.query().map_or(0, str::len));
                 self.request = Some(req);
 
+                // 3.1.1 Request Line
+                // http://httpwg.org/specs/rfc7230.html#request.line
+                //
+                // A server that receives a request-target longer than any URI it wishes to parse
+                // MUST respond with a 414 (URI Too Long) status code.
+                if uri_length > self.edge.max_uri_length {
+                    self.keep_alive = false;
+                    return self.reject(Status::UriTooLong, "request target is too long");
+                }
+
+                // do not accept new requests while shutting down
+                if self.edge.shutdown.is_requested() {
+                    self.keep_alive = false;
+                    return self.service_unavailable("server is shutting down", None);
+                }
+
+                // shed the request rather than queueing it when too many requests are in flight
+                self.in_flight = self.edge.shutdown.try_enter(self.edge.max_requests);
+                if self.in_flight.is_none() {
+                    // requests to high-priority routes (e.g. health checks) are served anyway
+                    let edge = self.edge;
+                    let priority = self.request.as_ref().map_or(false, |req|
+                        edge.routers.iter().any(|router| router.is_priority(req)));
+                    if !priority {
+                        return self.service_unavailable("too many concurrent requests", Some(1));
+                    }
+                    self.in_flight = self.edge.shutdown.try_enter(None);
+                }
+
                 match result {
-                    Err(msg) => self.bad_request(msg),
+                    Err(msg) => {
+                        // the framing of the message is invalid, the connection cannot be reused
+                        self.keep_alive = false;
+                        self.bad_request(msg)
+                    }
                     Ok(false) => self.callback(),
-                    Ok(true) => Next::read()
+                    Ok(true) if too_large => {
+                        self.buffer = None;
+                        self.keep_alive = false;
+                        self.payload_too_large()
+                    }
+                    Ok(true) => {
+                        if let Some(ref mut buffer) = self.buffer {
+                            buffer.set_limit(self.edge.max_body_size);
+                        }
+                        Next::read()
+                    }
                 }
             }
             Err(error) => {
-                self.bad_request(&error.to_string())
+                self.keep_alive = false;
+                self.bad_request(&error)
             }
         }
     }
 
-    fn on_request_readable(&mut self, transport: &mut Decoder<HttpStream>) -> Next {
+    fn on_request_readable(&mut self, transport: &mut Decoder<Transport>) -> Next {
         debug!("on_request_readable");
 
         // we can only get here if self.buffer = Some(...), or there is a bug
-        {
-            let body = self.buffer.as_mut().unwrap();
-            if let Ok(keep_reading) = body.read_from(transport) {
-                if keep_reading {
-                    return Next::read();
-                }
+        let result = self.buffer.as_mut().unwrap().read_from(transport);
+        match result {
+            Ok(true) => return Next::read(),
+            Err(ref e) if e.kind() == io::ErrorKind::InvalidData => {
+                // the body exceeds the maximum size
+                self.buffer = None;
+                self.keep_alive = false;
+                return self.payload_too_large();
             }
+            _ => ()
         }
 
-        // move body to the request
-        request::set_body(self.request.as_mut(), self.buffer.take());
+        // decode the body (if compressed) and move it to the request
+        let body = self.buffer.take().unwrap();
+        let result = decode_body(self.request.as_ref().unwrap().headers(), body, self.edge.max_body_size);
+        match result {
+            Ok(body) => request::set_body(self.request.as_mut(), Some(body)),
+            Err((Status::PayloadTooLarge, _)) => {
+                self.keep_alive = false;
+                return self.payload_too_large();
+            }
+            Err((status, message)) => return self.reject(status, message)
+        }
         self.callback()
     }
 
@@ -296,6 +801,22 @@ impl<'handler, 'scope> Handler<HttpStream> for EdgeHandler<'handler, 'scope> {
                 // set status and headers
                 res.set_status(status);
                 *res.headers_mut() = response.headers;
+                if !self.edge.verbatim_header_names {
+                    canonicalize_header_names(res.headers_mut());
+                }
+
+                // 6.3 Persistence
+                // http://httpwg.org/specs/rfc7230.html#persistent.connections
+                //
+                // close the connection if the client asked for it (or did not ask for
+                // keep-alive in HTTP/1.0), and tell HTTP/1.0 clients when we keep it alive
+                let close = res.headers().get::<Connection>().map_or(false, |&Connection(ref options)|
+                    options.contains(&ConnectionOption::Close));
+                if !self.keep_alive && !close {
+                    res.headers_mut().set(Connection::close());
+                } else if self.keep_alive && !close && self.is_http10 {
+                    res.headers_mut().set(Connection::keep_alive());
+                }
 
                 // 3.3.2 Content-Length
                 // http://httpwg.org/specs/rfc7230.html#header.content-length
@@ -312,7 +833,7 @@ impl<'handler, 'scope> Handler<HttpStream> for EdgeHandler<'handler, 'scope> {
                     // even in 304 and response to HEAD
                     // because we cannot guarantee that the length is the same
                     res.headers_mut().remove::<ContentLength>();
-                    return Next::end();
+                    return self.end();
                 }
 
                 match body {
@@ -322,11 +843,12 @@ impl<'handler, 'scope> Handler<HttpStream> for EdgeHandler<'handler, 'scope> {
                             Next::wait()
                         } else {
                             debug!("has no body, ending");
-                            Next::end()
+                            self.end()
                         }
                     }
-                    Some(body) => {
+                    Some(mut body) => {
                         debug!("has body");
+                        body.set_chunk_size(self.edge.buffers.write_size());
                         self.buffer = Some(body);
                         Next::write()
                     }
@@ -339,17 +861,25 @@ impl<'handler, 'scope> Handler<HttpStream> for EdgeHandler<'handler, 'scope> {
         }
     }
 
-    fn on_response_writable(&mut self, transport: &mut Encoder<HttpStream>) -> Next {
+    fn on_response_writable(&mut self, transport: &mut Encoder<Transport>) -> Next {
         debug!("on_response_writable");
 
         loop {
             self.buffer = match self.buffer {
                 None => {
                     match self.stealer.steal() {
-                        Steal::Data(Reply::Buffer(body)) => Some(body),
+                        Steal::Data(Reply::Buffer(mut body)) => {
+                            body.set_chunk_size(self.edge.buffers.write_size());
+                            Some(body)
+                        }
                         Steal::Data(Reply::End) => {
                             debug!("done writing");
-                            return Next::end();
+                            return self.end();
+                        }
+                        Steal::Data(Reply::Abort) => {
+                            debug!("response aborted, closing connection");
+                            self.in_flight = None;
+                            return Next::remove();
                         }
                         Steal::Empty => {
                             // no data yet, wait for notification
@@ -364,13 +894,17 @@ impl<'handler, 'scope> Handler<HttpStream> for EdgeHandler<'handler, 'scope> {
                             return Next::write();
                         } else {
                             // this buffer has been fully written to the transport
+                            self.edge.buffers.put(buffer.take());
                             if self.streaming {
                                 None
                             } else {
+                                self.in_flight = None;
                                 return Next::end();
                             }
                         }
                     } else {
+                        // the client went away
+                        self.cancelled.store(true, Ordering::SeqCst);
                         return Next::remove();
                     }
                 }
@@ -380,15 +914,57 @@ impl<'handler, 'scope> Handler<HttpStream> for EdgeHandler<'handler, 'scope> {
 
     fn on_error(&mut self, err: HyperError) -> Next {
         debug!("on_error {:?}", err);
+        self.cancelled.store(true, Ordering::SeqCst);
         Next::remove()
     }
 
-    fn on_remove(self, _transport: HttpStream) {
+    fn on_remove(self, _transport: Transport) {
         debug!("on_remove");
+        self.cancelled.store(true, Ordering::SeqCst);
     }
 }
 
-fn check_request(req: &Request, buffer: &mut Option<Buffer>) -> ::std::result::Result<bool, &'static str> {
+/// Renames the headers whose name is in lowercase to use the canonical casing,
+/// where each word starts with an uppercase letter.
+fn canonicalize_header_names(headers: &mut Headers) {
+    let names: Vec<String> = headers.iter()
+        .map(|header| header.name().to_string())
+        .filter(|name| name.chars().any(char::is_lowercase) && !name.chars().any(char::is_uppercase))
+        .collect();
+
+    for name in names {
+        let canonical = name.split('-').map(|word| {
+            let mut chars = word.chars();
+            match chars.next() {
+                Some(first) => first.to_ascii_uppercase().to_string() + chars.as_str(),
+                None => String::new()
+            }
+        }).collect::<Vec<_>>().join("-");
+
+        let raw = headers.get_raw(&name).map(|raw| raw.to_vec());
+        if let Some(raw) = raw {
+            headers.remove_raw(&name);
+            headers.set_raw(canonical, raw);
+        }
+    }
+}
+
+/// Returns `true` if the connection should be kept alive after this request.
+///
+/// HTTP/1.1 connections are persistent unless the client sends `Connection: close`,
+/// HTTP/1.0 connections are closed unless the client sends `Connection: keep-alive`.
+fn keep_alive(req: &Request) -> bool {
+    let options = req.headers().get::<Connection>().map_or(&[][..], |&Connection(ref options)| &options[..]);
+    if options.contains(&ConnectionOption::Close) {
+        false
+    } else if *req.version() == Http10 || *req.version() == Http09 {
+        options.contains(&ConnectionOption::KeepAlive)
+    } else {
+        true
+    }
+}
+
+fn check_request(req: &Request, buffer: &mut Option<Buffer>, pool: &BufferPool) -> ::std::result::Result<bool, &'static str> {
     let headers = req.headers();
     let http1x = { let version = req.version(); *version == Http09 || *version == Http10 || *version == Http11 };
 
@@ -398,6 +974,13 @@ fn check_request(req: &Request, buffer: &mut Option<Buffer>) -> ::std::result::R
     //
     let len =
         if let Some(&TransferEncoding(ref codings)) = headers.get() {
+            if headers.get_raw("Content-Length").is_some() {
+                // A message with both Transfer-Encoding and Content-Length might indicate
+                // an attempt to perform request smuggling or response splitting
+                // and ought to be handled as an error.
+                return Err("Transfer-Encoding and Content-Length must not both be present");
+            }
+
             if codings.last() != Some(&Encoding::Chunked) {
                 // 3. If a Transfer-Encoding header field is present in a request and
                 // the chunked transfer coding is not the final encoding,
@@ -459,7 +1042,46 @@ fn check_request(req: &Request, buffer: &mut Option<Buffer>) -> ::std::result::R
     } else {
         // payload is allowed
         // if Content-Length is known create buffer with fixed size, otherwise allocate growable buffer
-        *buffer = Some(len.map_or(Buffer::new(), |len| Buffer::new_fixed(len)));
+        *buffer = Some(len.map_or_else(|| pool.buffer(), |len| pool.buffer_fixed(len)));
         Ok(true)
     }
 }
+
+/// Decodes the body of a request sent with a Content-Encoding (gzip or deflate),
+/// so that handlers see the original content.
+///
+/// The limit (if any) applies to the decoded size, so that a small compressed body
+/// cannot expand into an arbitrarily large one.
+fn decode_body(headers: &Headers, mut body: Buffer, limit: Option<usize>) -> ::std::result::Result<Buffer, (Status, &'static str)> {
+    let codings = match headers.get::<ContentEncoding>() {
+        Some(&ContentEncoding(ref codings)) => codings.clone(),
+        None => return Ok(body)
+    };
+
+    // codings are listed in the order in which they were applied
+    let mut content = body.take();
+    for coding in codings.iter().rev() {
+        let decoded = match *coding {
+            Encoding::Identity => continue,
+            Encoding::Gzip => {
+                let decoder = try!(GzDecoder::new(&content[..]).map_err(|_| (Status::BadRequest, "invalid gzip body")));
+                try!(decode(decoder, limit))
+            }
+            Encoding::Deflate => try!(decode(ZlibDecoder::new(&content[..]), limit)),
+            _ => return Err((Status::UnsupportedMediaType, "unsupported Content-Encoding, expected gzip or deflate"))
+        };
+        content = decoded;
+    }
+    Ok(content.into())
+}
+
+/// Reads everything from the given decoder, failing with 413 when more than `limit` bytes are decoded.
+fn decode<R: Read>(decoder: R, limit: Option<usize>) -> ::std::result::Result<Vec<u8>, (Status, &'static str)> {
+    let mut decoded = Vec::new();
+    let max = limit.map_or(u64::max_value(), |limit| limit as u64 + 1);
+    try!(decoder.take(max).read_to_end(&mut decoded).map_err(|_| (Status::BadRequest, "invalid compressed body")));
+    if limit.map_or(false, |limit| decoded.len() > limit) {
+        return Err((Status::PayloadTooLarge, "request body is too large"));
+    }
+    Ok(decoded)
+}
@@ -0,0 +1,122 @@
+//! Replay of responses to requests carrying an Idempotency-Key header.
+
+use hyper::Headers;
+use hyper::status::StatusCode as Status;
+
+use request::Request;
+use response::Response;
+
+use std::collections::{HashMap, VecDeque};
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+/// A response recorded for an idempotency key.
+struct Entry {
+    recorded: Instant,
+    status: Status,
+    headers: Headers,
+    body: Option<Vec<u8>>
+}
+
+struct Entries {
+    map: HashMap<String, Entry>,
+
+    /// keys in insertion order, so that the oldest entry can be evicted when the store is full
+    order: VecDeque<String>
+}
+
+/// Returns the identity of the caller of a request, if known.
+pub type Identity = Box<Fn(&Request) -> Option<String> + Sync>;
+
+/// Bounded in-memory store of responses indexed by idempotency key.
+///
+/// Keys are made of the identity of the caller, the method, the path, and the value of
+/// the Idempotency-Key header, so the same key sent to two different endpoints does not
+/// replay the wrong response, and a caller cannot get the response recorded for another one.
+/// Note that two identical requests received at the same time may both reach the handler.
+pub struct IdempotencyStore {
+    capacity: usize,
+    window: Duration,
+    identity: Identity,
+    entries: Mutex<Entries>
+}
+
+impl IdempotencyStore {
+    pub fn new(capacity: usize, window: Duration, identity: Identity) -> IdempotencyStore {
+        IdempotencyStore {
+            capacity: capacity,
+            window: window,
+            identity: identity,
+            entries: Mutex::new(Entries {
+                map: HashMap::new(),
+                order: VecDeque::new()
+            })
+        }
+    }
+
+    /// Returns the key for the given request, if it has an Idempotency-Key header,
+    /// its method is not safe (safe methods are idempotent by definition),
+    /// and the identity of its caller is known.
+    pub fn key(&self, req: &Request) -> Option<String> {
+        if req.method().safe() {
+            return None;
+        }
+
+        let value = match req.headers().get_raw("Idempotency-Key")
+            .and_then(|values| values.first())
+            .and_then(|value| ::std::str::from_utf8(value).ok()) {
+            Some(value) => value.trim(),
+            None => return None
+        };
+
+        (self.identity)(req).map(|identity|
+            // the identity is length-prefixed so that it cannot be confused with the rest of the key
+            format!("{}:{} {} /{} {}", identity.len(), identity, req.method(), req.path().join("/"), value))
+    }
+
+    /// Returns a copy of the response recorded for the given key, unless it is older than the window.
+    pub fn get(&self, key: &str) -> Option<(Response, Option<Vec<u8>>)> {
+        let entries = self.entries.lock().unwrap();
+        entries.map.get(key).and_then(|entry|
+            if entry.recorded.elapsed() < self.window {
+                debug!("replaying response for idempotency key {}", key);
+                let mut response = Response::new();
+                response.status(entry.status);
+                response.headers = entry.headers.clone();
+                Some((response, entry.body.clone()))
+            } else {
+                None
+            }
+        )
+    }
+
+    /// Records the given response and body for the given key, evicting the oldest entry if the store is full.
+    ///
+    /// Only successful responses are recorded, so that a request that failed can be retried;
+    /// cookies are not recorded, so that a replay never sets them again.
+    pub fn insert(&self, key: String, response: &Response, body: Option<Vec<u8>>) {
+        if self.capacity == 0 || !response.status.is_success() {
+            return;
+        }
+
+        let mut headers = response.headers.clone();
+        headers.remove_raw("Set-Cookie");
+
+        let mut entries = self.entries.lock().unwrap();
+        if entries.map.contains_key(&key) {
+            entries.order.retain(|existing| *existing != key);
+        } else if entries.map.len() >= self.capacity {
+            if let Some(oldest) = entries.order.pop_front() {
+                entries.map.remove(&oldest);
+            }
+        }
+
+        entries.order.push_back(key.clone());
+        entries.map.insert(key, Entry {
+            recorded: Instant::now(),
+            status: response.status,
+            headers: headers,
+            body: body
+        });
+    }
+}
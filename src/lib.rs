@@ -62,14 +62,14 @@
 //!
 //! Under the hood, Edge uses the asynchronous version of Hyper. This means that to get the maximum
 //! performance, you should avoid waiting in a handler, so that other requests
-//! can be served as soon as possible. In that example, the handler waits in a separate thread before sending
-//! the response.
+//! can be served as soon as possible. In that example, the handler sets the headers of the response,
+//! then defers the body: the response is moved to a separate thread that waits before sending it.
+//! Once deferred, the headers cannot be modified anymore, and the response can only be sent once.
 //!
 //! ```no_run
-//! #[macro_use]
 //! extern crate edge;
 //!
-//! use edge::{Edge, Request, Response, Result, Router, Status};
+//! use edge::{defer, Edge, Request, Response, Result, Router};
 //! use std::thread;
 //! use std::time::Duration;
 //!
@@ -77,11 +77,14 @@
 //! struct AsyncHello;
 //! impl AsyncHello {
 //!     fn hello(&mut self, _req: &Request, res: &mut Response) -> Result {
-//!         println!("waiting 1 second");
-//!         thread::sleep(Duration::from_secs(1));
-//!
 //!         res.content_type("text/plain");
-//!         ok!(Status::Ok, "Hello, world!")
+//!         defer(|deferred| {
+//!             thread::spawn(move || {
+//!                 println!("waiting 1 second");
+//!                 thread::sleep(Duration::from_secs(1));
+//!                 deferred.send("Hello, world!");
+//!             });
+//!         })
 //!     }
 //! }
 //!
@@ -142,17 +145,25 @@
 //! see the example for asynchronous handling above.
 //! ```
 
+extern crate backtrace;
 extern crate crossbeam;
+extern crate crypto;
+extern crate flate2;
 extern crate handlebars;
 extern crate hyper;
+extern crate mio;
+extern crate net2;
 extern crate num_cpus;
 extern crate pulldown_cmark;
 extern crate scoped_pool;
 extern crate serde;
+extern crate time;
 extern crate url;
 
 #[macro_use]
 extern crate log;
+#[cfg(feature = "tracing")]
+extern crate tracing;
 pub extern crate serde_json;
 
 pub use hyper::header as header;
@@ -162,9 +173,10 @@ pub use hyper::status::StatusCode as Status;
 /// serde_json crate
 pub use serde_json as json;
 
+pub use url::Url;
+
 use handlebars::{Context, Handlebars, Helper, RenderContext, RenderError};
 
-use hyper::net::HttpListener;
 use hyper::server::Server;
 
 use pulldown_cmark::Parser;
@@ -173,33 +185,130 @@ use pulldown_cmark::html;
 
 use scoped_pool::Pool;
 
-use url::Url;
+use serde_json::value::ToJson;
 
+use std::collections::{BTreeMap, HashMap};
+use std::env;
 use std::fs::read_dir;
-use std::io::Result as IoResult;
-use std::net::ToSocketAddrs;
+use std::io::{self, Result as IoResult};
+use std::net::{SocketAddr, TcpListener, ToSocketAddrs};
 use std::path::{Path, PathBuf};
 use std::result;
+use std::sync::mpsc;
+use std::time::Duration;
 
+mod assets;
 mod buffer;
 mod client;
+mod conditional;
+mod connection;
+mod cookie;
+mod csp;
+mod extensions;
+mod forwarded;
 mod handler;
+mod patch;
+mod idempotency;
+mod listener;
+mod multipart;
+mod proxy;
+mod recover;
 mod router;
 mod request;
 mod response;
+mod shutdown;
+mod signed;
+mod urlencoded;
 
-pub use client::Client;
-pub use request::Request;
-pub use response::{Response, Result, Action, stream};
+pub use assets::Assets;
+pub use client::{Client, ClientError};
+pub use connection::Connection;
+pub use cookie::{CookieBuilder, SameSite};
+pub use csp::Csp;
+pub use extensions::Extensions;
+pub use multipart::{Multipart, Part};
+pub use forwarded::Forwarded;
+pub use patch::{Operation, Patch, PatchError};
+pub use proxy::proxy;
+pub use request::{FetchMode, FetchSite, Request, UrlDecoding};
+pub use response::{Response, Result, Action, Error, CachePolicy, Deferred, Disposition, FileOptions, MixedReplace, RetryAfter, append_file, defer, mixed_replace, ndjson, stream};
 pub use router::{Router};
+pub use shutdown::Shutdown;
 
 /// Structure for an Edge application.
 pub struct Edge {
     base_url: Url,
     routers: Vec<router::RouterAny>,
-    handlebars: Handlebars
+    handlebars: Handlebars,
+    views: PathBuf,
+    template_extension: String,
+    status_handlers: HashMap<u16, router::Static>,
+    idempotency: Option<idempotency::IdempotencyStore>,
+    mode: Mode,
+    dispatch: Dispatch,
+    buffers: buffer::BufferPool,
+    shutdown: Shutdown,
+    drain_timeout: Duration,
+    max_accept_backoff: Duration,
+    trust_proxy: forwarded::ProxyTrust,
+    max_requests: Option<usize>,
+    max_body_size: Option<usize>,
+    max_uri_length: usize,
+    path_decoding: UrlDecoding,
+    query_decoding: UrlDecoding,
+    json_decimals: Option<usize>,
+    html_cache_policy: Option<CachePolicy>,
+    csp: Option<Csp>,
+    prewarm_templates: bool,
+    auto_head: bool,
+    auto_options: bool,
+    middleware_on_auto_head: bool,
+    empty_json_as_null: bool,
+    verbatim_header_names: bool,
+    debug_endpoints: bool,
+    reuse_port: bool,
+    secret: Option<signed::Secret>,
+    listener: Option<(TcpListener, SocketAddr)>,
+    rewrites: Vec<Box<Fn(&mut Request) + Sync>>,
+    server_timing: bool,
+    template_globals: BTreeMap<String, json::Value>
+}
+
+/// Mode in which an Edge application runs.
+///
+/// The mode controls how much detail about errors is sent to clients:
+/// in development, the details of server errors (handlers that panic or return a 5xx error,
+/// templates that fail to render) and of malformed requests are sent in the response;
+/// in production they are only logged along with a correlation id, and the response
+/// only has a generic message with this id.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Mode {
+    Development,
+    Production
+}
+
+/// Where request handlers run.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Dispatch {
+    /// Handlers run on the threads of a pool, so that a slow or blocking handler
+    /// does not prevent the server from accepting and reading other requests.
+    OnPool,
+
+    /// Handlers run on the thread of the event loop that read the request,
+    /// which avoids the hand-off to the pool but blocks the loop while they run.
+    /// Only suitable for handlers that never block.
+    Inline
 }
 
+/// How long browsers may cache the favicon, in seconds (one week).
+const FAVICON_MAX_AGE: u32 = 7 * 24 * 3600;
+
+/// Default maximum length of the path and query of requests.
+const DEFAULT_MAX_URI_LENGTH: usize = 8 * 1024;
+
+/// Default maximum number of buffers kept for reuse across requests.
+const DEFAULT_POOLED_BUFFERS: usize = 64;
+
 /// ok!() means Ok(Action::End).
 /// ok!(expr) returns Ok(From::from(expr))
 #[macro_export]
@@ -220,15 +329,134 @@ impl Edge {
     /// Creates an Edge application using the given address and application.
     pub fn new(addr: &str) -> Edge {
         let mut handlebars = Handlebars::new();
-        init_handlebars(&mut handlebars).unwrap();
+        init_handlebars(&mut handlebars);
 
         Edge {
             base_url: Url::parse(&("http://".to_string() + addr)).unwrap(),
             routers: Vec::new(),
-            handlebars: handlebars
+            handlebars: handlebars,
+            views: PathBuf::from("views"),
+            template_extension: "hbs".to_string(),
+            status_handlers: HashMap::new(),
+            idempotency: None,
+            mode: Mode::Production,
+            dispatch: Dispatch::OnPool,
+            buffers: buffer::BufferPool::new(DEFAULT_POOLED_BUFFERS),
+            shutdown: Shutdown::new(),
+            drain_timeout: Duration::from_secs(shutdown::DEFAULT_DRAIN_TIMEOUT),
+            max_accept_backoff: Duration::from_secs(listener::DEFAULT_MAX_ACCEPT_BACKOFF),
+            trust_proxy: forwarded::ProxyTrust::Nobody,
+            max_requests: None,
+            max_body_size: None,
+            max_uri_length: DEFAULT_MAX_URI_LENGTH,
+            path_decoding: UrlDecoding::Strict,
+            query_decoding: UrlDecoding::Lenient,
+            json_decimals: None,
+            html_cache_policy: Some(CachePolicy::new().private().no_cache()),
+            csp: None,
+            prewarm_templates: false,
+            auto_head: true,
+            auto_options: true,
+            middleware_on_auto_head: true,
+            empty_json_as_null: false,
+            verbatim_header_names: false,
+            debug_endpoints: false,
+            reuse_port: false,
+            secret: None,
+            listener: None,
+            rewrites: Vec::new(),
+            server_timing: false,
+            template_globals: BTreeMap::new()
+        }
+    }
+
+    /// Creates an Edge application listening on the port given by the `PORT` environment variable
+    /// (on all interfaces), as is customary on hosting platforms, or on the given address
+    /// if the variable is not set.
+    ///
+    /// Panics if `PORT` is set but is not a valid port number.
+    pub fn from_env(default_addr: &str) -> Edge {
+        match env::var("PORT") {
+            Ok(port) => {
+                let port: u16 = port.trim().parse().unwrap_or_else(|_| panic!("invalid PORT environment variable: {:?}", port));
+                Edge::new(&format!("0.0.0.0:{}", port))
+            }
+            Err(_) => Edge::new(default_addr)
         }
     }
 
+    /// Mounts the given in-memory assets at the given path.
+    ///
+    /// For example, assets mounted at "/static" with a "css/app.css" asset
+    /// serve that asset for requests to "/static/css/app.css".
+    pub fn mount_assets(&mut self, mount: &str, assets: Assets) {
+        let mut router = Router::<()>::new();
+        router.mount_service("/", move |req, res| assets.serve(req, res));
+        self.mount(mount, router);
+    }
+
+    /// Serves the given icon at "/favicon.ico", with caching headers so that browsers
+    /// do not request it again for a week.
+    ///
+    /// The icon is typically embedded in the binary with `include_bytes!("favicon.ico")`.
+    pub fn favicon(&mut self, icon: &'static [u8]) {
+        let mut assets = Assets::new();
+        assets.insert("favicon.ico", icon, "image/x-icon");
+        assets.cache_control(CachePolicy::new().public().max_age(FAVICON_MAX_AGE));
+
+        let mut router = Router::<()>::new();
+        router::get_service(&mut router, "/favicon.ico", move |req, res| assets.serve_path("favicon.ico", req, res));
+        self.mount("/", router);
+    }
+
+    /// Sets the directory where templates are looked up (default is "views").
+    ///
+    /// Partials are loaded from the "partials" subdirectory of this directory when the server starts.
+    pub fn views_dir<P: AsRef<Path>>(&mut self, path: P) {
+        self.views = path.as_ref().to_path_buf();
+    }
+
+    /// Sets the extension of template files (default is "hbs"), which may have several parts,
+    /// e.g. "hbs.html" for templates named like "index.hbs.html".
+    ///
+    /// The extension applies to templates registered with `register_template`, partials,
+    /// and the templates compiled by `prewarm_templates`.
+    pub fn template_extension(&mut self, extension: &str) {
+        self.template_extension = extension.trim_left_matches('.').to_string();
+    }
+
+    /// Compiles all the templates of the views directory when the server starts (default is false).
+    ///
+    /// Every template file of the views directory (see `template_extension`) is registered
+    /// under its name without the extension, so templates do not need to be registered one by one, and `start`
+    /// fails with an error if one of them (or a partial) does not compile, instead of
+    /// the first request that renders it.
+    pub fn prewarm_templates(&mut self, prewarm: bool) {
+        self.prewarm_templates = prewarm;
+    }
+
+    /// Registers a hook that can rewrite requests before they are routed.
+    ///
+    /// Hooks are called in the order in which they were registered, and can change the path
+    /// used for routing and set parameters. For example, to take the language from the path:
+    ///
+    /// ```
+    /// use edge::Edge;
+    ///
+    /// let mut edge = Edge::new("0.0.0.0:3000");
+    /// edge.rewrite(|req| {
+    ///     let lang = req.path().first().cloned().unwrap_or_default();
+    ///     if lang == "en" || lang == "fr" {
+    ///         let rest = req.path()[1..].join("/");
+    ///         req.set_path(&format!("/{}", rest));
+    ///         req.set_param("lang", lang);
+    ///     }
+    /// });
+    /// ```
+    pub fn rewrite<F>(&mut self, hook: F) where F: Fn(&mut Request) + Sync + 'static {
+        self.rewrites.push(Box::new(hook));
+    }
+
     /// Mounts the given router at the given path.
     ///
     /// Use "/" to mount the router at the root.
@@ -238,12 +466,332 @@ impl Edge {
         self.routers.push(router)
     }
 
-    // Registers a template with the given name.
+    /// Sets the mode of this application (default is production).
+    pub fn mode(&mut self, mode: Mode) {
+        self.mode = mode;
+    }
+
+    /// Sets where request handlers run (default is `Dispatch::OnPool`).
+    pub fn handler_dispatch(&mut self, dispatch: Dispatch) {
+        self.dispatch = dispatch;
+    }
+
+    /// Sets the maximum number of buffers kept for reuse across requests (0 disables pooling).
+    pub fn buffer_pool(&mut self, max_buffers: usize) {
+        self.buffers.set_max_buffers(max_buffers);
+    }
+
+    /// Sets the size of reads from and writes to connections (default is 4 KB for both).
+    ///
+    /// Request bodies of unknown length are read in chunks of `read_size` bytes,
+    /// and at most `write_size` bytes of a response are written at once.
+    /// Larger sizes mean fewer system calls for large bodies, but more memory per connection.
+    ///
+    /// Panics if a size is less than 512 bytes or more than 1 MB.
+    pub fn buffer_sizes(&mut self, read_size: usize, write_size: usize) {
+        for &(name, size) in &[("read", read_size), ("write", write_size)] {
+            assert!(size >= buffer::MIN_BUF_SIZE && size <= buffer::MAX_BUF_SIZE,
+                "{} buffer size must be between {} and {} bytes, got {}", name, buffer::MIN_BUF_SIZE, buffer::MAX_BUF_SIZE, size);
+        }
+        self.buffers.set_sizes(read_size, write_size);
+    }
+
+    /// Returns a handle that can be used to shut down this application once started.
+    pub fn shutdown_handle(&self) -> Shutdown {
+        self.shutdown.clone()
+    }
+
+    /// Sets how long to wait for in-flight requests to complete on shutdown (default is 30 seconds).
+    ///
+    /// Connections still busy when the timeout elapses are closed. The number of requests
+    /// in flight is given by `Shutdown::in_flight`.
+    pub fn drain_timeout(&mut self, timeout: Duration) {
+        self.drain_timeout = timeout;
+    }
+
+    /// Sets the maximum delay before accepting connections again after `accept` fails
+    /// (default is 1 second).
+    ///
+    /// When accepting a connection fails, for instance because the process has run out
+    /// of file descriptors, the error is logged and the server waits before trying again,
+    /// doubling the delay after each consecutive failure up to this maximum.
+    /// Connections are accepted on dedicated threads, so requests on connections that are
+    /// already open keep being served in the meantime.
+    pub fn max_accept_backoff(&mut self, max: Duration) {
+        self.max_accept_backoff = max;
+    }
+
+    /// Sets whether the forwarding headers added by proxies (`Forwarded`, `X-Forwarded-For`,
+    /// `X-Forwarded-Proto`) are trusted (default is false).
+    ///
+    /// Only enable this when the server is behind a proxy that sets these headers,
+    /// otherwise clients can forge them.
+    pub fn trust_proxy(&mut self, trust: bool) {
+        self.trust_proxy = if trust { forwarded::ProxyTrust::All } else { forwarded::ProxyTrust::Nobody };
+    }
+
+    /// Trusts the forwarding headers added by the given number of proxies in front of the server.
+    ///
+    /// Each proxy appends the address of its client to `X-Forwarded-For` (or `Forwarded`),
+    /// so the client is identified by the entry that many positions from the end of the list;
+    /// the entries before it may have been forged by the client and are ignored.
+    /// For instance with a single load balancer, `trusted_proxies(1)` uses the last entry.
+    pub fn trusted_proxies(&mut self, hops: usize) {
+        self.trust_proxy = forwarded::ProxyTrust::Hops(hops);
+    }
+
+    /// Sets the maximum number of requests handled concurrently (default is no limit).
+    ///
+    /// Requests received while this many requests are in flight are not queued,
+    /// they get a 503 Service Unavailable response with a `Retry-After` header,
+    /// unless they match a high-priority route (see `Router::prioritize`).
+    pub fn max_concurrent_requests(&mut self, max: usize) {
+        self.max_requests = Some(max);
+    }
+
+    /// Sets the maximum size of request bodies in bytes (default is no limit).
+    ///
+    /// Requests with a larger body get a 413 Payload Too Large response, either right away
+    /// when the `Content-Length` is known, or as soon as a chunked body exceeds the limit.
+    /// Bodies compressed with `Content-Encoding: gzip` or `deflate` are decoded before
+    /// the handler is called, and the limit also applies to their decoded size.
+    pub fn max_body_size(&mut self, max: usize) {
+        self.max_body_size = Some(max);
+    }
+
+    /// Serializes floating-point numbers of JSON responses with the given number of decimals.
+    ///
+    /// By default, floats are written in their shortest form, e.g. `1` for 1.0 and `1e21`
+    /// for very large values; with this setting they are always written in fixed notation,
+    /// e.g. `json_float_decimals(2)` writes 1.0 as `1.00`. Integers are not affected.
+    pub fn json_float_decimals(&mut self, decimals: usize) {
+        self.json_decimals = Some(decimals);
+    }
+
+    /// Sets the Cache-Control policy of HTML responses, rendered from templates or sent by handlers,
+    /// that do not set one themselves (default is `private, no-cache`, so that pages
+    /// with user data are neither stored by shared caches nor reused without revalidation).
+    ///
+    /// With `None`, HTML responses have no Cache-Control header unless the handler sets one.
+    pub fn html_cache_policy(&mut self, policy: Option<CachePolicy>) {
+        self.html_cache_policy = policy;
+    }
+
+    /// Sets the Content Security Policy of HTML responses that do not set one themselves
+    /// with `Response::csp` (default is none).
+    pub fn default_csp(&mut self, csp: Csp) {
+        self.csp = Some(csp);
+    }
+
+    /// Sets whether HEAD requests are handled by GET routes when no HEAD route matches (default is true).
+    ///
+    /// The response to a HEAD request has the headers of the GET response, without the body.
+    /// When disabled, HEAD requests need explicit HEAD routes, otherwise they get
+    /// a 405 Method Not Allowed response.
+    pub fn auto_head(&mut self, auto_head: bool) {
+        self.auto_head = auto_head;
+    }
+
+    /// Sets whether OPTIONS requests to a path that has routes, but no OPTIONS route,
+    /// are answered automatically with an `Allow` header listing the methods of these routes
+    /// (default is true). Otherwise they get a 405 Method Not Allowed response.
+    ///
+    /// Automatic OPTIONS responses never run middleware, so that CORS preflight requests
+    /// are not rejected by authentication middleware.
+    pub fn auto_options(&mut self, auto_options: bool) {
+        self.auto_options = auto_options;
+    }
+
+    /// Sets whether middleware runs for HEAD requests handled automatically by GET routes
+    /// (default is true, see `auto_head`).
+    pub fn middleware_on_auto_head(&mut self, run: bool) {
+        self.middleware_on_auto_head = run;
+    }
+
+    /// Sets whether an empty request body is read as JSON `null` (default is false).
+    ///
+    /// By default, `Request::json` and `Request::parse_body` fail on an empty body
+    /// (the latter with 400 Bad Request). When enabled, an empty body is read as `null`
+    /// whatever its Content-Type, so that a handler parsing an `Option` gets `None`.
+    pub fn empty_json_as_null(&mut self, empty_json_as_null: bool) {
+        self.empty_json_as_null = empty_json_as_null;
+    }
+
+    /// Sets the maximum length of the path and query of requests (default is 8 KiB).
+    ///
+    /// Requests with a longer target get a 414 URI Too Long response, and malformed
+    /// request targets a 400 Bad Request response.
+    pub fn max_uri_length(&mut self, max: usize) {
+        self.max_uri_length = max;
+    }
+
+    /// Sets how invalid percent-encoding is handled in the path of requests (default is strict)
+    /// and in their query (default is lenient).
+    ///
+    /// Path segments are decoded before routing, except for encoded slashes ("%2F")
+    /// which are kept as is. In strict mode, requests with malformed escapes or invalid UTF-8
+    /// get a 400 Bad Request response; in lenient mode they are decoded as well as possible.
+    pub fn url_decoding(&mut self, path: UrlDecoding, query: UrlDecoding) {
+        self.path_decoding = path;
+        self.query_decoding = query;
+    }
+
+    /// Sends the names of response headers exactly as they were given (default is false).
+    ///
+    /// By default, header names set in lowercase with `Response::header_raw` are sent
+    /// in canonical casing, e.g. "x-request-id" is sent as "X-Request-Id"; names with
+    /// uppercase letters, as well as typed headers, are always sent as they are.
+    /// Enable this for clients that expect a specific casing.
+    pub fn verbatim_header_names(&mut self, verbatim: bool) {
+        self.verbatim_header_names = verbatim;
+    }
+
+    /// Enables the "/debug/routes" and "/debug/config" endpoints, which return the route table
+    /// and the effective configuration of this application as JSON.
+    ///
+    /// The endpoints are only registered in development mode (see `mode`); in production mode
+    /// this setting is ignored, since they expose the internals of the application.
+    pub fn enable_debug_endpoints(&mut self) {
+        self.debug_endpoints = true;
+    }
+
+    /// Sets the `SO_REUSEPORT` option on the listening socket (default is false), so that
+    /// several processes can listen on the same port, for example to restart without downtime.
+    ///
+    /// This option is only available on Unix platforms, it is ignored elsewhere.
+    pub fn reuse_port(&mut self, reuse: bool) {
+        self.reuse_port = reuse;
+    }
+
+    /// Sets the secret key of this application, used to sign URLs (see `Request::sign_url`).
+    ///
+    /// The key should be long and random, and kept secret: anyone who knows it can forge signatures.
+    pub fn secret_key(&mut self, key: &[u8]) {
+        self.secret = Some(signed::Secret::new(key));
+    }
+
+    /// Adds a `Server-Timing` header to responses (default is false), with the total time
+    /// spent handling the request, and the timings recorded with `Request::add_timing`.
+    ///
+    /// This header is visible in the developer tools of browsers, but also tells clients
+    /// about the internals of the application, so it is best left disabled in production.
+    pub fn server_timing(&mut self, enabled: bool) {
+        self.server_timing = enabled;
+    }
+
+    /// Registers a handler for responses with the given status.
+    ///
+    /// The handler is called when a response with this status and no body is produced,
+    /// for example when a handler returns `Err(Status::Forbidden)`, so that it can
+    /// send a consistent error page.
+    pub fn on_status(&mut self, status: Status, handler: router::Static) {
+        self.status_handlers.insert(status.to_u16(), handler);
+    }
+
+    /// Enables replay of responses for requests carrying an `Idempotency-Key` header.
+    ///
+    /// The successful response to a POST/PUT/PATCH/DELETE request with such a header is recorded,
+    /// and sent again without calling the handler when a request from the same caller with
+    /// the same method, path, and key is received within `window`. At most `capacity` responses
+    /// are kept, the oldest ones are evicted first. Streaming responses, error responses,
+    /// and the `Set-Cookie` header are not recorded.
+    ///
+    /// The caller is given by `identity`, typically the user or session set by an authentication
+    /// middleware: responses are looked up after middleware has run and the media type of the body
+    /// has been checked. Requests for which `identity` returns `None` are never replayed.
+    ///
+    /// ```
+    /// # use edge::Edge;
+    /// # use std::time::Duration;
+    /// struct CurrentUser(String);
+    ///
+    /// let mut edge = Edge::new("0.0.0.0:3000");
+    /// edge.idempotency(1000, Duration::from_secs(24 * 3600), |req| {
+    ///     req.extensions().get::<CurrentUser>().map(|user| user.0.clone())
+    /// });
+    /// ```
+    pub fn idempotency<F>(&mut self, capacity: usize, window: Duration, identity: F)
+        where F: Fn(&Request) -> Option<String> + Sync + 'static {
+        self.idempotency = Some(idempotency::IdempotencyStore::new(capacity, window, Box::new(identity)));
+    }
+
+    /// Sets data available to every template, such as the name of the site.
+    ///
+    /// The data must be a JSON object (e.g. a map); its entries are added to the data
+    /// of each template when rendering, unless the data given by the handler has an entry
+    /// with the same name. Panics if the data is not an object.
+    pub fn template_globals<T: ToJson>(&mut self, data: T) {
+        match data.to_json() {
+            json::Value::Object(map) => self.template_globals = map,
+            other => panic!("template globals must be a JSON object, got {}", other)
+        }
+    }
+
+    /// Returns a minimal OpenAPI 3.0 document describing the routes mounted so far,
+    /// with the given title and API version.
+    ///
+    /// Each route is listed with its method, and its path parameters (e.g. `:id`
+    /// becomes `{id}`); services, which accept any method, are not listed.
+    /// The document is a starting point, to be completed with schemas and descriptions.
+    pub fn openapi(&self, title: &str, version: &str) -> json::Value {
+        let mut paths: BTreeMap<String, json::Value> = BTreeMap::new();
+        for (method, pattern, _) in self.routers.iter().flat_map(|router| router.describe()) {
+            let method = match method {
+                Some(method) => method.to_string().to_lowercase(),
+                None => continue
+            };
+
+            let mut names = Vec::new();
+            let path: String = pattern.split('/').filter(|segment| !segment.is_empty()).map(|segment|
+                if segment.starts_with(':') || segment.starts_with('*') {
+                    names.push(segment[1..].to_string());
+                    format!("/{{{}}}", &segment[1..])
+                } else {
+                    format!("/{}", segment)
+                }
+            ).collect();
+            let path = if path.is_empty() { "/".to_string() } else { path };
+
+            let parameters = names.into_iter().map(|name| {
+                let mut schema = BTreeMap::new();
+                schema.insert("type".to_string(), json::Value::String("string".to_string()));
+                let mut parameter = BTreeMap::new();
+                parameter.insert("name".to_string(), json::Value::String(name));
+                parameter.insert("in".to_string(), json::Value::String("path".to_string()));
+                parameter.insert("required".to_string(), json::Value::Bool(true));
+                parameter.insert("schema".to_string(), json::Value::Object(schema));
+                json::Value::Object(parameter)
+            }).collect();
+
+            let mut response = BTreeMap::new();
+            response.insert("description".to_string(), json::Value::String("response".to_string()));
+            let mut responses = BTreeMap::new();
+            responses.insert("default".to_string(), json::Value::Object(response));
+
+            let mut operation = BTreeMap::new();
+            operation.insert("parameters".to_string(), json::Value::Array(parameters));
+            operation.insert("responses".to_string(), json::Value::Object(responses));
+
+            let entry = paths.entry(path).or_insert_with(|| json::Value::Object(BTreeMap::new()));
+            if let json::Value::Object(ref mut operations) = *entry {
+                operations.insert(method, json::Value::Object(operation));
+            }
+        }
+
+        let mut info = BTreeMap::new();
+        info.insert("title".to_string(), json::Value::String(title.to_string()));
+        info.insert("version".to_string(), json::Value::String(version.to_string()));
+
+        let mut document = BTreeMap::new();
+        document.insert("openapi".to_string(), json::Value::String("3.0.0".to_string()));
+        document.insert("info".to_string(), json::Value::Object(info));
+        document.insert("paths".to_string(), json::Value::Object(paths));
+        json::Value::Object(document)
+    }
+
+    /// Registers a template with the given name, found in the views directory.
     pub fn register_template(&mut self, name: &str) {
-        let mut path = PathBuf::new();
-        path.push("views");
-        path.push(name);
-        path.set_extension("hbs");
+        let path = self.views.join(format!("{}.{}", name, self.template_extension));
 
         self.handlebars.register_template_file(name, &path).unwrap();
     }
@@ -251,42 +799,175 @@ impl Edge {
     /// Runs the server in one thread per cpu.
     ///
     /// Creates one instance of `T` per request by calling `Default::default`.
-    /// This method blocks the current thread.
+    /// This method blocks the current thread until the application is shut down
+    /// (see `shutdown_handle`).
     pub fn start(&mut self) -> IoResult<()> {
-        assert!(!self.routers.is_empty(), "No router registered! Please mount at least one router");
+        if self.listener.is_none() {
+            try!(self.bind());
+        }
+
+        let (listener, addr) = self.listener.take().unwrap();
+        self.serve(listener, addr)
+    }
 
+    /// Binds the address given to `new` and returns the actual address, without starting the server.
+    ///
+    /// This is useful to learn the port chosen by the system when binding to port 0,
+    /// for example in tests; `start` then runs the server on this socket.
+    pub fn bind(&mut self) -> IoResult<SocketAddr> {
         // get address and start listening
         let addr = self.base_url.to_socket_addrs().unwrap().next().unwrap();
-        let listener = HttpListener::bind(&addr).unwrap();
+        let listener = if self.reuse_port {
+            try!(bind_reuse_port(&addr))
+        } else {
+            try!(TcpListener::bind(&addr))
+        };
+
+        let addr = try!(listener.local_addr());
+        self.listener = Some((listener, addr));
+        Ok(addr)
+    }
+
+    /// Runs the server on the given listener, which is already bound (and listening).
+    ///
+    /// This is useful when the socket is created by another process (e.g. systemd socket
+    /// activation) or needs custom options; the listener is cloned for each thread.
+    /// The address given to `new` is ignored.
+    pub fn start_with_listener(&mut self, listener: TcpListener) -> IoResult<()> {
+        let addr = try!(listener.local_addr());
+        // connections are accepted on a dedicated thread, which blocks
+        try!(listener.set_nonblocking(false));
+        self.serve(listener, addr)
+    }
+}
+
+impl Edge {
+    /// Runs the server in one thread per cpu on the given listener.
+    fn serve(&mut self, listener: TcpListener, addr: SocketAddr) -> IoResult<()> {
+        assert!(!self.routers.is_empty(), "No router registered! Please mount at least one router");
+
+        try!(register_partials(&mut self.handlebars, &self.views.join("partials"), &self.template_extension));
+        if self.prewarm_templates {
+            try!(register_views(&mut self.handlebars, &self.views, &self.template_extension));
+        }
+
+        if self.debug_endpoints {
+            if self.mode == Mode::Development {
+                self.mount_debug_endpoints();
+            } else {
+                warn!("debug endpoints are only available in development mode");
+            }
+        }
 
+        let edge: &Edge = self;
         let num_cpus = num_cpus::get();
+        let listeners = try!(listener::BackoffListener::for_threads(&listener, num_cpus, edge.max_accept_backoff));
         let pool = Pool::new(num_cpus * 4);
         pool.scoped(|pool_scope| {
             crossbeam::scope(|scope| {
-                for i in 0..num_cpus {
-                    let listener = listener.try_clone().unwrap();
-                    let base_url = &self.base_url;
-                    let routers = &self.routers;
-                    let handlebars = &self.handlebars;
+                let (tx, rx) = mpsc::channel();
+                for (i, listener) in listeners.into_iter().enumerate() {
+                    let tx = tx.clone();
                     scope.spawn(move || {
                         info!("thread {} listening on http://{}", i, addr);
-                        Server::new(listener).handle(move |control| {
-                            handler::EdgeHandler::new(pool_scope, &base_url, &routers, &handlebars, control)
+                        let (listening, server) = Server::new(listener).handle(move |control| {
+                            handler::EdgeHandler::new(pool_scope, edge, control)
                         }).unwrap();
+                        let _ = tx.send(listening);
+
+                        // the server loop runs until it is closed
+                        drop(server);
                     });
                 }
+
+                // wait for shutdown, let in-flight requests complete, then close the servers
+                scope.spawn(move || {
+                    edge.shutdown.wait();
+                    info!("shutting down, waiting up to {:?} for in-flight requests", edge.drain_timeout);
+
+                    let remaining = edge.shutdown.drain(edge.drain_timeout);
+                    if remaining > 0 {
+                        warn!("drain timeout elapsed, closing {} in-flight requests", remaining);
+                    }
+
+                    for listening in rx.iter().take(num_cpus) {
+                        listening.close();
+                    }
+                });
             });
         });
 
         Ok(())
     }
+
+    /// Mounts a router with the debug endpoints at "/debug".
+    fn mount_debug_endpoints(&mut self) {
+        let routes: Vec<json::Value> = self.routers.iter().flat_map(|router| router.describe()).map(|(method, path, host)| {
+            let mut route = BTreeMap::new();
+            route.insert("method".to_string(), method.map_or(json::Value::Null, |method| json::Value::String(method.to_string())));
+            route.insert("path".to_string(), json::Value::String(path));
+            route.insert("host".to_string(), host.map_or(json::Value::Null, json::Value::String));
+            json::Value::Object(route)
+        }).collect();
+        let routes = json::Value::Array(routes);
+
+        let num_cpus = num_cpus::get() as u64;
+        let mut config = BTreeMap::new();
+        config.insert("address".to_string(), json::Value::String(self.base_url.to_string()));
+        config.insert("mode".to_string(), json::Value::String(format!("{:?}", self.mode).to_lowercase()));
+        config.insert("dispatch".to_string(), json::Value::String(format!("{:?}", self.dispatch)));
+        config.insert("threads".to_string(), json::Value::U64(num_cpus));
+        config.insert("worker_threads".to_string(), json::Value::U64(num_cpus * 4));
+        config.insert("max_concurrent_requests".to_string(), self.max_requests.map_or(json::Value::Null, |max| json::Value::U64(max as u64)));
+        config.insert("max_body_size".to_string(), self.max_body_size.map_or(json::Value::Null, |max| json::Value::U64(max as u64)));
+        config.insert("drain_timeout_secs".to_string(), json::Value::U64(self.drain_timeout.as_secs()));
+        config.insert("max_accept_backoff_ms".to_string(), json::Value::U64(self.max_accept_backoff.as_secs() * 1000 + self.max_accept_backoff.subsec_nanos() as u64 / 1_000_000));
+        config.insert("trust_proxy".to_string(), json::Value::String(format!("{:?}", self.trust_proxy)));
+        config.insert("idempotency".to_string(), json::Value::Bool(self.idempotency.is_some()));
+        config.insert("verbatim_header_names".to_string(), json::Value::Bool(self.verbatim_header_names));
+        config.insert("read_buffer_size".to_string(), json::Value::U64(self.buffers.read_size() as u64));
+        config.insert("write_buffer_size".to_string(), json::Value::U64(self.buffers.write_size() as u64));
+        let config = json::Value::Object(config);
+
+        let mut router = Router::<()>::new();
+        router::get_service(&mut router, "/routes", move |_, _| Ok(Action::Json(routes.clone())));
+        router::get_service(&mut router, "/config", move |_, _| Ok(Action::Json(config.clone())));
+        self.mount("/debug", router);
+    }
 }
 
-fn render_html(text: &str) -> String {
+/// Binds a listener to the given address with the `SO_REUSEADDR` and `SO_REUSEPORT` options.
+#[cfg(unix)]
+fn bind_reuse_port(addr: &SocketAddr) -> IoResult<TcpListener> {
+    use net2::TcpBuilder;
+    use net2::unix::UnixTcpBuilderExt;
+
+    let builder = try!(match *addr {
+        SocketAddr::V4(_) => TcpBuilder::new_v4(),
+        SocketAddr::V6(_) => TcpBuilder::new_v6()
+    });
+    try!(builder.reuse_address(true));
+    try!(builder.reuse_port(true));
+    try!(builder.bind(addr));
+
+    builder.listen(1024)
+}
+
+#[cfg(not(unix))]
+fn bind_reuse_port(addr: &SocketAddr) -> IoResult<TcpListener> {
+    warn!("SO_REUSEPORT is not supported on this platform, ignoring");
+    TcpListener::bind(addr)
+}
+
+/// Returns the options used to render Markdown, unless overridden in a template.
+fn markdown_options() -> Options {
     let mut opts = Options::empty();
     opts.insert(OPTION_ENABLE_TABLES);
     opts.insert(OPTION_ENABLE_FOOTNOTES);
+    opts
+}
 
+fn render_html(text: &str, opts: Options) -> String {
     let mut s = String::with_capacity(text.len() * 3 / 2);
     let p = Parser::new_ext(text, opts);
     html::push_html(&mut s, p);
@@ -297,6 +978,9 @@ fn render_html(text: &str) -> String {
 /// see https://github.com/waynenilsen/handlebars-markdown-helper/blob/master/src/lib.rs#L31
 ///
 /// because the handlebars-markdown-helper crate does not allow custom options for Markdown rendering yet
+///
+/// Options can be overridden for a single invocation with boolean hash arguments,
+/// for example `{{markdown content tables=false footnotes=false}}`.
 fn markdown_helper(_: &Context, h: &Helper, _ : &Handlebars, rc: &mut RenderContext) -> result::Result<(), RenderError> {
     let markdown_text_var = try!(h.param(0).ok_or_else(|| RenderError::new(
         "Param not found for helper \"markdown\"")
@@ -304,25 +988,61 @@ fn markdown_helper(_: &Context, h: &Helper, _ : &Handlebars, rc: &mut RenderCont
     let markdown = try!(markdown_text_var.value().as_string().ok_or_else(||
         RenderError::new(format!("Expected a string for parameter {:?}", markdown_text_var))
     ));
-    let html = render_html(markdown);
+
+    let mut opts = markdown_options();
+    for &(name, option) in &[("tables", OPTION_ENABLE_TABLES), ("footnotes", OPTION_ENABLE_FOOTNOTES)] {
+        if let Some(value) = h.hash_get(name) {
+            match value.value().as_boolean() {
+                Some(true) => opts.insert(option),
+                Some(false) => opts.remove(option),
+                None => return Err(RenderError::new(format!("Expected a boolean for option {} of helper \"markdown\"", name)))
+            }
+        }
+    }
+
+    let html = render_html(markdown, opts);
     try!(rc.writer.write_all(html.as_bytes()));
     Ok(())
 }
 
-fn init_handlebars(handlebars: &mut Handlebars) -> IoResult<()> {
+fn init_handlebars(handlebars: &mut Handlebars) {
     // register markdown helper
     handlebars.register_helper("markdown", Box::new(::markdown_helper));
+}
 
+fn register_partials(handlebars: &mut Handlebars, partials: &Path, extension: &str) -> IoResult<()> {
     // register partials folder (if it exists)
-    let partials = Path::new("views/partials");
     if partials.exists() {
-        for it in try!(read_dir("views/partials")) {
-            let entry = try!(it);
-            let path = entry.path();
-            if path.extension().is_some() && path.extension().unwrap() == "hbs" {
-                let name = path.file_stem().unwrap().to_str().unwrap();
-                handlebars.register_template_file(name, path.as_path()).unwrap();
-            }
+        try!(register_dir(handlebars, partials, extension));
+    }
+
+    Ok(())
+}
+
+fn register_views(handlebars: &mut Handlebars, views: &Path, extension: &str) -> IoResult<()> {
+    // compile all templates of the views folder (if it exists)
+    if views.exists() {
+        try!(register_dir(handlebars, views, extension));
+    }
+
+    Ok(())
+}
+
+/// Registers the files of the given directory that have the given extension,
+/// failing if one does not compile.
+fn register_dir(handlebars: &mut Handlebars, dir: &Path, extension: &str) -> IoResult<()> {
+    let suffix = format!(".{}", extension);
+    for it in try!(read_dir(dir)) {
+        let entry = try!(it);
+        let path = entry.path();
+        let name = match path.file_name().and_then(|name| name.to_str()) {
+            Some(name) if name.len() > suffix.len() && name.ends_with(suffix.as_str()) => name[..name.len() - suffix.len()].to_string(),
+            _ => continue
+        };
+
+        if let Err(e) = handlebars.register_template_file(&name, path.as_path()) {
+            let message = format!("cannot compile template {}: {:?}", path.display(), e);
+            return Err(io::Error::new(io::ErrorKind::InvalidData, message));
         }
     }
 
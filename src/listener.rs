@@ -0,0 +1,347 @@
+//! Listener that backs off when accepting connections fails.
+
+use hyper::net::{Accept, HttpStream};
+#[cfg(not(unix))]
+use hyper::net::HttpListener;
+
+use mio::{EventSet, Evented, PollOpt, Selector, Token};
+#[cfg(unix)]
+use mio::tcp::TcpStream as MioTcpStream;
+#[cfg(not(unix))]
+use mio::tcp::TcpListener as MioTcpListener;
+#[cfg(unix)]
+use mio::unix::{self, PipeReader, PipeWriter};
+
+use connection::Stream;
+
+use std::cell::RefCell;
+use std::cmp;
+use std::io::{self, Write};
+#[cfg(unix)]
+use std::io::Read;
+use std::net::{SocketAddr, TcpListener, TcpStream};
+#[cfg(unix)]
+use std::net::{IpAddr, Ipv4Addr, Ipv6Addr};
+#[cfg(unix)]
+use std::os::unix::io::{FromRawFd, IntoRawFd};
+#[cfg(unix)]
+use std::sync::Arc;
+use std::sync::atomic::{AtomicBool, Ordering};
+#[cfg(unix)]
+use std::sync::atomic::AtomicUsize;
+use std::sync::mpsc::Sender;
+#[cfg(unix)]
+use std::sync::mpsc::{self, Receiver};
+use std::thread;
+use std::time::Duration;
+
+/// Default maximum delay between attempts to accept a connection after errors.
+pub const DEFAULT_MAX_ACCEPT_BACKOFF: u64 = 1;
+
+/// Delay after the first error.
+const MIN_ACCEPT_BACKOFF_MS: u64 = 5;
+
+/// Delay before accepting again after consecutive errors, doubled after each error up to a maximum.
+struct Backoff {
+    max: Duration,
+    delay: Option<Duration>
+}
+
+impl Backoff {
+    fn new(max: Duration) -> Backoff {
+        Backoff {
+            max: max,
+            delay: None
+        }
+    }
+
+    /// Returns the delay to wait after an error.
+    fn failed(&mut self) -> Duration {
+        let delay = self.delay.map_or(Duration::from_millis(MIN_ACCEPT_BACKOFF_MS), |delay| delay * 2);
+        let delay = cmp::min(delay, self.max);
+        self.delay = Some(delay);
+        delay
+    }
+
+    fn succeeded(&mut self) {
+        self.delay = None;
+    }
+}
+
+/// Accepts connections and sends them to the listener, until `closed` is set.
+///
+/// After an error, `sleep` is called with the delay given by `backoff`.
+#[cfg_attr(not(unix), allow(dead_code))]
+fn accept_loop<A, W, S>(mut accept: A, streams: Sender<TcpStream>, mut waker: W, mut backoff: Backoff, closed: &AtomicBool, mut sleep: S)
+    where A: FnMut() -> io::Result<TcpStream>, W: Write, S: FnMut(Duration) {
+    loop {
+        let result = accept();
+        if closed.load(Ordering::SeqCst) {
+            return;
+        }
+
+        match result {
+            Ok(stream) => {
+                backoff.succeeded();
+                if streams.send(stream).is_err() {
+                    return;
+                }
+
+                // one byte per connection; if the pipe is full, the loop is awake anyway
+                let _ = waker.write(&[0]);
+            }
+            Err(e) => {
+                let delay = backoff.failed();
+                error!("could not accept connection: {}, retrying in {:?}", e, delay);
+                sleep(delay);
+            }
+        }
+    }
+}
+
+/// State shared by the listeners accepting on clones of the same socket.
+#[cfg(unix)]
+struct Shared {
+    closed: AtomicBool,
+    /// number of accepting threads that have not returned yet
+    accepting: AtomicUsize
+}
+
+/// A listener whose connections are accepted on a dedicated thread.
+///
+/// When `accept` fails (e.g. when the process runs out of file descriptors), the accepting
+/// thread waits before accepting again, doubling the delay after each consecutive error
+/// up to a maximum, instead of spinning; the delay is reset as soon as a connection is accepted.
+/// Accepted connections are handed to the event loop through a channel, and a pipe wakes
+/// the loop up, so the loop keeps serving the connections it has while accepting backs off.
+#[cfg(unix)]
+pub struct BackoffListener {
+    addr: SocketAddr,
+    streams: Receiver<TcpStream>,
+    waker: RefCell<PipeReader>,
+    shared: Arc<Shared>
+}
+
+#[cfg(unix)]
+impl BackoffListener {
+    /// Creates `count` listeners accepting on clones of the given socket.
+    ///
+    /// Closing any of them closes them all, since a thread blocked in `accept` may be woken up
+    /// by a connection meant for another.
+    pub fn for_threads(listener: &TcpListener, count: usize, max_delay: Duration) -> io::Result<Vec<BackoffListener>> {
+        let shared = Arc::new(Shared {
+            closed: AtomicBool::new(false),
+            accepting: AtomicUsize::new(0)
+        });
+
+        (0..count).map(|_| listener.try_clone().and_then(|listener| BackoffListener::new(listener, max_delay, shared.clone()))).collect()
+    }
+
+    fn new(listener: TcpListener, max_delay: Duration, shared: Arc<Shared>) -> io::Result<BackoffListener> {
+        let addr = try!(listener.local_addr());
+        let (reader, writer) = try!(unix::pipe());
+        let (tx, rx) = mpsc::channel();
+
+        let thread_shared = shared.clone();
+        shared.accepting.fetch_add(1, Ordering::SeqCst);
+        let spawned = thread::Builder::new().name("edge-accept".to_string()).spawn(move || {
+            let accept = || listener.accept().map(|(stream, _)| stream);
+            accept_loop(accept, tx, writer, Backoff::new(max_delay), &thread_shared.closed, thread::sleep);
+            thread_shared.accepting.fetch_sub(1, Ordering::SeqCst);
+        });
+        if let Err(e) = spawned {
+            shared.accepting.fetch_sub(1, Ordering::SeqCst);
+            return Err(e);
+        }
+
+        Ok(BackoffListener {
+            addr: addr,
+            streams: rx,
+            waker: RefCell::new(reader),
+            shared: shared
+        })
+    }
+}
+
+#[cfg(unix)]
+impl Drop for BackoffListener {
+    fn drop(&mut self) {
+        // any accepting thread may take a given connection, so connect once per thread
+        // still accepting to wake them all up, and let them see the listeners are closed
+        self.shared.closed.store(true, Ordering::SeqCst);
+        let ip = match self.addr.ip() {
+            IpAddr::V4(ip) if ip.is_unspecified() => IpAddr::V4(Ipv4Addr::new(127, 0, 0, 1)),
+            IpAddr::V6(ip) if ip.is_unspecified() => IpAddr::V6(Ipv6Addr::new(0, 0, 0, 0, 0, 0, 0, 1)),
+            ip => ip
+        };
+        for _ in 0..self.shared.accepting.load(Ordering::SeqCst) {
+            if TcpStream::connect(SocketAddr::new(ip, self.addr.port())).is_err() {
+                break;
+            }
+        }
+    }
+}
+
+#[cfg(unix)]
+impl Accept for BackoffListener {
+    type Output = Stream;
+
+    fn accept(&self) -> io::Result<Option<Stream>> {
+        let mut byte = [0];
+        match self.waker.borrow_mut().read(&mut byte) {
+            Err(ref e) if e.kind() == io::ErrorKind::WouldBlock => (),
+            Err(e) => return Err(e),
+            Ok(_) => ()
+        }
+
+        match self.streams.try_recv() {
+            Ok(stream) => {
+                try!(stream.set_nonblocking(true));
+                let stream = into_mio(stream);
+                Ok(Some(Stream::new(HttpStream(stream))))
+            }
+            Err(_) => Ok(None)
+        }
+    }
+
+    fn local_addr(&self) -> io::Result<SocketAddr> {
+        Ok(self.addr)
+    }
+}
+
+#[cfg(unix)]
+fn into_mio(stream: TcpStream) -> MioTcpStream {
+    unsafe { MioTcpStream::from_raw_fd(stream.into_raw_fd()) }
+}
+
+#[cfg(unix)]
+impl Evented for BackoffListener {
+    fn register(&self, selector: &mut Selector, token: Token, interest: EventSet, opts: PollOpt) -> io::Result<()> {
+        self.waker.borrow().register(selector, token, interest, opts)
+    }
+
+    fn reregister(&self, selector: &mut Selector, token: Token, interest: EventSet, opts: PollOpt) -> io::Result<()> {
+        self.waker.borrow().reregister(selector, token, interest, opts)
+    }
+
+    fn deregister(&self, selector: &mut Selector) -> io::Result<()> {
+        self.waker.borrow().deregister(selector)
+    }
+}
+
+/// Without pipes to wake the event loop up, connections are accepted by the loop itself,
+/// and the delay after an error blocks the loop.
+#[cfg(not(unix))]
+pub struct BackoffListener {
+    inner: HttpListener,
+    backoff: RefCell<Backoff>
+}
+
+#[cfg(not(unix))]
+impl BackoffListener {
+    /// Creates `count` listeners accepting on clones of the given socket.
+    pub fn for_threads(listener: &TcpListener, count: usize, max_delay: Duration) -> io::Result<Vec<BackoffListener>> {
+        (0..count).map(|_| {
+            let listener = try!(listener.try_clone());
+            let addr = try!(listener.local_addr());
+            let listener = try!(MioTcpListener::from_listener(listener, &addr));
+            Ok(BackoffListener {
+                inner: HttpListener(listener),
+                backoff: RefCell::new(Backoff::new(max_delay))
+            })
+        }).collect()
+    }
+}
+
+#[cfg(not(unix))]
+impl Accept for BackoffListener {
+    type Output = Stream;
+
+    fn accept(&self) -> io::Result<Option<Stream>> {
+        match self.inner.accept() {
+            Ok(stream) => {
+                self.backoff.borrow_mut().succeeded();
+                Ok(stream.map(Stream::new))
+            }
+            Err(e) => {
+                let delay = self.backoff.borrow_mut().failed();
+                error!("could not accept connection: {}, retrying in {:?}", e, delay);
+                thread::sleep(delay);
+                Err(e)
+            }
+        }
+    }
+
+    fn local_addr(&self) -> io::Result<SocketAddr> {
+        self.inner.local_addr()
+    }
+}
+
+#[cfg(not(unix))]
+impl Evented for BackoffListener {
+    fn register(&self, selector: &mut Selector, token: Token, interest: EventSet, opts: PollOpt) -> io::Result<()> {
+        self.inner.register(selector, token, interest, opts)
+    }
+
+    fn reregister(&self, selector: &mut Selector, token: Token, interest: EventSet, opts: PollOpt) -> io::Result<()> {
+        self.inner.reregister(selector, token, interest, opts)
+    }
+
+    fn deregister(&self, selector: &mut Selector) -> io::Result<()> {
+        self.inner.deregister(selector)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{accept_loop, Backoff};
+    use std::io;
+    use std::net::{TcpListener, TcpStream};
+    use std::sync::atomic::{AtomicBool, Ordering};
+    use std::sync::mpsc;
+    use std::time::Duration;
+
+    #[test]
+    fn backoff_doubles_up_to_max() {
+        let mut backoff = Backoff::new(Duration::from_millis(30));
+        let delays: Vec<Duration> = (0..4).map(|_| backoff.failed()).collect();
+        assert_eq!(delays, vec![Duration::from_millis(5), Duration::from_millis(10),
+            Duration::from_millis(20), Duration::from_millis(30)]);
+    }
+
+    #[test]
+    fn backoff_resets_after_success() {
+        let mut backoff = Backoff::new(Duration::from_secs(1));
+        backoff.failed();
+        backoff.failed();
+        backoff.succeeded();
+        assert_eq!(backoff.failed(), Duration::from_millis(5));
+    }
+
+    #[test]
+    fn accept_loop_backs_off_until_a_connection_is_accepted() {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let addr = listener.local_addr().unwrap();
+        let closed = AtomicBool::new(false);
+        let (tx, rx) = mpsc::channel();
+        let mut sleeps = Vec::new();
+        let mut attempts = 0;
+
+        // fails 3 times, accepts a connection, fails twice, then is closed
+        accept_loop(|| {
+            attempts += 1;
+            match attempts {
+                4 => TcpStream::connect(addr),
+                7 => {
+                    closed.store(true, Ordering::SeqCst);
+                    Err(io::Error::new(io::ErrorKind::Other, "closed"))
+                }
+                _ => Err(io::Error::new(io::ErrorKind::Other, "too many open files"))
+            }
+        }, tx, io::sink(), Backoff::new(Duration::from_secs(1)), &closed, |delay| sleeps.push(delay));
+
+        let ms = Duration::from_millis;
+        assert_eq!(sleeps, vec![ms(5), ms(10), ms(20), ms(5), ms(10)]);
+        assert_eq!(rx.iter().count(), 1);
+    }
+}
@@ -0,0 +1,290 @@
+//! Incremental parsing of `multipart/form-data` bodies (RFC 7578).
+//!
+//! Parts are returned one at a time, and the body of each part is read in chunks
+//! from any reader, so that a part can be processed or saved to disk without
+//! making another copy of it. Note that the body of a request is received in full
+//! before the handler is called, so the body itself is still buffered in memory
+//! (up to `Edge::max_body_size`).
+
+use forwarded::{split_unquoted, unquote};
+
+use std::ascii::AsciiExt;
+use std::cmp;
+use std::fs::File;
+use std::io::{self, ErrorKind, Read};
+use std::path::Path;
+
+/// Number of bytes read from the underlying reader at a time.
+const CHUNK_SIZE: usize = 8 * 1024;
+
+/// Maximum size of the headers of a part.
+const MAX_HEADERS_SIZE: usize = 8 * 1024;
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+enum State {
+    /// Before the first delimiter.
+    Preamble,
+
+    /// Within the body of a part.
+    Body,
+
+    /// Right after a delimiter.
+    Delimiter,
+
+    /// After the close delimiter.
+    Done
+}
+
+/// A reader of the parts of a multipart body.
+///
+/// ```no_run
+/// use edge::{Request, Response, Result, Status};
+///
+/// fn upload(req: &Request, _res: &mut Response) -> Result {
+///     let mut multipart = try!(req.multipart());
+///     let mut files = 0;
+///     while let Some(mut part) = try!(multipart.next_part().map_err(|e| (Status::BadRequest, e.to_string()))) {
+///         if part.filename().is_some() {
+///             files += 1;
+///             let path = format!("/tmp/upload-{}", files);
+///             try!(part.save_to(path).map_err(|e| (Status::InternalServerError, e.to_string())));
+///         }
+///     }
+///     Ok(format!("{} files uploaded", files).into())
+/// }
+/// ```
+pub struct Multipart<R> {
+    reader: R,
+    delimiter: Vec<u8>,
+    buf: Vec<u8>,
+    state: State
+}
+
+/// One part of a multipart body, readable with `Read`.
+pub struct Part<'a, R: 'a> {
+    multipart: &'a mut Multipart<R>,
+    headers: Vec<(String, String)>
+}
+
+impl<R: Read> Multipart<R> {
+    /// Creates a reader of the parts separated by the given boundary.
+    ///
+    /// ```
+    /// use edge::Multipart;
+    /// use std::io::{Cursor, Read};
+    ///
+    /// let large = vec![b'x'; 100 * 1024];
+    /// let mut body = b"--XYZ\r\nContent-Disposition: form-data; name=\"file\"; filename=\"a.txt\"\r\n\r\n".to_vec();
+    /// body.extend_from_slice(&large);
+    /// body.extend_from_slice(b"\r\n--XYZ\r\nContent-Disposition: form-data; name=\"title\"\r\n\r\nhello\r\n--XYZ--\r\n");
+    ///
+    /// let mut multipart = Multipart::new(Cursor::new(body), "XYZ");
+    /// {
+    ///     let mut part = multipart.next_part().unwrap().unwrap();
+    ///     assert_eq!(part.filename(), Some("a.txt".to_string()));
+    ///
+    ///     // read the large part in small chunks
+    ///     let (mut chunk, mut total) = ([0; 4096], 0);
+    ///     loop {
+    ///         let read = part.read(&mut chunk).unwrap();
+    ///         if read == 0 { break; }
+    ///         assert!(chunk[..read].iter().all(|&b| b == b'x'));
+    ///         total += read;
+    ///     }
+    ///     assert_eq!(total, large.len());
+    /// }
+    /// {
+    ///     let mut part = multipart.next_part().unwrap().unwrap();
+    ///     assert_eq!(part.name(), Some("title".to_string()));
+    ///     let mut title = String::new();
+    ///     part.read_to_string(&mut title).unwrap();
+    ///     assert_eq!(title, "hello");
+    /// }
+    /// assert!(multipart.next_part().unwrap().is_none());
+    /// ```
+    pub fn new(reader: R, boundary: &str) -> Multipart<R> {
+        Multipart {
+            reader: reader,
+            delimiter: format!("\r\n--{}", boundary).into_bytes(),
+            // the first delimiter may not be preceded by a line break
+            buf: b"\r\n".to_vec(),
+            state: State::Preamble
+        }
+    }
+
+    /// Returns the next part, or `None` after the last one.
+    ///
+    /// What remains of the body of the previous part is skipped.
+    pub fn next_part(&mut self) -> io::Result<Option<Part<R>>> {
+        if self.state == State::Preamble || self.state == State::Body {
+            // the preamble is skipped like the body of a part
+            self.state = State::Body;
+            let mut sink = [0; 1024];
+            while try!(self.read_body(&mut sink)) > 0 {}
+        }
+
+        if self.state == State::Done {
+            return Ok(None);
+        }
+
+        // the close delimiter is followed by "--", other delimiters by a line break
+        while self.buf.len() < 2 {
+            if !try!(self.fill()) {
+                return Err(unexpected_eof());
+            }
+        }
+        if self.buf.starts_with(b"--") {
+            self.state = State::Done;
+            return Ok(None);
+        }
+
+        // skip the rest of the delimiter line, but not its line break,
+        // so that a part without headers is handled as an empty header block
+        let end = try!(self.find(b"\r\n"));
+        self.buf.drain(..end);
+        let end = try!(self.find(b"\r\n\r\n"));
+        let headers = parse_headers(&self.buf[2..cmp::max(end, 2)]);
+        self.buf.drain(..end + 4);
+
+        self.state = State::Body;
+        Ok(Some(Part {
+            multipart: self,
+            headers: headers
+        }))
+    }
+
+    /// Reads from the body of the current part, returning 0 at the end of the part.
+    fn read_body(&mut self, out: &mut [u8]) -> io::Result<usize> {
+        loop {
+            if let Some(pos) = position(&self.buf, &self.delimiter) {
+                if pos == 0 {
+                    self.buf.drain(..self.delimiter.len());
+                    self.state = State::Delimiter;
+                    return Ok(0);
+                }
+                return Ok(self.consume(out, pos));
+            }
+
+            // the end of the buffer may be the beginning of a delimiter
+            let safe = self.buf.len().saturating_sub(self.delimiter.len() - 1);
+            if safe > 0 {
+                return Ok(self.consume(out, safe));
+            }
+
+            if !try!(self.fill()) {
+                return Err(unexpected_eof());
+            }
+        }
+    }
+
+    /// Moves at most `max` bytes from the buffer to `out`.
+    fn consume(&mut self, out: &mut [u8], max: usize) -> usize {
+        let len = cmp::min(out.len(), max);
+        out[..len].copy_from_slice(&self.buf[..len]);
+        self.buf.drain(..len);
+        len
+    }
+
+    /// Returns the position of the given bytes in the buffer, reading more data as needed.
+    fn find(&mut self, needle: &[u8]) -> io::Result<usize> {
+        loop {
+            if let Some(pos) = position(&self.buf, needle) {
+                return Ok(pos);
+            }
+            if self.buf.len() > MAX_HEADERS_SIZE {
+                return Err(io::Error::new(ErrorKind::InvalidData, "multipart headers too large"));
+            }
+            if !try!(self.fill()) {
+                return Err(unexpected_eof());
+            }
+        }
+    }
+
+    /// Reads a chunk from the underlying reader into the buffer, returns `false` at the end.
+    fn fill(&mut self) -> io::Result<bool> {
+        let len = self.buf.len();
+        self.buf.resize(len + CHUNK_SIZE, 0);
+        let result = self.reader.read(&mut self.buf[len..]);
+        let read = match result {
+            Ok(read) => read,
+            Err(e) => {
+                self.buf.truncate(len);
+                return Err(e);
+            }
+        };
+        self.buf.truncate(len + read);
+        Ok(read > 0)
+    }
+}
+
+impl<'a, R: Read> Part<'a, R> {
+    /// Returns the value of the header with the given name (case-insensitive).
+    pub fn header(&self, name: &str) -> Option<&str> {
+        self.headers.iter()
+            .find(|&&(ref header, _)| header.eq_ignore_ascii_case(name))
+            .map(|&(_, ref value)| value.as_str())
+    }
+
+    /// Returns the name of the form field of this part.
+    pub fn name(&self) -> Option<String> {
+        self.disposition_param("name")
+    }
+
+    /// Returns the file name of this part, if it is a file upload.
+    pub fn filename(&self) -> Option<String> {
+        self.disposition_param("filename")
+    }
+
+    /// Returns the content type of this part (if given).
+    pub fn content_type(&self) -> Option<&str> {
+        self.header("Content-Type")
+    }
+
+    /// Writes the body of this part to a file at the given path in chunks,
+    /// returning the number of bytes written.
+    pub fn save_to<P: AsRef<Path>>(&mut self, path: P) -> io::Result<u64> {
+        let mut file = try!(File::create(path));
+        io::copy(self, &mut file)
+    }
+
+    fn disposition_param(&self, name: &str) -> Option<String> {
+        self.header("Content-Disposition").and_then(|disposition|
+            split_unquoted(disposition, ';').into_iter().skip(1).filter_map(|param| {
+                let mut parts = param.splitn(2, '=');
+                match (parts.next(), parts.next()) {
+                    (Some(key), Some(value)) if key.trim().eq_ignore_ascii_case(name) => Some(unquote(value.trim())),
+                    _ => None
+                }
+            }).next()
+        )
+    }
+}
+
+impl<'a, R: Read> Read for Part<'a, R> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        if self.multipart.state != State::Body {
+            return Ok(0);
+        }
+        self.multipart.read_body(buf)
+    }
+}
+
+/// Parses the header lines of a part.
+fn parse_headers(block: &[u8]) -> Vec<(String, String)> {
+    String::from_utf8_lossy(block).split("\r\n").filter_map(|line| {
+        let mut parts = line.splitn(2, ':');
+        match (parts.next(), parts.next()) {
+            (Some(name), Some(value)) => Some((name.trim().to_string(), value.trim().to_string())),
+            _ => None
+        }
+    }).collect()
+}
+
+/// Returns the position of the first occurrence of `needle` in `haystack`.
+fn position(haystack: &[u8], needle: &[u8]) -> Option<usize> {
+    haystack.windows(needle.len()).position(|window| window == needle)
+}
+
+fn unexpected_eof() -> io::Error {
+    io::Error::new(ErrorKind::UnexpectedEof, "multipart body ends unexpectedly")
+}
@@ -0,0 +1,241 @@
+//! Partial updates of JSON documents.
+//!
+//! Supports JSON Merge Patch (RFC 7386, `application/merge-patch+json`)
+//! and JSON Patch (RFC 6902, `application/json-patch+json`).
+
+use serde_json::value::Value;
+
+use std::collections::BTreeMap;
+use std::{error, fmt, result};
+
+/// Error returned when a patch cannot be parsed or applied.
+#[derive(Debug)]
+pub struct PatchError {
+    message: String
+}
+
+impl PatchError {
+    fn new<S: Into<String>>(message: S) -> PatchError {
+        PatchError {
+            message: message.into()
+        }
+    }
+}
+
+impl fmt::Display for PatchError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        self.message.fmt(f)
+    }
+}
+
+impl error::Error for PatchError {
+    fn description(&self) -> &str {
+        &self.message
+    }
+}
+
+pub type Result<T> = result::Result<T, PatchError>;
+
+/// An operation of a JSON Patch, with paths given as JSON Pointers (RFC 6901).
+#[derive(Clone, Debug, PartialEq)]
+pub enum Operation {
+    Add(String, Value),
+    Remove(String),
+    Replace(String, Value),
+    Move(String, String),
+    Copy(String, String),
+    Test(String, Value)
+}
+
+/// A patch that can be applied to a JSON document.
+#[derive(Clone, Debug, PartialEq)]
+pub enum Patch {
+    /// JSON Merge Patch: the patch document describes the changes, `null` removes a member.
+    Merge(Value),
+
+    /// JSON Patch: a sequence of operations.
+    Json(Vec<Operation>)
+}
+
+impl Patch {
+    /// Parses a JSON Patch from the given JSON value, which must be an array of operations.
+    pub fn json_patch(value: &Value) -> Result<Patch> {
+        let operations = match *value {
+            Value::Array(ref operations) => operations,
+            _ => return Err(PatchError::new("JSON Patch must be an array"))
+        };
+
+        let mut result = Vec::with_capacity(operations.len());
+        for operation in operations {
+            let member = |name: &str| operation.find(name);
+            let string = |name: &str| member(name).and_then(Value::as_string).map(str::to_string)
+                .ok_or_else(|| PatchError::new(format!("missing or invalid \"{}\" in operation {}", name, operation)));
+            let value = || member("value").cloned()
+                .ok_or_else(|| PatchError::new(format!("missing \"value\" in operation {}", operation)));
+
+            let op = try!(string("op"));
+            let path = try!(string("path"));
+            result.push(match op.as_str() {
+                "add" => Operation::Add(path, try!(value())),
+                "remove" => Operation::Remove(path),
+                "replace" => Operation::Replace(path, try!(value())),
+                "move" => Operation::Move(try!(string("from")), path),
+                "copy" => Operation::Copy(try!(string("from")), path),
+                "test" => Operation::Test(path, try!(value())),
+                _ => return Err(PatchError::new(format!("unknown operation \"{}\"", op)))
+            });
+        }
+
+        Ok(Patch::Json(result))
+    }
+
+    /// Applies this patch to the given document.
+    ///
+    /// A JSON Patch is applied atomically: if an operation fails, the document is left unchanged.
+    pub fn apply(&self, doc: &mut Value) -> Result<()> {
+        match *self {
+            Patch::Merge(ref patch) => {
+                merge(doc, patch);
+                Ok(())
+            }
+            Patch::Json(ref operations) => {
+                let mut patched = doc.clone();
+                for operation in operations {
+                    try!(apply_operation(&mut patched, operation));
+                }
+                *doc = patched;
+                Ok(())
+            }
+        }
+    }
+}
+
+/// Applies the given merge patch to the target, see RFC 7386 section 2.
+fn merge(target: &mut Value, patch: &Value) {
+    if let Value::Object(ref members) = *patch {
+        if !target.is_object() {
+            *target = Value::Object(BTreeMap::new());
+        }
+
+        if let Value::Object(ref mut map) = *target {
+            for (name, value) in members {
+                if value.is_null() {
+                    map.remove(name);
+                } else {
+                    merge(map.entry(name.clone()).or_insert(Value::Null), value);
+                }
+            }
+        }
+    } else {
+        *target = patch.clone();
+    }
+}
+
+/// Parses the given JSON Pointer into reference tokens.
+fn parse_pointer(pointer: &str) -> Result<Vec<String>> {
+    if pointer.is_empty() {
+        return Ok(Vec::new());
+    }
+    if !pointer.starts_with('/') {
+        return Err(PatchError::new(format!("invalid JSON Pointer \"{}\"", pointer)));
+    }
+
+    Ok(pointer[1..].split('/').map(|token| token.replace("~1", "/").replace("~0", "~")).collect())
+}
+
+/// Returns the index designated by the given token in an array of the given length.
+fn parse_index(token: &str, len: usize) -> Result<usize> {
+    match token.parse::<usize>() {
+        Ok(index) if index < len && (token == "0" || !token.starts_with('0')) => Ok(index),
+        _ => Err(PatchError::new(format!("invalid array index \"{}\"", token)))
+    }
+}
+
+fn get_mut<'a>(doc: &'a mut Value, tokens: &[String]) -> Result<&'a mut Value> {
+    let mut current = doc;
+    for token in tokens {
+        let next = current;
+        current = match *next {
+            Value::Object(ref mut map) => try!(map.get_mut(token).ok_or_else(||
+                PatchError::new(format!("no member \"{}\"", token)))),
+            Value::Array(ref mut array) => {
+                let index = try!(parse_index(token, array.len()));
+                &mut array[index]
+            }
+            _ => return Err(PatchError::new(format!("cannot find \"{}\" in a scalar value", token)))
+        };
+    }
+    Ok(current)
+}
+
+fn add(doc: &mut Value, pointer: &str, value: Value) -> Result<()> {
+    let tokens = try!(parse_pointer(pointer));
+    let (last, parent) = match tokens.split_last() {
+        Some(split) => split,
+        None => {
+            *doc = value;
+            return Ok(());
+        }
+    };
+
+    match *try!(get_mut(doc, parent)) {
+        Value::Object(ref mut map) => {
+            map.insert(last.clone(), value);
+            Ok(())
+        }
+        Value::Array(ref mut array) => {
+            let len = array.len();
+            let index = if last == "-" { len } else { try!(parse_index(last, len + 1)) };
+            array.insert(index, value);
+            Ok(())
+        }
+        _ => Err(PatchError::new(format!("cannot add to a scalar value at \"{}\"", pointer)))
+    }
+}
+
+fn remove(doc: &mut Value, pointer: &str) -> Result<Value> {
+    let tokens = try!(parse_pointer(pointer));
+    let (last, parent) = try!(tokens.split_last().ok_or_else(|| PatchError::new("cannot remove the whole document")));
+
+    match *try!(get_mut(doc, parent)) {
+        Value::Object(ref mut map) => map.remove(last).ok_or_else(||
+            PatchError::new(format!("no member at \"{}\"", pointer))),
+        Value::Array(ref mut array) => {
+            let index = try!(parse_index(last, array.len()));
+            Ok(array.remove(index))
+        }
+        _ => Err(PatchError::new(format!("cannot remove from a scalar value at \"{}\"", pointer)))
+    }
+}
+
+fn apply_operation(doc: &mut Value, operation: &Operation) -> Result<()> {
+    match *operation {
+        Operation::Add(ref path, ref value) => add(doc, path, value.clone()),
+        Operation::Remove(ref path) => remove(doc, path).map(|_| ()),
+        Operation::Replace(ref path, ref value) => {
+            let tokens = try!(parse_pointer(path));
+            *try!(get_mut(doc, &tokens)) = value.clone();
+            Ok(())
+        }
+        Operation::Move(ref from, ref path) => {
+            if path.starts_with(&(from.clone() + "/")) {
+                return Err(PatchError::new(format!("cannot move \"{}\" into one of its children", from)));
+            }
+            let value = try!(remove(doc, from));
+            add(doc, path, value)
+        }
+        Operation::Copy(ref from, ref path) => {
+            let tokens = try!(parse_pointer(from));
+            let value = try!(get_mut(doc, &tokens)).clone();
+            add(doc, path, value)
+        }
+        Operation::Test(ref path, ref value) => {
+            let tokens = try!(parse_pointer(path));
+            if *try!(get_mut(doc, &tokens)) == *value {
+                Ok(())
+            } else {
+                Err(PatchError::new(format!("test failed for \"{}\"", path)))
+            }
+        }
+    }
+}
@@ -0,0 +1,75 @@
+//! Forwarding of requests to an upstream server.
+
+use hyper::Headers;
+use hyper::status::StatusCode as Status;
+
+use client::{Client, ClientError};
+use request::Request;
+use response::{Action, Response, Result};
+
+/// Hop-by-hop headers are meaningful only for a single connection and must not be forwarded.
+///
+/// See RFC 7230 6.1 Connection, http://httpwg.org/specs/rfc7230.html#header.connection
+const HOP_BY_HOP: &'static [&'static str] = &["connection", "keep-alive", "proxy-authenticate",
+    "proxy-authorization", "te", "trailer", "trailers", "transfer-encoding", "upgrade"];
+
+/// Returns a copy of the given headers, minus hop-by-hop headers and any header in `except`.
+fn copy_headers(from: &Headers, except: &[&str]) -> Headers {
+    let mut headers = Headers::new();
+    for header in from.iter() {
+        let name = header.name().to_lowercase();
+        if !HOP_BY_HOP.contains(&name.as_str()) && !except.contains(&name.as_str()) {
+            headers.set_raw(header.name().to_string(), vec![header.value_string().into_bytes()]);
+        }
+    }
+    headers
+}
+
+/// Forwards the given request to the given URL, and sends back the response of the upstream server.
+///
+/// The method, headers (except hop-by-hop headers and Host), and body of the request are copied,
+/// as are the status and headers of the upstream response.
+/// Both request and response bodies are buffered.
+/// If the URL is invalid or the upstream server cannot be reached, sends a 502 Bad Gateway response;
+/// if the upstream server does not respond in time, sends a 504 Gateway Timeout response.
+///
+/// ```no_run
+/// # extern crate edge;
+/// # use edge::{proxy, Router};
+/// # fn main() {
+/// # let mut router = Router::<()>::new();
+/// router.mount_service("/api", |req, res| {
+///     let mut url = format!("http://localhost:8080/{}", req.param("path").unwrap_or(""));
+///     if let Some(query) = req.raw_query() {
+///         url.push('?');
+///         url.push_str(query);
+///     }
+///     proxy(req, res, &url)
+/// });
+/// # }
+/// ```
+pub fn proxy(req: &Request, res: &mut Response, url: &str) -> Result {
+    let headers = copy_headers(req.headers(), &["host", "content-length"]);
+    let body = req.body().ok().map(|body| body.to_vec());
+
+    let mut client = Client::new();
+    let body = match client.try_send(req.method().clone(), url, headers, body) {
+        Ok(body) => body,
+        Err(ClientError::InvalidUrl(e)) => {
+            warn!("cannot proxy to {}", e);
+            return Err(From::from((Status::BadGateway, "invalid upstream URL")));
+        }
+        Err(ClientError::Timeout) => {
+            warn!("upstream {} timed out", url);
+            return Err(From::from((Status::GatewayTimeout, "upstream server timed out")));
+        }
+        Err(ClientError::NoResponse) => {
+            warn!("no response from upstream {}", url);
+            return Err(From::from((Status::BadGateway, "no response from upstream server")));
+        }
+    };
+
+    res.status(client.status());
+    res.headers = copy_headers(client.headers(), &["content-length"]);
+    Ok(Action::Send(body))
+}
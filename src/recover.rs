@@ -0,0 +1,62 @@
+//! Recovery from panics in handlers.
+
+use backtrace::Backtrace;
+
+use std::any::Any;
+use std::cell::{Cell, RefCell};
+use std::panic::{self, AssertUnwindSafe};
+use std::sync::{Once, ONCE_INIT};
+
+/// Set while a closure runs in `catch`, to `true` if a backtrace is wanted.
+thread_local!(static ARMED: Cell<Option<bool>> = Cell::new(None));
+
+/// Backtrace of the last panic caught on this thread.
+thread_local!(static BACKTRACE: RefCell<Option<String>> = RefCell::new(None));
+
+static HOOK: Once = ONCE_INIT;
+
+/// A panic caught by `catch`.
+pub struct Panic {
+    pub message: String,
+    pub backtrace: Option<String>
+}
+
+/// Installs a panic hook that records a backtrace for panics caught by `catch`,
+/// and leaves reporting them to the caller; other panics go to the previous hook.
+fn install_hook() {
+    HOOK.call_once(|| {
+        let previous = panic::take_hook();
+        panic::set_hook(Box::new(move |info| {
+            match ARMED.with(|armed| armed.get()) {
+                Some(true) => {
+                    let backtrace = format!("{:?}", Backtrace::new());
+                    BACKTRACE.with(|last| *last.borrow_mut() = Some(backtrace));
+                }
+                Some(false) => (),
+                None => previous(info)
+            }
+        }));
+    });
+}
+
+/// Calls the given closure, and returns the panic it raised (if any), with a backtrace
+/// if `backtrace` is true.
+pub fn catch<F, R>(backtrace: bool, f: F) -> Result<R, Panic> where F: FnOnce() -> R {
+    install_hook();
+
+    let previous = ARMED.with(|armed| armed.get());
+    ARMED.with(|armed| armed.set(Some(backtrace)));
+    let result = panic::catch_unwind(AssertUnwindSafe(f));
+    ARMED.with(|armed| armed.set(previous));
+
+    result.map_err(|payload| Panic {
+        message: message(&*payload),
+        backtrace: BACKTRACE.with(|last| last.borrow_mut().take())
+    })
+}
+
+fn message(payload: &(Any + Send)) -> String {
+    payload.downcast_ref::<&str>().map(|message| message.to_string())
+        .or_else(|| payload.downcast_ref::<String>().cloned())
+        .unwrap_or_else(|| "<no message>".to_string())
+}
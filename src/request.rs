@@ -1,24 +1,44 @@
 extern crate url;
 
 pub use hyper::header as header;
-use header::{Cookie as CookieHeader, ContentType};
+use header::{Cookie as CookieHeader, ContentLength, ContentType, HttpDate, IfUnmodifiedSince, TransferEncoding};
 pub use header::CookiePair as Cookie;
 pub use hyper::status::StatusCode as Status;
 
 use hyper::{Headers, HttpVersion, Method};
-use hyper::uri::RequestUri::{AbsolutePath, Star};
-use hyper::mime::{Mime, TopLevel, SubLevel};
+use hyper::uri::RequestUri::{AbsolutePath, AbsoluteUri, Authority, Star};
+use hyper::mime::{Attr, Mime, TopLevel, SubLevel};
 use hyper::server::Request as HttpRequest;
 
+use std::ascii::AsciiExt;
 use std::borrow::Cow;
+use std::cell::RefCell;
 use std::collections::BTreeMap;
-use std::io::{Error as IoError, ErrorKind};
+use std::io::{Cursor, Error as IoError, ErrorKind};
+use std::net::IpAddr;
+use std::sync::Arc;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::time::Duration;
 
 use buffer::Buffer;
+use conditional;
+use connection::Connection;
+use extensions::Extensions;
+use forwarded::{self, Forwarded, ProxyTrust};
+use multipart::Multipart;
+use patch::Patch;
+use response::Error;
+use signed::{Invalid, Secret};
+use urlencoded;
+
+use serde::Deserialize;
+
+use time;
 
 use serde_json as json;
 
-use url::{ParseError, Url};
+use url::Url;
+use url::percent_encoding::percent_decode;
 
 /// A request, with a path, query, and fragment (accessor methods not yet implemented for the last two).
 ///
@@ -29,24 +49,74 @@ pub struct Request {
     path: Vec<String>,
     query: Option<BTreeMap<String, String>>,
     params: Option<BTreeMap<String, String>>,
-    body: Option<Buffer>
+    body: Option<Buffer>,
+    body_limit: Option<usize>,
+    empty_json_as_null: bool,
+    connection: Connection,
+    extensions: Extensions,
+    trust_proxy: ProxyTrust,
+    secret: Option<Secret>,
+    cancelled: Arc<AtomicBool>,
+    timings: RefCell<Vec<(String, Duration)>>
 }
 
-pub fn new(base_url: &Url, inner: HttpRequest) -> Result<Request, ParseError> {
+/// How invalid percent-encoding in the path or query of requests is handled.
+///
+/// Escapes are malformed when a '%' is not followed by two hexadecimal digits,
+/// and they are invalid when the decoded bytes are not UTF-8.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum UrlDecoding {
+    /// Requests with invalid percent-encoding get a 400 Bad Request response.
+    Strict,
+
+    /// Malformed escapes are kept as is, and invalid UTF-8 is replaced with U+FFFD.
+    Lenient
+}
+
+/// Relation between the origin of a request and the origin of its target,
+/// as given by the `Sec-Fetch-Site` header.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum FetchSite {
+    SameOrigin,
+    SameSite,
+    CrossSite,
+
+    /// The request was initiated by the user, e.g. by typing a URL or using a bookmark.
+    None
+}
+
+/// Mode of a request, as given by the `Sec-Fetch-Mode` header.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum FetchMode {
+    Cors,
+    Navigate,
+    NoCors,
+    SameOrigin,
+    WebSocket
+}
+
+/// Creates a request, decoding the segments of its path and the parameters of its query.
+pub fn new(base_url: &Url, inner: HttpRequest, path_decoding: UrlDecoding, query_decoding: UrlDecoding) -> Result<Request, String> {
     let url = match *inner.uri() {
-        AbsolutePath(ref path) => Some(try!(base_url.join(path))),
+        AbsolutePath(ref path) => Some(try!(base_url.join(path).map_err(|e| format!("invalid request target: {}", e)))),
         Star => None,
-        _ => panic!("unsupported request URI")
+        // 5.3.2 absolute-form, which servers MUST accept
+        // http://httpwg.org/specs/rfc7230.html#absolute-form
+        AbsoluteUri(ref uri) => Some(try!(Url::parse(&uri.to_string()).map_err(|e| format!("invalid request target: {}", e)))),
+        Authority(_) => return Err("authority-form request target is only used by CONNECT, which is not supported".to_string())
     };
 
     let path = match url {
         None => vec!["*".to_owned()],
-        Some(ref url) => url.path_segments().unwrap().map(|s| s.to_string()).collect()
+        Some(ref url) => match url.path_segments() {
+            Some(segments) => try!(segments.map(|segment| decode(segment, true, path_decoding)).collect::<Result<_, String>>()),
+            None => return Err(format!("invalid request target: {}", url))
+        }
     };
 
     let query = match url {
         None => None,
-        Some(ref url) => Some(url.query_pairs().into_owned().collect())
+        Some(ref url) => Some(try!(decode_query(url.query().unwrap_or(""), query_decoding)))
     };
 
     Ok(Request {
@@ -55,7 +125,15 @@ pub fn new(base_url: &Url, inner: HttpRequest) -> Result<Request, ParseError> {
         path: path,
         query: query,
         params: None,
-        body: None})
+        body: None,
+        body_limit: None,
+        empty_json_as_null: false,
+        connection: Connection::new(),
+        extensions: Extensions::new(),
+        trust_proxy: ProxyTrust::Nobody,
+        secret: None,
+        cancelled: Arc::new(AtomicBool::new(false)),
+        timings: RefCell::new(Vec::new())})
 }
 
 pub fn set_body(request: Option<&mut Request>, body: Option<Buffer>) {
@@ -64,6 +142,46 @@ pub fn set_body(request: Option<&mut Request>, body: Option<Buffer>) {
     }
 }
 
+/// Takes the body out of the request.
+pub fn take_body(request: &mut Request) -> Option<Buffer> {
+    request.body.take()
+}
+
+/// Sets the state of the connection on which this request was received.
+pub fn set_connection(request: &mut Request, connection: Connection) {
+    request.connection = connection;
+}
+
+/// Sets the maximum size of the body of this request (if any).
+pub fn set_body_limit(request: &mut Request, limit: Option<usize>) {
+    request.body_limit = limit;
+}
+
+/// Sets whether an empty body is read as JSON `null`.
+pub fn set_empty_json_as_null(request: &mut Request, empty_json_as_null: bool) {
+    request.empty_json_as_null = empty_json_as_null;
+}
+
+/// Sets whether the forwarding headers added by proxies can be trusted for this request.
+pub fn set_trust_proxy(request: &mut Request, trust_proxy: ProxyTrust) {
+    request.trust_proxy = trust_proxy;
+}
+
+/// Returns the timings recorded for this request with `add_timing`.
+pub fn take_timings(request: &Request) -> Vec<(String, Duration)> {
+    request.timings.borrow_mut().drain(..).collect()
+}
+
+/// Sets the flag raised when the client that sent this request goes away.
+pub fn set_cancelled(request: &mut Request, cancelled: Arc<AtomicBool>) {
+    request.cancelled = cancelled;
+}
+
+/// Sets the secret key of the application, used to sign and verify URLs.
+pub fn set_secret(request: &mut Request, secret: Option<Secret>) {
+    request.secret = secret;
+}
+
 impl Request {
     /// Returns this request's body as a vector of bytes.
     pub fn body(&self) -> Result<&[u8], IoError> {
@@ -73,6 +191,34 @@ impl Request {
         }
     }
 
+    /// Returns `true` if this request has no body, or an empty one.
+    fn is_body_empty(&self) -> bool {
+        self.body.as_ref().map_or(true, |buffer| buffer.len() == 0)
+    }
+
+    /// Returns how many more bytes the body of this request could have had under the maximum
+    /// body size (see `Edge::max_body_size`), or `None` if there is no limit.
+    ///
+    /// The body is received in full before the handler is called, so this value is fixed:
+    /// it is the limit minus the size of the body, and does not change as the body is read.
+    /// Handlers can use it to bound what they derive from the body (for instance when
+    /// decoding it), and answer 413 Payload Too Large themselves.
+    pub fn body_limit_headroom(&self) -> Option<usize> {
+        self.body_limit.map(|limit| limit.saturating_sub(self.body.as_ref().map_or(0, |buffer| buffer.len())))
+    }
+
+    /// Returns the length of the body declared by the `Content-Length` header,
+    /// or `None` if the header is missing or the body is chunked.
+    ///
+    /// The length is declared by the client, it may differ from the size of the body
+    /// actually received (which is decoded if compressed).
+    pub fn content_length(&self) -> Option<u64> {
+        if self.headers().has::<TransferEncoding>() {
+            return None;
+        }
+        self.headers().get::<ContentLength>().map(|&ContentLength(len)| len)
+    }
+
     /// Returns an iterator over the cookies of this request.
     pub fn cookies(&self) -> ::std::slice::Iter<Cookie> {
         self.headers().get::<CookieHeader>().map_or([].iter(),
@@ -80,17 +226,74 @@ impl Request {
         )
     }
 
+    /// Returns the state of the connection on which this request was received.
+    ///
+    /// This state is shared by all the requests sent over the same (keep-alive) connection.
+    pub fn connection(&self) -> &Connection {
+        &self.connection
+    }
+
+    /// Returns `true` if the client that sent this request has gone away, in which case
+    /// the response will not be sent.
+    ///
+    /// Long-running handlers can check this from time to time to stop early;
+    /// writes to a stream fail with a `BrokenPipe` error once the client is gone.
+    pub fn is_cancelled(&self) -> bool {
+        self.cancelled.load(Ordering::SeqCst)
+    }
+
+    /// Records the duration of a named step of the processing of this request,
+    /// such as a database query, to be reported in the `Server-Timing` header of the response
+    /// when enabled (see `Edge::server_timing`).
+    pub fn add_timing(&self, name: &str, duration: Duration) {
+        self.timings.borrow_mut().push((name.to_string(), duration));
+    }
+
+    /// Returns the values attached to this request, e.g. by middleware for the handler.
+    pub fn extensions(&self) -> &Extensions {
+        &self.extensions
+    }
+
+    /// Returns the values attached to this request, so that middleware can add values.
+    ///
+    /// ```
+    /// use edge::{Request, Response, Router};
+    ///
+    /// #[derive(Default)]
+    /// struct App;
+    /// struct CurrentUser(String);
+    ///
+    /// fn authenticate(_app: &mut App, req: &mut Request, _res: &mut Response) {
+    ///     let user = req.query("user").unwrap_or("anonymous").to_string();
+    ///     req.extensions_mut().insert(CurrentUser(user));
+    /// }
+    ///
+    /// let mut router = Router::<App>::new();
+    /// router.add_middleware(authenticate);
+    /// // handlers then call req.extensions().get::<CurrentUser>()
+    /// ```
+    pub fn extensions_mut(&mut self) -> &mut Extensions {
+        &mut self.extensions
+    }
+
     /// Parses the body of this request as an URL-encoded form.
     ///
     /// The Content-Type header must indicate ```application/x-www-form-urlencoded```.
     /// Returns a (key, value) map of clone-on-write strings.
+    ///
+    /// Values are decoded as ISO-8859-1 when the Content-Type declares this charset
+    /// (as legacy clients do), and as UTF-8 otherwise.
     pub fn form<'a>(&'a self) -> Result<BTreeMap<Cow<'a, str>, Cow<'a, str>>, IoError> {
         let body = try!(self.body());
 
         match self.headers().get::<ContentType>() {
-            Some(&ContentType(Mime(TopLevel::Application, SubLevel::WwwFormUrlEncoded, _))) => {
-                let parse = url::form_urlencoded::parse(body);
-                Ok(parse.collect())
+            Some(&ContentType(ref mime @ Mime(TopLevel::Application, SubLevel::WwwFormUrlEncoded, _))) => {
+                if is_latin1(mime) {
+                    Ok(parse_latin1_form(body).into_iter().map(|(name, value)| (name.into(), value.into())).collect())
+                } else {
+                    let parse = url::form_urlencoded::parse(body);
+                    Ok(parse.collect())
+                }
             }
             Some(_) => Err(IoError::new(ErrorKind::InvalidInput, "invalid Content-Type, expected application/x-www-form-urlencoded")),
             None => Err(IoError::new(ErrorKind::InvalidInput, "missing Content-Type header"))
@@ -98,7 +301,18 @@ impl Request {
     }
 
     /// Parses the body of this request as JSON (indicated by ```application/json``` content type).
+    ///
+    /// An empty body is an error, or `null` when empty JSON bodies are accepted
+    /// (see `Edge::empty_json_as_null`).
     pub fn json(&self) -> Result<json::Value, json::Error> {
+        if self.is_body_empty() {
+            return if self.empty_json_as_null {
+                Ok(json::Value::Null)
+            } else {
+                Err(json::Error::Io(IoError::new(ErrorKind::UnexpectedEof, "empty body")))
+            };
+        }
+
         let body = try!(self.body());
 
         match self.headers().get::<ContentType>() {
@@ -110,6 +324,156 @@ impl Request {
         }
     }
 
+    /// Deserializes the body of this request into a value of type `T`, according to its Content-Type:
+    /// either JSON (```application/json```) or an URL-encoded form (```application/x-www-form-urlencoded```),
+    /// so that a handler can accept both.
+    ///
+    /// Returns a 415 Unsupported Media Type error for other content types, and a 400 Bad Request
+    /// error if the body cannot be deserialized, so that handlers can just use `try!`.
+    ///
+    /// An empty body is a 400 Bad Request error, unless empty JSON bodies are accepted
+    /// (see `Edge::empty_json_as_null`), in which case it is deserialized from `null`:
+    /// an `Option` is `None`, and other types generally fail with a 400 Bad Request.
+    pub fn parse_body<T: Deserialize>(&self) -> Result<T, Error> {
+        if self.is_body_empty() {
+            return if self.empty_json_as_null {
+                json::from_value(json::Value::Null).map_err(|e| (Status::BadRequest, format!("empty body: {}", e)).into())
+            } else {
+                Err((Status::BadRequest, "empty body").into())
+            };
+        }
+
+        let (is_json, latin1) = match self.headers().get::<ContentType>() {
+            Some(&ContentType(Mime(TopLevel::Application, SubLevel::Json, _))) => (true, false),
+            Some(&ContentType(ref mime @ Mime(TopLevel::Application, SubLevel::WwwFormUrlEncoded, _))) => (false, is_latin1(mime)),
+            _ => return Err((Status::UnsupportedMediaType, "expected application/json or application/x-www-form-urlencoded").into())
+        };
+
+        let body = try!(self.body().map_err(|e| Error::from((Status::BadRequest, e.to_string()))));
+        if is_json {
+            json::from_slice(body).map_err(|e| (Status::BadRequest, format!("invalid JSON body: {}", e)).into())
+        } else {
+            let pairs = if latin1 {
+                parse_latin1_form(body)
+            } else {
+                url::form_urlencoded::parse(body).into_owned().collect()
+            };
+            urlencoded::from_pairs(pairs).map_err(|e| (Status::BadRequest, format!("invalid form: {}", e)).into())
+        }
+    }
+
+    /// Returns a reader of the parts of the body of this request, which must be `multipart/form-data`.
+    ///
+    /// Parts are read one at a time from the body, which is buffered in memory before
+    /// the handler is called; the body of each part can be read in chunks
+    /// (or saved to a file with `Part::save_to`) without making another copy of it.
+    /// Returns a 415 Unsupported Media Type error for other content types,
+    /// or a 400 Bad Request if the boundary is missing.
+    pub fn multipart(&self) -> Result<Multipart<Cursor<&[u8]>>, Error> {
+        let boundary = match self.headers().get::<ContentType>() {
+            Some(&ContentType(ref mime @ Mime(TopLevel::Multipart, SubLevel::FormData, _))) =>
+                try!(mime.get_param(Attr::Boundary).ok_or((Status::BadRequest, "missing multipart boundary"))).to_string(),
+            _ => return Err((Status::UnsupportedMediaType, "expected multipart/form-data").into())
+        };
+
+        Ok(Multipart::new(Cursor::new(self.body.as_ref().map_or(&[][..], |buffer| buffer.as_ref())), &boundary))
+    }
+
+    /// Parses the body of this request as a patch, according to its Content-Type:
+    /// either ```application/merge-patch+json``` (JSON Merge Patch)
+    /// or ```application/json-patch+json``` (JSON Patch).
+    pub fn patch(&self) -> Result<Patch, json::Error> {
+        let body = try!(self.body());
+
+        let is_json_patch = match self.headers().get::<ContentType>() {
+            Some(&ContentType(Mime(TopLevel::Application, SubLevel::Ext(ref sub), _))) if sub == "merge-patch+json" => false,
+            Some(&ContentType(Mime(TopLevel::Application, SubLevel::Ext(ref sub), _))) if sub == "json-patch+json" => true,
+            Some(_) => return Err(json::Error::Io(IoError::new(ErrorKind::InvalidInput, "invalid Content-Type, expected application/merge-patch+json or application/json-patch+json"))),
+            None => return Err(json::Error::Io(IoError::new(ErrorKind::InvalidInput, "missing Content-Type header")))
+        };
+
+        let value = try!(json::from_slice(body));
+        if is_json_patch {
+            Patch::json_patch(&value).map_err(|e| json::Error::Io(IoError::new(ErrorKind::InvalidData, e)))
+        } else {
+            Ok(Patch::Merge(value))
+        }
+    }
+
+    /// Returns the elements of the `Forwarded` header (RFC 7239) of this request,
+    /// the first one being added by the proxy closest to the client.
+    ///
+    /// These values are reported by the client and the proxies, and can be forged
+    /// unless the server is behind a proxy that sets this header.
+    pub fn forwarded(&self) -> Vec<Forwarded> {
+        let lines = self.headers().get_raw("Forwarded").map_or(Vec::new(), |lines|
+            lines.iter().filter_map(|line| ::std::str::from_utf8(line).ok()).collect()
+        );
+        forwarded::parse(&lines)
+    }
+
+    /// Returns the IP address of the client as reported by the proxies in front of the server,
+    /// from the `Forwarded` header or else the `X-Forwarded-For` header.
+    ///
+    /// Returns `None` unless proxy trust is enabled (see `Edge::trust_proxy` and
+    /// `Edge::trusted_proxies`).
+    pub fn real_ip(&self) -> Option<IpAddr> {
+        let forwarded = self.forwarded();
+        if !forwarded.is_empty() {
+            return self.trusted(forwarded).and_then(|forwarded| forwarded.client_ip());
+        }
+
+        self.trusted(self.header_values("X-Forwarded-For")).and_then(|ip| ip.parse().ok())
+    }
+
+    /// Returns the scheme used by the client to make this request, usually "http" or "https".
+    ///
+    /// When proxy trust is enabled (see `Edge::trust_proxy`), the scheme is taken from the
+    /// `Forwarded` header or else the `X-Forwarded-Proto` header, if present.
+    pub fn scheme(&self) -> String {
+        let forwarded = self.trusted(self.forwarded()).and_then(|forwarded| forwarded.proto);
+        let proto = forwarded.or_else(||
+            self.trusted(self.header_values("X-Forwarded-Proto")).map(|proto| proto.to_ascii_lowercase())
+        );
+        if let Some(proto) = proto {
+            return proto;
+        }
+
+        self.url.as_ref().map_or("http", |url| url.scheme()).to_string()
+    }
+
+    /// Returns the host to which this request was made, without the port.
+    ///
+    /// This is the value of the `Host` header, or when proxy trust is enabled
+    /// (see `Edge::trust_proxy`), the host reported by the `Forwarded` header
+    /// or else the `X-Forwarded-Host` header, if present.
+    pub fn host(&self) -> Option<String> {
+        self.host_with_port().map(|host| strip_port(&host).to_string())
+    }
+
+    /// Returns the host to which this request was made, including the port (if any).
+    ///
+    /// See `host` for how the host is determined.
+    pub fn host_with_port(&self) -> Option<String> {
+        let forwarded = self.trusted(self.forwarded()).and_then(|forwarded| forwarded.host);
+        let host = forwarded.or_else(||
+            self.trusted(self.header_values("X-Forwarded-Host")).map(|host| host.to_string())
+        );
+        if host.is_some() {
+            return host;
+        }
+
+        self.headers().get::<header::Host>().map(|host| match host.port {
+            Some(port) => format!("{}:{}", host.hostname, port),
+            None => host.hostname.clone()
+        })
+    }
+
+    /// Returns the entry of a forwarding header that is trusted according to the proxy trust policy.
+    fn trusted<T>(&self, mut entries: Vec<T>) -> Option<T> {
+        self.trust_proxy.select(entries.len()).map(|index| entries.swap_remove(index))
+    }
+
     /// Returns the HTTP version
     pub fn version(&self) -> &HttpVersion {
         self.inner.version()
@@ -125,20 +489,277 @@ impl Request {
     pub fn headers(&self) -> &Headers { self.inner.headers() }
 
     /// Returns the parameter with the given name declared by the route that matched the URL of this request (if any).
+    ///
+    /// The value is borrowed from the request, no allocation takes place.
     pub fn param(&self, key: &str) -> Option<&str> {
         self.params.as_ref().map_or(None, |map| map.get(key).map(String::as_str))
     }
 
+    /// Returns a copy of the parameter with the given name, for use beyond the lifetime of the request
+    /// (for instance in a closure passed to `stream`).
+    pub fn param_owned(&self, key: &str) -> Option<String> {
+        self.param(key).map(str::to_string)
+    }
+
+    /// Returns the URL of this request, from which the path, query, and fragment are derived.
+    ///
+    /// Returns `None` for a request to the server as a whole (e.g. `OPTIONS *`).
+    pub fn uri(&self) -> Option<&Url> {
+        self.url.as_ref()
+    }
+
     /// Returns the path of this request, i.e. the list of segments of the URL.
     pub fn path(&self) -> &[String] {
         &self.path
     }
 
+    /// Replaces the path used to route this request, e.g. in a rewrite hook (see `Edge::rewrite`).
+    ///
+    /// The given path must be absolute, e.g. "/about". The URL returned by `uri` is unchanged.
+    pub fn set_path(&mut self, path: &str) {
+        self.path = path.trim_left_matches('/').split('/').map(str::to_string).collect();
+    }
+
+    /// Sets a parameter of this request, e.g. in a rewrite hook (see `Edge::rewrite`).
+    ///
+    /// Parameters declared by the route that matches the request take precedence.
+    pub fn set_param<K: Into<String>, V: Into<String>>(&mut self, key: K, value: V) {
+        if self.params.is_none() {
+            self.params = Some(BTreeMap::new());
+        }
+        self.params.as_mut().unwrap().insert(key.into(), value.into());
+    }
+
+    /// Returns the best language among `available` according to the `Accept-Language` header.
+    ///
+    /// Languages are tried by decreasing quality value. A language range matches an available
+    /// language if they are equal (ignoring case), or if one is a prefix of the other,
+    /// so that `en-US` matches `en` and vice versa.
+    /// Returns `None` if the header is missing or no available language is acceptable.
+    pub fn preferred_language<'a>(&self, available: &[&'a str]) -> Option<&'a str> {
+        let mut ranges = self.header_values("Accept-Language").into_iter().filter_map(|item| {
+            let mut parts = item.split(';').map(str::trim);
+            let range = parts.next().unwrap_or("");
+            let quality = parts.filter_map(|param| if param.starts_with("q=") {
+                param[2..].parse::<f32>().ok()
+            } else {
+                None
+            }).next().unwrap_or(1.0);
+
+            if range.is_empty() || quality <= 0.0 {
+                None
+            } else {
+                Some((range, quality))
+            }
+        }).collect::<Vec<_>>();
+
+        // stable sort so that ranges with the same quality keep their order
+        ranges.sort_by(|a, b| b.1.partial_cmp(&a.1).unwrap_or(::std::cmp::Ordering::Equal));
+
+        for (range, _) in ranges {
+            if range == "*" {
+                return available.first().map(|lang| *lang);
+            }
+
+            let exact = available.iter().find(|lang| lang.eq_ignore_ascii_case(range));
+            let found = exact.or_else(|| available.iter().find(|lang|
+                is_language_prefix(lang, range) || is_language_prefix(range, lang)
+            ));
+
+            if let Some(lang) = found {
+                return Some(*lang);
+            }
+        }
+
+        None
+    }
+
+    /// Returns `true` if this request asks to upgrade the connection to a WebSocket.
+    ///
+    /// RFC 6455 4.2.1 Reading the Client's Opening Handshake
+    /// https://tools.ietf.org/html/rfc6455#section-4.2.1
+    ///
+    /// The request must be a GET with `Connection: Upgrade`, `Upgrade: websocket`,
+    /// `Sec-WebSocket-Version: 13` and a `Sec-WebSocket-Key` header.
+    pub fn is_websocket_upgrade(&self) -> bool {
+        *self.method() == Method::Get &&
+            self.header_values("Connection").iter().any(|option| option.eq_ignore_ascii_case("upgrade")) &&
+            self.header_values("Upgrade").iter().any(|protocol| protocol.eq_ignore_ascii_case("websocket")) &&
+            self.header_values("Sec-WebSocket-Version") == ["13"] &&
+            !self.header_values("Sec-WebSocket-Key").is_empty()
+    }
+
+    /// Returns the relation between the site that initiated this request and its target,
+    /// from the `Sec-Fetch-Site` header sent by browsers (if any).
+    ///
+    /// Fetch Metadata Request Headers
+    /// https://www.w3.org/TR/fetch-metadata/
+    ///
+    /// This can be used to reject cross-site requests that change state, as a defense
+    /// against CSRF. Requests from clients that are not browsers, or from older browsers,
+    /// have no such header:
+    ///
+    /// ```
+    /// # use edge::{Error, FetchSite, Request, Response, Status};
+    /// # #[derive(Default)] struct App;
+    /// fn same_site_writes(_app: &mut App, req: &mut Request, _res: &mut Response) -> Result<(), Error> {
+    ///     if !req.method().safe() && req.fetch_site() == Some(FetchSite::CrossSite) {
+    ///         return Err((Status::Forbidden, "cross-site request").into());
+    ///     }
+    ///     Ok(())
+    /// }
+    /// ```
+    pub fn fetch_site(&self) -> Option<FetchSite> {
+        self.fetch_metadata("Sec-Fetch-Site").and_then(|value| match value {
+            "same-origin" => Some(FetchSite::SameOrigin),
+            "same-site" => Some(FetchSite::SameSite),
+            "cross-site" => Some(FetchSite::CrossSite),
+            "none" => Some(FetchSite::None),
+            _ => None
+        })
+    }
+
+    /// Returns the mode of this request, from the `Sec-Fetch-Mode` header sent by browsers (if any).
+    pub fn fetch_mode(&self) -> Option<FetchMode> {
+        self.fetch_metadata("Sec-Fetch-Mode").and_then(|value| match value {
+            "cors" => Some(FetchMode::Cors),
+            "navigate" => Some(FetchMode::Navigate),
+            "no-cors" => Some(FetchMode::NoCors),
+            "same-origin" => Some(FetchMode::SameOrigin),
+            "websocket" => Some(FetchMode::WebSocket),
+            _ => None
+        })
+    }
+
+    /// Returns the destination of this request, e.g. "document", "image" or "script",
+    /// from the `Sec-Fetch-Dest` header sent by browsers (if any).
+    pub fn fetch_dest(&self) -> Option<&str> {
+        self.fetch_metadata("Sec-Fetch-Dest")
+    }
+
+    /// Returns `true` if this request was triggered by the user (e.g. by clicking a link),
+    /// as indicated by the `Sec-Fetch-User` header.
+    pub fn fetch_user(&self) -> bool {
+        self.fetch_metadata("Sec-Fetch-User") == Some("?1")
+    }
+
+    /// Returns the value of the given fetch metadata header, which is a single token.
+    fn fetch_metadata(&self, name: &str) -> Option<&str> {
+        let values = self.header_values(name);
+        if values.len() == 1 {
+            Some(values[0])
+        } else {
+            None
+        }
+    }
+
+    /// Returns the values of the header with the given name, split on commas.
+    fn header_values(&self, name: &str) -> Vec<&str> {
+        self.headers().get_raw(name).map_or(Vec::new(), |lines|
+            lines.iter()
+                .filter_map(|line| ::std::str::from_utf8(line).ok())
+                .flat_map(|line| line.split(','))
+                .map(str::trim)
+                .filter(|value| !value.is_empty())
+                .collect()
+        )
+    }
+
+    /// Returns the date of the If-Unmodified-Since header (if any).
+    pub fn if_unmodified_since(&self) -> Option<&HttpDate> {
+        self.headers().get::<IfUnmodifiedSince>().map(|header| &header.0)
+    }
+
+    /// Checks the If-Unmodified-Since header of this request against the date the resource
+    /// was last modified, typically before updating or deleting it.
+    ///
+    /// Returns a 412 Precondition Failed error if the resource was modified after the given date.
+    pub fn check_unmodified_since(&self, modified: &HttpDate) -> Result<(), Error> {
+        if conditional::precondition_failed(self, modified) {
+            Err((Status::PreconditionFailed, "resource modified since the given date").into())
+        } else {
+            Ok(())
+        }
+    }
+
     /// Returns the parameter with the given name in this request's query (if any).
     pub fn query(&self, key: &str) -> Option<&str> {
         self.query.as_ref().map_or(None, |map| map.get(key).map(String::as_str))
     }
 
+    /// Returns the query of this request exactly as it was received, i.e. the part of
+    /// the request target after '?', without decoding (if any).
+    ///
+    /// Unlike `query`, this keeps the order and encoding of parameters, for instance
+    /// to verify a signature computed over the query.
+    pub fn raw_query(&self) -> Option<&str> {
+        let query = match *self.inner.uri() {
+            AbsolutePath(ref target) => target.find('?').map(|index| &target[index + 1..]),
+            AbsoluteUri(ref url) => url.query(),
+            Authority(_) | Star => None
+        };
+        query.map(|query| query.split('#').next().unwrap())
+    }
+
+    /// Returns the path of this request exactly as it was received, without decoding.
+    fn raw_path(&self) -> Option<&str> {
+        match *self.inner.uri() {
+            AbsolutePath(ref target) => target.split(|c| c == '?' || c == '#').next(),
+            AbsoluteUri(ref url) => Some(url.path()),
+            Authority(_) | Star => None
+        }
+    }
+
+    /// Deserializes the query of this request into a value of type `T`, usually a struct
+    /// whose fields are the parameters of the query.
+    ///
+    /// Values are parsed according to the types of the fields, and fields marked with
+    /// `#[serde(default)]` can be omitted from the query. Returns a 400 Bad Request error
+    /// if the query does not match the type, so that handlers can just use `try!`.
+    ///
+    /// Parameters are decoded like those returned by `query` (see `Edge::url_decoding`),
+    /// and likewise, when a parameter is repeated, only its last value is kept:
+    /// "a=1&a=2" gives "2" for `a`.
+    pub fn query_as<T: Deserialize>(&self) -> Result<T, Error> {
+        let pairs = self.query.as_ref().map_or(Vec::new(), |map| map.clone().into_iter().collect());
+        urlencoded::from_pairs(pairs).map_err(|e| (Status::BadRequest, format!("invalid query: {}", e)).into())
+    }
+
+    /// Returns the given URL (path and query) with an expiry time and a signature added
+    /// to its query, so that it can be given out as a temporary link, checked with
+    /// `verify_signed_url` when it is requested.
+    ///
+    /// The URL must be given exactly as it will be requested, i.e. already percent-encoded,
+    /// because the signature is checked against the path and query as they are received.
+    ///
+    /// Returns `None` if no secret key is set (see `Edge::secret_key`).
+    pub fn sign_url(&self, url: &str, valid_for: Duration) -> Option<String> {
+        let expires = time::get_time().sec as u64 + valid_for.as_secs();
+        self.secret.as_ref().map(|secret| secret.sign_url(url, expires))
+    }
+
+    /// Checks that the URL of this request was signed with `sign_url` and has not expired.
+    ///
+    /// Returns a 403 Forbidden error if the URL is not signed, has been modified, or has expired.
+    pub fn verify_signed_url(&self) -> Result<(), Error> {
+        let secret = match self.secret {
+            Some(ref secret) => secret,
+            None => {
+                error!("cannot verify signed URL, no secret key is set");
+                return Err(Status::Forbidden.into());
+            }
+        };
+
+        // check the URL as it was received, as re-serializing it may change its encoding
+        let path = try!(self.raw_path().ok_or(Error::from(Status::Forbidden)));
+        let now = time::get_time().sec as u64;
+        secret.verify_url(path, self.raw_query(), now).map_err(|invalid| match invalid {
+            Invalid::Unsigned => (Status::Forbidden, "missing signature").into(),
+            Invalid::Tampered => (Status::Forbidden, "invalid signature").into(),
+            Invalid::Expired => (Status::Forbidden, "link expired").into()
+        })
+    }
+
     /// Returns the fragment of this request (if any).
     pub fn fragment(&self) -> Option<&str> {
         match self.url {
@@ -148,7 +769,106 @@ impl Request {
     }
 }
 
-/// Sets the parameters declared by the route that matched the URL of this request.
+/// Names of the charsets whose characters are single bytes of ISO-8859-1 (US-ASCII is a subset).
+const LATIN1_CHARSETS: &'static [&'static str] = &["iso-8859-1", "latin1", "l1", "us-ascii", "ascii"];
+
+/// Returns `true` if the given media type declares the ISO-8859-1 charset.
+fn is_latin1(mime: &Mime) -> bool {
+    mime.get_param(Attr::Charset).map_or(false, |charset|
+        LATIN1_CHARSETS.iter().any(|name| charset.as_str().eq_ignore_ascii_case(name)))
+}
+
+/// Parses the given URL-encoded form, whose percent-decoded bytes are ISO-8859-1 characters.
+fn parse_latin1_form(body: &[u8]) -> Vec<(String, String)> {
+    let decode = |component: &[u8]| -> String {
+        let component: Vec<u8> = component.iter().map(|&b| if b == b'+' { b' ' } else { b }).collect();
+        percent_decode(&component).map(|b| b as char).collect()
+    };
+
+    body.split(|&b| b == b'&').filter(|pair| !pair.is_empty()).map(|pair| {
+        let mut parts = pair.splitn(2, |&b| b == b'=');
+        let name = decode(parts.next().unwrap());
+        let value = decode(parts.next().unwrap_or(&[]));
+        (name, value)
+    }).collect()
+}
+
+/// Returns `true` if `prefix` is a language prefix of `tag`, e.g. `en` is a prefix of `en-US`.
+fn is_language_prefix(prefix: &str, tag: &str) -> bool {
+    tag.len() > prefix.len() && tag.as_bytes()[prefix.len()] == b'-' &&
+        tag[..prefix.len()].eq_ignore_ascii_case(prefix)
+}
+
+/// Removes the port (if any) from the given host, e.g. "example.com:8080" becomes "example.com".
+///
+/// IPv6 literals are kept in brackets, so "[::1]:8080" becomes "[::1]".
+fn strip_port(host: &str) -> &str {
+    let start = if host.starts_with('[') {
+        host.find(']').unwrap_or(0)
+    } else {
+        0
+    };
+
+    match host[start..].rfind(':') {
+        Some(index) => &host[..start + index],
+        None => host
+    }
+}
+
+/// Sets the parameters declared by the route that matched the URL of this request,
+/// in addition to those set by rewrite hooks.
 pub fn set_params(request: &mut Request, params: BTreeMap<String, String>) {
+    if let Some(ref mut existing) = request.params {
+        existing.extend(params);
+        return;
+    }
     request.params = Some(params);
 }
+
+/// Decodes the parameters of the given query (`application/x-www-form-urlencoded`).
+fn decode_query(query: &str, decoding: UrlDecoding) -> Result<BTreeMap<String, String>, String> {
+    let mut params = BTreeMap::new();
+    for pair in query.split('&').filter(|pair| !pair.is_empty()) {
+        let mut parts = pair.splitn(2, '=');
+        let name = try!(decode(&parts.next().unwrap().replace('+', " "), false, decoding));
+        let value = try!(decode(&parts.next().unwrap_or("").replace('+', " "), false, decoding));
+        params.insert(name, value);
+    }
+    Ok(params)
+}
+
+/// Decodes the percent-encoded bytes of the given path segment or query component.
+///
+/// An encoded slash is kept as is in path segments (`keep_slash`), so that it is never
+/// mistaken for a separator, for instance in a parameter used as a file path.
+fn decode(component: &str, keep_slash: bool, decoding: UrlDecoding) -> Result<String, String> {
+    let bytes = component.as_bytes();
+    let mut decoded = Vec::with_capacity(bytes.len());
+    let mut i = 0;
+    while i < bytes.len() {
+        if bytes[i] == b'%' {
+            let digits = (bytes.get(i + 1).and_then(|&b| (b as char).to_digit(16)),
+                bytes.get(i + 2).and_then(|&b| (b as char).to_digit(16)));
+            match digits {
+                (Some(high), Some(low)) => {
+                    let byte = (high * 16 + low) as u8;
+                    if !(keep_slash && byte == b'/') {
+                        decoded.push(byte);
+                        i += 3;
+                        continue;
+                    }
+                }
+                _ if decoding == UrlDecoding::Strict => return Err(format!("malformed percent-encoding in {}", component)),
+                _ => ()
+            }
+        }
+
+        decoded.push(bytes[i]);
+        i += 1;
+    }
+
+    match decoding {
+        UrlDecoding::Strict => String::from_utf8(decoded).map_err(|_| format!("invalid UTF-8 in percent-encoded {}", component)),
+        UrlDecoding::Lenient => Ok(String::from_utf8_lossy(&decoded).into_owned())
+    }
+}
@@ -1,19 +1,31 @@
-use hyper::header::{self, CookiePair as Cookie, ContentType, Header, SetCookie};
+use hyper::header::{self, Accept, CacheControl, CacheDirective, CookiePair as Cookie, ContentType, EntityTag, Header, HttpDate, SetCookie};
 use hyper::status::StatusCode as Status;
 
-use hyper::Headers;
+use hyper::{Headers, Method};
 use hyper::mime::{Mime, TopLevel, SubLevel, Attr, Value};
 
+use serde::Serialize;
+
+use serde_json;
 use serde_json::value as json;
 use serde_json::value::ToJson;
 
+use time;
+
+use conditional;
+use csp::Csp;
+use request::Request;
+
 use std::any::Any;
+use std::ascii::AsciiExt;
 use std::boxed::Box;
 use std::borrow::Cow;
-use std::{error, fmt, result};
+use std::cell::RefCell;
+use std::{cmp, error, fmt, result};
 use std::fs::File;
-use std::io::{self, ErrorKind, Read, Write};
+use std::io::{self, ErrorKind, Read, Seek, SeekFrom, Write};
 use std::path::Path;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
 
 /// Defines a handler error
 #[derive(Debug)]
@@ -90,9 +102,25 @@ pub enum Action {
     /// Sends the response with the given bytes as the body.
     Send(Vec<u8>),
 
+    /// Sends the given JSON value serialized as the body.
+    ///
+    /// If no Content-Type header is set, the content type is set to `application/json`,
+    /// without a charset parameter since JSON text is always encoded in UTF-8 (RFC 8259).
+    /// Set the Content-Type explicitly to use another value.
+    Json(json::Value),
+
     /// Returns a closure that is called with a Stream argument.
     Stream(Box<Fn(&mut Any, &mut Write)>),
 
+    /// Returns a closure that is called with the response, to send its body later.
+    Defer(Box<FnMut(Deferred)>),
+
+    /// Sends the given response with the given body (see `Response::body`).
+    ///
+    /// Its status and headers replace those of the response given to the handler,
+    /// other headers of that response are kept.
+    Respond(Box<Response>, Vec<u8>),
+
     /// Sends the given file, setting the Content-Type based on the file's extension.
     ///
     /// Known extensions are:
@@ -169,10 +197,10 @@ impl From<String> for Action {
     }
 }
 
-/// Conversion from `json::Value` into `Action::Send(bytes)`.
+/// Conversion from `json::Value` into `Action::Json(json)`.
 impl From<json::Value> for Action {
     fn from(json: json::Value) -> Action {
-        From::from(json.to_string())
+        Action::Json(json)
     }
 }
 
@@ -181,6 +209,14 @@ impl From<json::Value> for Action {
 /// The closure will be called with a writer implementing the `Write` trait
 /// so that each call to `write` notifies the handler that data can be written
 /// to the HTTP transport.
+///
+/// If the total length is known in advance, set it with `Response::len` before returning,
+/// so that the response is not sent with chunked encoding. In that case, writing more bytes
+/// than declared is an error, and writing fewer is logged when the stream ends.
+///
+/// So that the data reaches clients as soon as it is written, streaming responses are sent
+/// with `X-Accel-Buffering: no` and `Cache-Control: no-cache, no-transform` to keep proxies
+/// from buffering them; set these headers on the response to override the defaults.
 pub fn stream<F, T, R>(closure: F) -> Result where T: Any, F: 'static + Fn(&mut T, &mut Write) -> io::Result<R> {
     Ok(Action::Stream(Box::new(move |any, writer| {
         if let Some(app) = any.downcast_mut::<T>() {
@@ -191,6 +227,428 @@ pub fn stream<F, T, R>(closure: F) -> Result where T: Any, F: 'static + Fn(&mut
     })))
 }
 
+/// Copies the content of the file at the given path to the given writer, in chunks,
+/// returning the number of bytes copied.
+///
+/// This is meant to be used within `stream`, to send a file between dynamic content:
+///
+/// ```no_run
+/// use edge::{append_file, stream, Request, Response, Result};
+/// use std::io::Write;
+///
+/// fn report(_req: &Request, res: &mut Response) -> Result {
+///     res.content_type("text/csv");
+///     stream(|_app: &mut (), writer| {
+///         try!(writer.write_all(b"date,amount\n"));
+///         try!(append_file(writer, "data/report.csv"));
+///         writer.write_all(b"total,42\n")
+///     })
+/// }
+/// ```
+pub fn append_file<P: AsRef<Path>>(writer: &mut Write, path: P) -> io::Result<u64> {
+    let mut file = try!(File::open(path));
+    let mut chunk = vec![0; APPEND_CHUNK_SIZE];
+    let mut copied = 0;
+    loop {
+        let read = match file.read(&mut chunk) {
+            Ok(0) => return Ok(copied),
+            Ok(read) => read,
+            Err(ref e) if e.kind() == ErrorKind::Interrupted => continue,
+            Err(e) => return Err(e)
+        };
+        try!(writer.write_all(&chunk[..read]));
+        copied += read as u64;
+    }
+}
+
+/// Size of the chunks in which `append_file` copies a file.
+const APPEND_CHUNK_SIZE: usize = 64 * 1024;
+
+/// Wraps the given closure in a box and returns `Ok(Action::Defer(box))`.
+///
+/// Use this to complete the response asynchronously: the status and headers are set
+/// on the response by the handler before returning, then the response is moved into
+/// the `Deferred` value given to the closure. That value can be moved to another thread,
+/// and the body is sent by calling `Deferred::send`.
+///
+/// ```no_run
+/// use edge::{defer, Request, Response, Result};
+/// use std::thread;
+///
+/// fn handler(_req: &Request, res: &mut Response) -> Result {
+///     res.content_type("text/plain");
+///     defer(|deferred| {
+///         thread::spawn(move || deferred.send("Hello, world!"));
+///     })
+/// }
+/// ```
+pub fn defer<F>(closure: F) -> Result where F: 'static + FnOnce(Deferred) {
+    let mut closure = Some(closure);
+    Ok(Action::Defer(Box::new(move |deferred| {
+        // the closure is only called once
+        if let Some(closure) = closure.take() {
+            closure(deferred);
+        }
+    })))
+}
+
+/// A response whose headers are set, and whose body is sent later (see `defer`).
+///
+/// The headers cannot be modified anymore, and since sending the body consumes this value,
+/// the response can only be sent once: sending it twice does not compile.
+/// If it is dropped without being sent, a 500 Internal Server Error response is sent instead.
+///
+/// ```compile_fail
+/// # use edge::Deferred;
+/// fn reply(deferred: Deferred) {
+///     deferred.send("first");
+///     deferred.send("second");
+/// }
+/// ```
+pub struct Deferred {
+    response: Option<Response>,
+    sender: Box<FnMut(Response, Option<Vec<u8>>) + Send>
+}
+
+impl Deferred {
+    /// Sends the response with the given body.
+    pub fn send<B: Into<Vec<u8>>>(mut self, body: B) {
+        let body = body.into();
+        let mut response = self.response.take().unwrap();
+        response.len(body.len() as u64);
+        (self.sender)(response, Some(body));
+    }
+
+    /// Sends the response with no body.
+    pub fn end(mut self) {
+        let response = self.response.take().unwrap();
+        (self.sender)(response, None);
+    }
+}
+
+impl Drop for Deferred {
+    fn drop(&mut self) {
+        if let Some(mut response) = self.response.take() {
+            // a panic is reported by whoever catches it
+            if !::std::thread::panicking() {
+                error!("deferred response dropped without being sent");
+            }
+            response.status(Status::InternalServerError);
+            (self.sender)(response, None);
+        }
+    }
+}
+
+/// Returns `true` if the Accept header of the request gives JSON a higher quality than HTML.
+fn prefers_json(req: &Request) -> bool {
+    let items = match req.headers().get::<Accept>() {
+        Some(&Accept(ref items)) => items,
+        None => return false
+    };
+
+    // quality of the most specific range matching each type
+    let quality = |top: TopLevel, sub: SubLevel| items.iter().filter_map(|item| {
+        let Mime(ref item_top, ref item_sub, _) = item.item;
+        match (item_top == &top, item_sub == &sub) {
+            (true, true) => Some((2, item.quality.0)),
+            (true, false) if *item_sub == SubLevel::Star => Some((1, item.quality.0)),
+            _ if *item_top == TopLevel::Star && *item_sub == SubLevel::Star => Some((0, item.quality.0)),
+            _ => None
+        }
+    }).max().map_or(0, |(_, quality)| quality);
+
+    quality(TopLevel::Application, SubLevel::Json) > quality(TopLevel::Text, SubLevel::Html)
+}
+
+/// Moves the response into a `Deferred` value, which calls `sender` when it is sent.
+pub fn deferred(response: Response, sender: Box<FnMut(Response, Option<Vec<u8>>) + Send>) -> Deferred {
+    Deferred {
+        response: Some(response),
+        sender: sender
+    }
+}
+
+/// How a file sent with `Response::serve_file` is presented by the browser.
+#[derive(Clone, Debug, PartialEq)]
+pub enum Disposition {
+    /// Displayed in the browser (default).
+    Inline,
+
+    /// Downloaded and saved with the given file name.
+    Attachment(String)
+}
+
+/// Options for sending a file with `Response::serve_file`.
+///
+/// ```
+/// use edge::{CachePolicy, FileOptions};
+/// let options = FileOptions::new()
+///     .content_type("text/csv")
+///     .attachment("report.csv")
+///     .cache_control(CachePolicy::new().no_cache());
+/// ```
+#[derive(Clone, Debug, Default)]
+pub struct FileOptions {
+    content_type: Option<String>,
+    disposition: Option<Disposition>,
+    cache_control: Option<CachePolicy>
+}
+
+impl FileOptions {
+    /// Creates options with the defaults of `send_file`.
+    pub fn new() -> FileOptions {
+        FileOptions::default()
+    }
+
+    /// Sets the content type, instead of guessing it from the file's extension.
+    pub fn content_type<S: Into<String>>(mut self, content_type: S) -> FileOptions {
+        self.content_type = Some(content_type.into());
+        self
+    }
+
+    /// Sends the file with `Content-Disposition: inline`.
+    pub fn inline(mut self) -> FileOptions {
+        self.disposition = Some(Disposition::Inline);
+        self
+    }
+
+    /// Sends the file as an attachment, to be saved with the given file name.
+    pub fn attachment<S: Into<String>>(mut self, filename: S) -> FileOptions {
+        self.disposition = Some(Disposition::Attachment(filename.into()));
+        self
+    }
+
+    /// Sets the Cache-Control header from the given policy.
+    pub fn cache_control(mut self, policy: CachePolicy) -> FileOptions {
+        self.cache_control = Some(policy);
+        self
+    }
+}
+
+/// Returns the value of the Content-Disposition header for the given disposition.
+///
+/// File names that are not plain ASCII are also given percent-encoded in UTF-8 (RFC 6266).
+fn content_disposition(disposition: &Disposition) -> String {
+    match *disposition {
+        Disposition::Inline => "inline".to_string(),
+        Disposition::Attachment(ref filename) => {
+            let ascii: String = filename.chars().map(|c| match c {
+                '"' | '\\' => '_',
+                c if c.is_ascii() && !c.is_control() => c,
+                _ => '_'
+            }).collect();
+
+            let mut value = format!("attachment; filename=\"{}\"", ascii);
+            if ascii != *filename {
+                value.push_str("; filename*=UTF-8''");
+                for byte in filename.bytes() {
+                    match byte {
+                        b'a'...b'z' | b'A'...b'Z' | b'0'...b'9' | b'.' | b'-' | b'_' => value.push(byte as char),
+                        _ => value.push_str(&format!("%{:02X}", byte))
+                    }
+                }
+            }
+            value
+        }
+    }
+}
+
+/// Builder for the Cache-Control header of a response.
+///
+/// ```
+/// use edge::CachePolicy;
+/// let policy = CachePolicy::new().public().max_age(3600).immutable();
+/// assert_eq!(policy.to_string(), "public, max-age=3600, immutable");
+/// ```
+#[derive(Clone, Debug, Default)]
+pub struct CachePolicy {
+    directives: Vec<CacheDirective>
+}
+
+impl CachePolicy {
+    /// Creates an empty cache policy.
+    pub fn new() -> CachePolicy {
+        CachePolicy::default()
+    }
+
+    /// The response must not be stored by any cache.
+    pub fn no_store(self) -> Self {
+        self.with(CacheDirective::NoStore)
+    }
+
+    /// The response must be revalidated with the server before each use.
+    pub fn no_cache(self) -> Self {
+        self.with(CacheDirective::NoCache)
+    }
+
+    /// Intermediaries must not transform the response.
+    pub fn no_transform(self) -> Self {
+        self.with(CacheDirective::NoTransform)
+    }
+
+    /// The response may be stored by shared caches (replaces `private`).
+    pub fn public(mut self) -> Self {
+        self.directives.retain(|directive| *directive != CacheDirective::Private);
+        self.with(CacheDirective::Public)
+    }
+
+    /// The response may only be stored by the client's cache (replaces `public`).
+    pub fn private(mut self) -> Self {
+        self.directives.retain(|directive| *directive != CacheDirective::Public);
+        self.with(CacheDirective::Private)
+    }
+
+    /// The response is fresh for the given number of seconds.
+    pub fn max_age(mut self, secs: u32) -> Self {
+        self.directives.retain(|directive| match *directive {
+            CacheDirective::MaxAge(_) => false,
+            _ => true
+        });
+        self.with(CacheDirective::MaxAge(secs))
+    }
+
+    /// The response will not change while it is fresh.
+    pub fn immutable(self) -> Self {
+        self.with(CacheDirective::Extension("immutable".to_string(), None))
+    }
+
+    /// Adds the given directive unless already present.
+    fn with(mut self, directive: CacheDirective) -> Self {
+        if !self.directives.contains(&directive) {
+            self.directives.push(directive);
+        }
+        self
+    }
+}
+
+impl fmt::Display for CachePolicy {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        CacheControl(self.directives.clone()).fmt(f)
+    }
+}
+
+/// When the client may retry a request, sent in the Retry-After header
+/// of 503 Service Unavailable and 429 Too Many Requests responses.
+///
+/// ```
+/// use edge::RetryAfter;
+/// use std::time::{Duration, UNIX_EPOCH};
+///
+/// assert_eq!(RetryAfter::Delay(Duration::from_secs(120)).to_string(), "120");
+/// let date = UNIX_EPOCH + Duration::from_secs(784111777);
+/// assert_eq!(RetryAfter::At(date).to_string(), "Sun, 06 Nov 1994 08:49:37 GMT");
+/// ```
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum RetryAfter {
+    /// After the given delay, sent in seconds.
+    Delay(Duration),
+
+    /// After the given date, sent as an HTTP date.
+    At(SystemTime)
+}
+
+impl fmt::Display for RetryAfter {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            RetryAfter::Delay(delay) => write!(f, "{}", delay.as_secs()),
+            RetryAfter::At(date) => {
+                // dates before the epoch are in the past anyway
+                let secs = date.duration_since(UNIX_EPOCH).map(|duration| duration.as_secs()).unwrap_or(0);
+                fmt::Display::fmt(&HttpDate(time::at_utc(time::Timespec::new(secs as i64, 0))), f)
+            }
+        }
+    }
+}
+
+/// Streams the items of the given iterator as newline-delimited JSON (one JSON value per line),
+/// without buffering the whole result set.
+///
+/// If no Content-Type header is set, the content type is set to `application/x-ndjson`.
+/// If an item cannot be serialized, or the client goes away, the error is logged and
+/// the stream ends.
+pub fn ndjson<I>(res: &mut Response, items: I) -> Result where I: IntoIterator, I::IntoIter: 'static, I::Item: Serialize {
+    if !res.headers.has::<ContentType>() {
+        res.content_type("application/x-ndjson");
+    }
+
+    let items = RefCell::new(Some(items.into_iter()));
+    Ok(Action::Stream(Box::new(move |_, writer| {
+        // the closure is only called once
+        if let Some(items) = items.borrow_mut().take() {
+            for item in items {
+                let result = serde_json::to_vec(&item)
+                    .map_err(|e| io::Error::new(ErrorKind::InvalidData, e))
+                    .and_then(|mut line| {
+                        line.push(b'\n');
+                        writer.write_all(&line)
+                    });
+
+                if let Err(e) = result {
+                    error!("aborting NDJSON stream: {}", e);
+                    return;
+                }
+            }
+        }
+    })))
+}
+
+/// Streams parts that replace each other as a `multipart/x-mixed-replace` response,
+/// for example the frames of a video (MJPEG) or the successive states of a document.
+///
+/// The Content-Type is set with the given boundary, and the closure is called with
+/// a `MixedReplace` writer to send each part with its own content type. The boundary
+/// must not appear in the data of the parts.
+///
+/// ```no_run
+/// use edge::{mixed_replace, Request, Response, Result};
+/// use std::thread;
+/// use std::time::Duration;
+///
+/// #[derive(Default)]
+/// struct Camera;
+///
+/// fn frames(_camera: &mut Camera, _req: &Request, res: &mut Response) -> Result {
+///     mixed_replace(res, "frame", |_camera: &mut Camera, parts| {
+///         loop {
+///             try!(parts.part("text/plain", b"tick"));
+///             thread::sleep(Duration::from_secs(1));
+///         }
+///     })
+/// }
+/// ```
+pub fn mixed_replace<F, T>(res: &mut Response, boundary: &str, closure: F) -> Result
+    where T: Any, F: 'static + Fn(&mut T, &mut MixedReplace) -> io::Result<()> {
+    res.content_type(format!("multipart/x-mixed-replace; boundary={}", boundary));
+
+    let boundary = boundary.to_string();
+    stream(move |app: &mut T, writer| {
+        let mut parts = MixedReplace {
+            writer: writer,
+            boundary: boundary.clone()
+        };
+        closure(app, &mut parts)
+    })
+}
+
+/// Writer of the parts of a `multipart/x-mixed-replace` response, see `mixed_replace`.
+pub struct MixedReplace<'a> {
+    writer: &'a mut Write,
+    boundary: String
+}
+
+impl<'a> MixedReplace<'a> {
+    /// Sends a part with the given content type and data, replacing the previous part.
+    pub fn part(&mut self, content_type: &str, data: &[u8]) -> io::Result<()> {
+        let head = format!("--{}\r\nContent-Type: {}\r\nContent-Length: {}\r\n\r\n", self.boundary, content_type, data.len());
+        let mut part = head.into_bytes();
+        part.extend_from_slice(data);
+        part.extend_from_slice(b"\r\n");
+
+        // written at once, so that a part is sent in a single chunk
+        self.writer.write_all(&part)
+    }
+}
+
 /// This represents the response that will be sent back to the application.
 ///
 /// Includes a status code (default 200 OK), headers, and a body.
@@ -201,7 +659,8 @@ pub fn stream<F, T, R>(closure: F) -> Result where T: Any, F: 'static + Fn(&mut
 pub struct Response {
     pub status: Status,
     pub headers: Headers,
-    streaming: bool
+    streaming: bool,
+    weak_etag: bool
 }
 
 impl Response {
@@ -210,10 +669,34 @@ impl Response {
         Response {
             status: Status::Ok,
             headers: Headers::default(),
-            streaming: false
+            streaming: false,
+            weak_etag: false
         }
     }
 
+    /// Creates a response with the given status, to be built fluently and returned with `body`.
+    ///
+    /// ```
+    /// use edge::{Request, Response, Result, Status};
+    ///
+    /// fn hello(_req: &Request, _res: &mut Response) -> Result {
+    ///     Response::with_status(Status::Ok).content_type("text/plain").body("hi")
+    /// }
+    /// ```
+    pub fn with_status(status: Status) -> Response {
+        let mut response = Response::new();
+        response.status = status;
+        response
+    }
+
+    /// Ends building this response with the given body, and returns it to be sent.
+    ///
+    /// The response is moved out of `self`, which is left as a new response.
+    pub fn body<B: Into<Vec<u8>>>(&mut self, body: B) -> Result {
+        let response = ::std::mem::replace(self, Response::new());
+        Ok(Action::Respond(Box::new(response), body.into()))
+    }
+
     /// Sets the status code of this response.
     pub fn status(&mut self, status: Status) -> &mut Self {
         self.status = status;
@@ -232,6 +715,177 @@ impl Response {
         self
     }
 
+    /// Makes `send_file` emit a weak ETag.
+    ///
+    /// The ETag of a file is derived from its length and modification time, not its contents,
+    /// which is what weak validators are for. Note that weak ETags cannot be used with If-Range.
+    pub fn weak_etag(&mut self) -> &mut Self {
+        self.weak_etag = true;
+        self
+    }
+
+    /// Sets the Cache-Control header from the given policy.
+    pub fn cache_control(&mut self, policy: CachePolicy) -> &mut Self {
+        self.headers.set(CacheControl(policy.directives));
+        self
+    }
+
+    /// Renders the template with the given name using the given data for clients that prefer HTML,
+    /// or sends the data as JSON for clients that prefer JSON, according to the Accept header.
+    ///
+    /// HTML is chosen when both are equally acceptable, or when the request has no Accept header.
+    /// Sets `Vary: Accept` so that caches keep the two representations apart.
+    pub fn render_json_and_html<T: ToJson>(&mut self, req: &Request, name: &str, data: T) -> Result {
+        self.vary("Accept");
+
+        let json = data.to_json();
+        if prefers_json(req) {
+            Ok(Action::Json(json))
+        } else {
+            Ok(Action::Render(name.to_string(), json))
+        }
+    }
+
+    /// Sends the file at the given path with the given options, for example as an attachment
+    /// with a custom content type.
+    ///
+    /// Returns `Ok(Action::SendFile(path))`, so the file is sent like with `Action::SendFile`
+    /// (including ranges and conditional requests).
+    pub fn serve_file<P: Into<String>>(&mut self, path: P, options: FileOptions) -> Result {
+        if let Some(content_type) = options.content_type {
+            self.content_type(content_type);
+        }
+
+        if let Some(ref disposition) = options.disposition {
+            self.header_raw("Content-Disposition", content_disposition(disposition));
+        }
+
+        if let Some(policy) = options.cache_control {
+            self.cache_control(policy);
+        }
+
+        Ok(Action::SendFile(path.into()))
+    }
+
+    /// Appends the given link values, such as `"</app.css>; rel=preload; as=style"`,
+    /// to the Link header of this response.
+    ///
+    /// ```
+    /// use edge::Response;
+    ///
+    /// let mut res = Response::new();
+    /// res.link(&["</app.css>; rel=preload; as=style"]).link(&["</app.js>; rel=preload; as=script"]);
+    /// assert_eq!(res.headers.get_raw("Link").unwrap()[0],
+    ///     b"</app.css>; rel=preload; as=style, </app.js>; rel=preload; as=script".to_vec());
+    /// ```
+    pub fn link(&mut self, links: &[&str]) -> &mut Self {
+        let mut values: Vec<String> = self.headers.get_raw("Link").map_or(Vec::new(), |lines|
+            lines.iter().filter_map(|line| ::std::str::from_utf8(line).ok()).map(str::to_string).collect()
+        );
+        values.extend(links.iter().map(|link| link.to_string()));
+        self.header_raw("Link", values.join(", "))
+    }
+
+    /// Asks the client to preload the resource at the given URL, which has the given type
+    /// (such as "script", "style", "font", or "image").
+    ///
+    /// Each call appends a link to the Link header, e.g. `</app.js>; rel=preload; as=script`.
+    /// Fonts are always fetched in CORS mode, so their link gets the `crossorigin` attribute.
+    pub fn preload(&mut self, url: &str, as_type: &str) -> &mut Self {
+        let link = if as_type == "font" {
+            format!("<{}>; rel=preload; as={}; crossorigin", url, as_type)
+        } else {
+            format!("<{}>; rel=preload; as={}", url, as_type)
+        };
+        self.link(&[&link])
+    }
+
+    /// Disables range requests for this response: sets `Accept-Ranges: none`,
+    /// and a `Range` request header is ignored, so the full body is sent with its status.
+    pub fn no_ranges(&mut self) -> &mut Self {
+        self.headers.set(header::AcceptRanges(vec![header::RangeUnit::None]));
+        self
+    }
+
+    /// Returns `true` if range requests were disabled with `no_ranges`.
+    fn ranges_disabled(&self) -> bool {
+        self.headers.get::<header::AcceptRanges>().map_or(false, |&header::AcceptRanges(ref units)|
+            units.contains(&header::RangeUnit::None))
+    }
+
+    /// Adds the given request header name to the Vary header of this response,
+    /// unless it is already listed (ignoring case).
+    ///
+    /// Call this whenever the response depends on a request header, such as `Accept`
+    /// or `Accept-Language`, so that caches keep the different representations apart.
+    pub fn vary(&mut self, name: &str) -> &mut Self {
+        let mut names: Vec<String> = self.headers.get_raw("Vary").map_or(Vec::new(), |lines|
+            lines.iter()
+                .filter_map(|line| ::std::str::from_utf8(line).ok())
+                .flat_map(|line| line.split(','))
+                .map(str::trim)
+                .filter(|name| !name.is_empty())
+                .map(str::to_string)
+                .collect()
+        );
+
+        // "*" already means that the response varies on everything
+        if !names.iter().any(|existing| existing == "*" || existing.eq_ignore_ascii_case(name)) {
+            names.push(name.to_string());
+        }
+
+        self.header_raw("Vary", names.join(", "))
+    }
+
+    /// Sets the given validators (ETag and Last-Modified) on this response, and returns
+    /// a 304 Not Modified "error" if the conditional headers of the request show that
+    /// the client already has this representation.
+    ///
+    /// Call this before computing the body, so that with `try!` the work is skipped
+    /// when the client's copy is still valid:
+    ///
+    /// ```
+    /// use edge::{Request, Response, Result};
+    /// use edge::header::EntityTag;
+    ///
+    /// fn article(req: &Request, res: &mut Response) -> Result {
+    ///     let version = 42; // e.g. read from the database
+    ///     try!(res.not_modified_if(req, Some(EntityTag::strong(version.to_string())), None));
+    ///     Ok(format!("article, version {}", version).into())
+    /// }
+    /// ```
+    pub fn not_modified_if(&mut self, req: &Request, etag: Option<EntityTag>, modified: Option<HttpDate>) -> result::Result<(), Error> {
+        if let Some(ref etag) = etag {
+            self.headers.set(header::ETag(etag.clone()));
+        }
+        if let Some(date) = modified {
+            self.headers.set(header::LastModified(date));
+        }
+
+        if conditional::not_modified(req, etag.as_ref(), modified.as_ref()) {
+            Err(Status::NotModified.into())
+        } else {
+            Ok(())
+        }
+    }
+
+    /// Sets the Content-Security-Policy header (or Content-Security-Policy-Report-Only
+    /// for a report-only policy) from the given policy.
+    pub fn csp(&mut self, csp: &Csp) -> &mut Self {
+        self.header_raw(csp.header_name(), csp.to_string())
+    }
+
+    /// Sets the Retry-After header, telling the client when to retry the request.
+    pub fn retry_after(&mut self, retry_after: RetryAfter) -> &mut Self {
+        self.header_raw("Retry-After", retry_after.to_string())
+    }
+
+    /// Closes the connection after this response has been sent.
+    pub fn close_connection(&mut self) -> &mut Self {
+        self.headers.set(header::Connection::close());
+        self
+    }
+
     /// Sets the given cookie.
     pub fn cookie(&mut self, cookie: Cookie) {
         if self.headers.has::<SetCookie>() {
@@ -267,7 +921,10 @@ impl Response {
     ///   - text: css, htm, html, txt
     ///   - video: avi, mp4, mpg, mpeg, ts
     /// If the file does not exist, this method sends a 404 Not Found response.
-    fn send_file<P: AsRef<Path>>(&mut self, path: P) -> Option<Vec<u8>> {
+    ///
+    /// Honors single byte ranges requested with the Range header, as well as If-Range.
+    /// Sends a 304 Not Modified response if the client's copy is current (If-None-Match or If-Modified-Since).
+    fn send_file<P: AsRef<Path>>(&mut self, req: &Request, path: P) -> Option<Vec<u8>> {
         if !self.headers.has::<ContentType>() {
             let extension = path.as_ref().extension();
             if let Some(ext) = extension {
@@ -308,16 +965,14 @@ impl Response {
             }
         }
 
-        // read the whole file at once and send it
-        // probably not the best idea for big files, we should use stream instead in that case
         match File::open(path) {
-            Ok(mut file) => {
-                let mut buf = Vec::with_capacity(file.metadata().ok().map_or(1024, |meta| meta.len() as usize));
-                if let Err(err) = file.read_to_end(&mut buf) {
-                    self.status(Status::InternalServerError).content_type("text/plain");
-                    Some(format!("{}", err).into())
-                } else {
-                    Some(buf)
+            Ok(file) => {
+                match self.send_file_range(req, file) {
+                    Ok(buf) => buf,
+                    Err(err) => {
+                        self.status(Status::InternalServerError).content_type("text/plain");
+                        Some(format!("{}", err).into())
+                    }
                 }
             },
             Err(ref err) if err.kind() == ErrorKind::NotFound => {
@@ -331,10 +986,229 @@ impl Response {
         }
     }
 
+    /// Sets validators (ETag and Last-Modified) for the given file, and reads either
+    /// the whole file or the byte range requested by the client.
+    ///
+    /// The ETag is derived from the length and modification time of the file.
+    fn send_file_range(&mut self, req: &Request, mut file: File) -> io::Result<Option<Vec<u8>>> {
+        let meta = try!(file.metadata());
+        let len = meta.len();
+        let modified = meta.modified().ok()
+            .and_then(|mtime| mtime.duration_since(UNIX_EPOCH).ok())
+            .map(|duration| HttpDate(time::at_utc(time::Timespec::new(duration.as_secs() as i64, 0))));
+
+        let etag = EntityTag::new(self.weak_etag, format!("{:x}-{:x}", len, modified.as_ref().map_or(0, |date| date.0.to_timespec().sec)));
+        self.headers.set(header::ETag(etag.clone()));
+        if let Some(ref date) = modified {
+            self.headers.set(header::LastModified(date.clone()));
+        }
+        let ranges = !self.ranges_disabled();
+        if ranges {
+            self.headers.set(header::AcceptRanges(vec![header::RangeUnit::Bytes]));
+        }
+
+        if conditional::not_modified(req, Some(&etag), modified.as_ref()) {
+            self.status(Status::NotModified);
+            return Ok(None);
+        }
+
+        // the response to a HEAD request has no body, no need to read the file
+        if *req.method() == Method::Head {
+            return Ok(None);
+        }
+
+        let range = if ranges { check_range(req, len, Some(&etag), modified.as_ref()) } else { RangeRequest::Full };
+        match range {
+            RangeRequest::Full => {
+                // read the whole file at once and send it
+                // probably not the best idea for big files, we should use stream instead in that case
+                let mut buf = Vec::with_capacity(len as usize);
+                try!(file.read_to_end(&mut buf));
+                Ok(Some(buf))
+            }
+            RangeRequest::Partial(first, last) => {
+                self.partial_content(first, last, len);
+
+                let mut buf = Vec::with_capacity((last - first + 1) as usize);
+                try!(file.seek(SeekFrom::Start(first)));
+                try!(file.take(last - first + 1).read_to_end(&mut buf));
+                Ok(Some(buf))
+            }
+            RangeRequest::Unsatisfiable => {
+                self.range_not_satisfiable(len);
+                Ok(None)
+            }
+        }
+    }
+
+    /// Sets the status and Content-Range header of a response with the bytes
+    /// between `first` and `last` (inclusive) of a representation of length `len`.
+    fn partial_content(&mut self, first: u64, last: u64, len: u64) {
+        self.status(Status::PartialContent);
+        self.headers.set(header::ContentRange(header::ContentRangeSpec::Bytes {
+            range: Some((first, last)),
+            instance_length: Some(len)
+        }));
+    }
+
+    /// Sets the status and Content-Range header of a response to an unsatisfiable range
+    /// of a representation of length `len`.
+    fn range_not_satisfiable(&mut self, len: u64) {
+        self.status(Status::RangeNotSatisfiable);
+        self.headers.set(header::ContentRange(header::ContentRangeSpec::Bytes {
+            range: None,
+            instance_length: Some(len)
+        }));
+    }
+
+}
+
+/// Result of checking the Range header of a request against a representation.
+enum RangeRequest {
+    /// the whole representation must be sent
+    Full,
+
+    /// only the bytes between first and last (inclusive) must be sent
+    Partial(u64, u64),
+
+    /// the range cannot be satisfied
+    Unsatisfiable
+}
+
+/// Checks the Range and If-Range headers of the given request against a representation
+/// with the given length and validators.
+///
+/// RFC 7233 Hypertext Transfer Protocol (HTTP/1.1): Range Requests
+/// http://httpwg.org/specs/rfc7233.html
+///
+/// Only single byte ranges are supported, other requests get the full representation.
+fn check_range(req: &Request, len: u64, etag: Option<&EntityTag>, modified: Option<&HttpDate>) -> RangeRequest {
+    // 3.1 Range
+    // http://httpwg.org/specs/rfc7233.html#header.range
+    //
+    // A server MUST ignore a Range header field received with a request method other than GET.
+    // In particular a HEAD request gets the headers of a full 200 OK response.
+    if *req.method() != Method::Get {
+        return RangeRequest::Full;
+    }
+
+    let spec = match req.headers().get::<header::Range>() {
+        Some(&header::Range::Bytes(ref ranges)) if ranges.len() == 1 => ranges[0].clone(),
+        _ => return RangeRequest::Full
+    };
+
+    // 3.2 If-Range
+    // http://httpwg.org/specs/rfc7233.html#header.if-range
+    //
+    // If the validator given in the If-Range header field matches the current validator
+    // for the selected representation of the target resource, then the server SHOULD process
+    // the Range header field as requested. If the validator does not match,
+    // the server MUST ignore the Range header field.
+    if let Some(if_range) = req.headers().get::<header::IfRange>() {
+        let matches = match *if_range {
+            header::IfRange::EntityTag(ref tag) => etag.map_or(false, |etag| tag.strong_eq(etag)),
+            header::IfRange::Date(ref date) => modified == Some(date)
+        };
+
+        if !matches {
+            debug!("If-Range does not match, ignoring Range");
+            return RangeRequest::Full;
+        }
+    }
+
+    match spec {
+        header::ByteRangeSpec::FromTo(first, last) if first > last => RangeRequest::Full,
+        header::ByteRangeSpec::FromTo(first, last) if first < len => RangeRequest::Partial(first, cmp::min(last, len - 1)),
+        header::ByteRangeSpec::AllFrom(first) if first < len => RangeRequest::Partial(first, len - 1),
+        header::ByteRangeSpec::Last(n) if n > 0 && len > 0 => RangeRequest::Partial(len - cmp::min(n, len), len - 1),
+        _ => RangeRequest::Unsatisfiable
+    }
+}
+
+/// Honors the Range header of a GET request answered with the given buffered body
+/// and a 200 OK status, using the ETag and Last-Modified headers of the response (if any)
+/// to evaluate If-Range. A HEAD request gets the `Accept-Ranges` header but no range.
+///
+/// Returns the new body if a range was requested: the requested bytes with a 206 Partial Content
+/// status, or an empty body with a 416 Range Not Satisfiable status.
+pub fn byte_range(response: &mut Response, req: &Request, body: &[u8]) -> Option<Vec<u8>> {
+    let method = req.method().clone();
+    if (method != Method::Get && method != Method::Head) || response.status != Status::Ok ||
+        response.headers.has::<header::ContentRange>() || response.ranges_disabled() {
+        return None;
+    }
+
+    // a HEAD request gets the same headers as a GET request, ranges are supported
+    if !response.headers.has::<header::AcceptRanges>() {
+        response.headers.set(header::AcceptRanges(vec![header::RangeUnit::Bytes]));
+    }
+
+    let len = body.len() as u64;
+    let range = {
+        let etag = response.headers.get::<header::ETag>().map(|&header::ETag(ref etag)| etag);
+        let modified = response.headers.get::<header::LastModified>().map(|&header::LastModified(ref date)| date);
+        check_range(req, len, etag, modified)
+    };
+
+    match range {
+        RangeRequest::Full => None,
+        RangeRequest::Partial(first, last) => {
+            response.partial_content(first, last, len);
+            Some(body[first as usize..(last + 1) as usize].to_vec())
+        }
+        RangeRequest::Unsatisfiable => {
+            response.range_not_satisfiable(len);
+            Some(Vec::new())
+        }
+    }
+}
+
+/// Serializes the given JSON value, writing floats with the given number of decimals (if any).
+pub fn json_bytes(value: &json::Value, decimals: Option<usize>) -> Vec<u8> {
+    match decimals {
+        None => value.to_string().into_bytes(),
+        Some(decimals) => {
+            let mut bytes = Vec::new();
+            write_json(&mut bytes, value, decimals);
+            bytes
+        }
+    }
+}
+
+fn write_json(out: &mut Vec<u8>, value: &json::Value, decimals: usize) {
+    match *value {
+        // NaN and infinities are written as null
+        json::Value::F64(f) if f.is_finite() => {
+            let _ = write!(out, "{:.*}", decimals, f);
+        }
+        json::Value::Array(ref items) => {
+            out.push(b'[');
+            for (i, item) in items.iter().enumerate() {
+                if i > 0 {
+                    out.push(b',');
+                }
+                write_json(out, item, decimals);
+            }
+            out.push(b']');
+        }
+        json::Value::Object(ref map) => {
+            out.push(b'{');
+            for (i, (key, item)) in map.iter().enumerate() {
+                if i > 0 {
+                    out.push(b',');
+                }
+                out.extend_from_slice(json::Value::String(key.clone()).to_string().as_bytes());
+                out.push(b':');
+                write_json(out, item, decimals);
+            }
+            out.push(b'}');
+        }
+        ref other => out.extend_from_slice(other.to_string().as_bytes())
+    }
 }
 
-pub fn send_file<P: AsRef<Path>>(response: &mut Response, path: P) -> Option<Vec<u8>> {
-    response.send_file(path)
+pub fn send_file<P: AsRef<Path>>(response: &mut Response, req: &Request, path: P) -> Option<Vec<u8>> {
+    response.send_file(req, path)
 }
 
 pub fn set_streaming(response: &mut Response) {
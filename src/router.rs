@@ -9,19 +9,38 @@ use std::marker::PhantomData;
 
 use request;
 use request::Request;
-use response::{Result, Response};
+use response::{Error, Result, Response};
 
 use std::result;
 
 pub type TypedCallback<T> = fn(&mut T, &Request, &mut Response) -> Result;
 pub type TypedMiddleware<T> = fn(&mut T, &mut Request, &mut Response);
+pub type TypedFallibleMiddleware<T> = fn(&mut T, &mut Request, &mut Response) -> result::Result<(), Error>;
 pub type Static = fn(&Request, &mut Response) -> Result;
 
-/// A segment is either a fixed string, or a variable with a name
+/// Default maximum number of segments of a route.
+pub const DEFAULT_MAX_SEGMENTS: usize = 32;
+
+/// Default maximum number of variables (named parameters) of a route.
+pub const DEFAULT_MAX_PARAMS: usize = 16;
+
+/// A segment is either a fixed string, a variable with a name,
+/// or a named wildcard that matches the rest of the path
 #[derive(Debug)]
 enum Segment {
     Fixed(String),
-    Variable(String)
+    Variable(String),
+    Rest(String)
+}
+
+impl fmt::Display for Segment {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            Segment::Fixed(ref fixed) => f.write_str(fixed),
+            Segment::Variable(ref name) => write!(f, ":{}", name),
+            Segment::Rest(ref name) => write!(f, "*{}", name)
+        }
+    }
 }
 
 impl Segment {
@@ -31,14 +50,39 @@ impl Segment {
             _ => false
         }
     }
+
+    /// Returns `true` if this segment matches exactly the same strings as the other one,
+    /// regardless of the names of variables.
+    fn same_as(&self, other: &Segment) -> bool {
+        match (self, other) {
+            (&Segment::Fixed(ref a), &Segment::Fixed(ref b)) => a == b,
+            (&Segment::Variable(_), &Segment::Variable(_)) => true,
+            (&Segment::Rest(_), &Segment::Rest(_)) => true,
+            _ => false
+        }
+    }
 }
 
 /// A route is an absolute URL pattern with a leading slash, and segments separated by slashes.
 ///
 /// A segment that begins with a colon declares a variable, for example "/:user_id".
+/// The last segment may begin with a star to match the rest of the path, for example "/files/*path".
 pub struct Route {
+    pattern: String,
     segments: Vec<Segment>,
-    callback: Callback
+    callback: Callback,
+
+    /// media types accepted in the body of requests, any if empty
+    accepts: Vec<String>,
+
+    /// host (in lowercase) to which this route is restricted, any if None
+    host: Option<String>,
+
+    /// served even when requests are shed because too many are in flight
+    priority: bool,
+
+    /// name of the handler, used in logs instead of the pattern
+    name: Option<String>
 }
 
 /// Returns a vector of segments from the given string.
@@ -51,21 +95,112 @@ fn get_segments(from: &str) -> result::Result<Vec<Segment>, &str> {
     }
 
     let stripped = &from[1..];
-    Ok(stripped.split('/').map(|segment| if segment.len() > 0 && segment.as_bytes()[0] == b':' {
+    let mut segments = Vec::new();
+    for segment in stripped.split('/') {
+        if let Some(&Segment::Rest(_)) = segments.last() {
+            return Err("wildcard must be the last segment of a route");
+        }
+
+        segments.push(if segment.starts_with(':') {
             Segment::Variable(segment[1..].to_string())
+        } else if segment.starts_with('*') {
+            Segment::Rest(segment[1..].to_string())
         } else {
             Segment::Fixed(segment.to_string())
-        }
-    ).collect::<Vec<Segment>>())
+        });
+    }
+
+    Ok(segments)
 }
 
 impl Route {
-    fn new(from: &str, callback: Callback) -> result::Result<Route, &str> {
+    /// Returns `true` if this route matches exactly the same hosts and paths as the other one.
+    fn same_as(&self, other: &Route) -> bool {
+        self.host == other.host && self.segments.len() == other.segments.len() &&
+            self.segments.iter().zip(&other.segments).all(|(a, b)| a.same_as(b))
+    }
+
+    fn new(from: &str, callback: Callback, max_segments: usize, max_params: usize) -> result::Result<Route, String> {
+        let segments = try!(get_segments(from).map_err(|e| e.to_string()));
+        if segments.len() > max_segments {
+            return Err(format!("route has {} segments, maximum is {}", segments.len(), max_segments));
+        }
+
+        let num_params = segments.iter().filter(|segment| match **segment {
+            Segment::Variable(_) | Segment::Rest(_) => true,
+            _ => false
+        }).count();
+        if num_params > max_params {
+            return Err(format!("route has {} variables, maximum is {}", num_params, max_params));
+        }
+
         Ok(Route {
-            segments: try!(get_segments(from)),
-            callback: callback
+            pattern: from.to_string(),
+            segments: segments,
+            callback: callback,
+            accepts: Vec::new(),
+            host: None,
+            priority: false,
+            name: None
         })
     }
+
+    /// Returns the pattern this route was registered with.
+    pub fn pattern(&self) -> &str {
+        &self.pattern
+    }
+
+    /// Returns the name given to this route with `Router::name`, or its pattern.
+    pub fn name(&self) -> &str {
+        self.name.as_ref().map_or(self.pattern.as_str(), |name| name.as_str())
+    }
+
+    /// Returns the callback associated with this route.
+    pub fn callback(&self) -> &Callback {
+        &self.callback
+    }
+
+    /// Returns `true` if the Content-Type of the given request is accepted by this route.
+    pub fn accepts(&self, req: &Request) -> bool {
+        if self.accepts.is_empty() {
+            return true;
+        }
+
+        req.headers().get_raw("Content-Type")
+            .and_then(|values| values.first())
+            .and_then(|value| ::std::str::from_utf8(value).ok())
+            .map_or(false, |value| {
+                let media_type = value.split(';').next().unwrap_or("").trim().to_lowercase();
+                self.accepts.iter().any(|accepted| *accepted == media_type)
+            })
+    }
+
+    /// Returns `true` if this route matches the given path, and adds matched variables to `params`.
+    fn matches(&self, path: &[String], params: &mut BTreeMap<String, String>) -> bool {
+        let mut it_route = self.segments.iter();
+        for (i, actual) in path.iter().enumerate() {
+            match it_route.next() {
+                Some(&Segment::Fixed(ref fixed)) if fixed != actual => return false,
+                Some(&Segment::Variable(ref name)) => {
+                    params.insert(name.to_owned(), actual.to_string());
+                },
+                Some(&Segment::Rest(ref name)) => {
+                    params.insert(name.to_owned(), path[i..].join("/"));
+                    return true;
+                },
+                _ => ()
+            }
+        }
+
+        match it_route.next() {
+            None => true,
+            Some(&Segment::Rest(ref name)) => {
+                params.insert(name.to_owned(), String::new());
+                true
+            }
+            _ => false
+        }
+    }
 }
 
 use std::fmt::{self, Debug, Formatter};
@@ -96,11 +231,74 @@ impl<T: Default + Any + Send> Router<T> {
         }
     }
 
+    /// Sets the maximum number of segments of routes registered after this call.
+    pub fn max_segments(&mut self, max: usize) {
+        self.inner.max_segments = max;
+    }
+
+    /// Sets the maximum number of variables of routes registered after this call.
+    pub fn max_params(&mut self, max: usize) {
+        self.inner.max_params = max;
+    }
+
     pub fn add_middleware(&mut self, middleware: TypedMiddleware<T>) {
         self.inner.middleware.push(Box::new(move |any, req, res| {
             if let Some(app) = any.downcast_mut::<T>() {
                 middleware(app, req, res);
             }
+            Ok(())
+        }))
+    }
+
+    /// Adds middleware that can fail, for instance when it cannot decode a token.
+    ///
+    /// When the middleware returns an error, the remaining middleware and the handler
+    /// are skipped, and the error is sent as the response like an error returned by a handler.
+    ///
+    /// ```
+    /// # use edge::{Error, Request, Response, Router, Status};
+    /// # #[derive(Default)] struct App;
+    /// fn authenticate(_app: &mut App, req: &mut Request, _res: &mut Response) -> Result<(), Error> {
+    ///     match req.headers().get_raw("Authorization") {
+    ///         Some(_) => Ok(()),
+    ///         None => Err((Status::Unauthorized, "missing credentials").into())
+    ///     }
+    /// }
+    ///
+    /// let mut router = Router::<App>::new();
+    /// router.add_fallible_middleware(authenticate);
+    /// ```
+    pub fn add_fallible_middleware(&mut self, middleware: TypedFallibleMiddleware<T>) {
+        self.inner.middleware.push(Box::new(move |any, req, res| {
+            match any.downcast_mut::<T>() {
+                Some(app) => middleware(app, req, res),
+                None => Ok(())
+            }
+        }))
+    }
+
+    /// Adds middleware that only runs for requests satisfying the given predicate.
+    ///
+    /// ```
+    /// # use edge::{Request, Response, Router};
+    /// # #[derive(Default)] struct App;
+    /// fn log_writes(_app: &mut App, req: &mut Request, _res: &mut Response) {
+    ///     println!("{} {:?}", req.method(), req.path());
+    /// }
+    ///
+    /// let mut router = Router::<App>::new();
+    /// router.add_middleware_if(|req| !req.method().safe(), log_writes);
+    /// ```
+    pub fn add_middleware_if<P>(&mut self, predicate: P, middleware: TypedMiddleware<T>)
+        where P: 'static + Fn(&Request) -> bool + Sync {
+        self.inner.middleware.push(Box::new(move |any, req, res| {
+            if !predicate(req) {
+                return Ok(());
+            }
+            if let Some(app) = any.downcast_mut::<T>() {
+                middleware(app, req, res);
+            }
+            Ok(())
         }))
     }
 
@@ -134,33 +332,181 @@ impl<T: Default + Any + Send> Router<T> {
         self.insert(Head, path, callback)
     }
 
+    /// Registers a callback for the given path for POST requests with a JSON body.
+    ///
+    /// Requests with another Content-Type get a 415 Unsupported Media Type response.
+    #[inline]
+    pub fn post_json(&mut self, path: &str, callback: TypedCallback<T>) {
+        self.insert_accepting(Post, path, &["application/json"], callback)
+    }
+
+    /// Registers a callback for the given path for PUT requests with a JSON body.
+    ///
+    /// Requests with another Content-Type get a 415 Unsupported Media Type response.
+    #[inline]
+    pub fn put_json(&mut self, path: &str, callback: TypedCallback<T>) {
+        self.insert_accepting(Put, path, &["application/json"], callback)
+    }
+
     /// Registers a static callback for the given path for GET requests.
     #[inline]
     pub fn get_static(&mut self, path: &str, callback: Static) {
         self.insert_static(Get, path, callback)
     }
 
+    /// Registers the routes added by the given closure for requests to the given host only.
+    ///
+    /// ```
+    /// use edge::{Request, Response, Result, Router};
+    ///
+    /// fn api(_req: &Request, _res: &mut Response) -> Result { Ok(().into()) }
+    /// fn site(_req: &Request, _res: &mut Response) -> Result { Ok(().into()) }
+    ///
+    /// let mut router = Router::<()>::new();
+    /// router.host("api.example.com", |r| r.get_static("/", api));
+    /// router.get_static("/", site);
+    /// ```
+    ///
+    /// The host is compared (ignoring case) to `Request::host`, so the port is ignored.
+    /// Routes registered for the host of a request are tried before the routes registered
+    /// for any host.
+    pub fn host<F>(&mut self, host: &str, routes: F) where F: FnOnce(&mut Router<T>) {
+        let previous = self.inner.host.take();
+        self.inner.host = Some(host.to_lowercase());
+        routes(self);
+        self.inner.host = previous;
+    }
+
+    /// Mounts the given service at the given path.
+    ///
+    /// The service handles requests with any method for this path and all the paths below it,
+    /// unless they match a route registered for that method.
+    /// The rest of the path is available as the "path" parameter, for example
+    /// a service mounted at "/proxy" gets "a/b" for the path "/proxy/a/b".
+    pub fn mount_service<F>(&mut self, path: &str, service: F)
+        where F: Fn(&Request, &mut Response) -> Result + Sync + 'static {
+        let pattern = path.trim_right_matches('/').to_string() + "/*path";
+        match Route::new(&pattern, Callback::Service(Box::new(service)), self.inner.max_segments, self.inner.max_params) {
+            Ok(mut route) => {
+                route.host = self.inner.host.clone();
+                info!("registered service for {} (parsed as {:?})", path, route);
+                self.inner.services.push(route)
+            }
+            Err(e) => panic!("could not register service {}: {}", path, e)
+        }
+    }
+
     /// Inserts the given callback for the given method and given route.
+    ///
+    /// Panics if the route is invalid, see `try_insert`.
     #[inline]
     pub fn insert(&mut self, method: Method, path: &str, callback: TypedCallback<T>) {
+        if let Err(e) = self.try_insert(method, path, callback) {
+            panic!("could not register route {}: {}", path, e);
+        }
+    }
+
+    /// Inserts the given callback for the given method and given route.
+    ///
+    /// Returns an error if the route is invalid, exceeds the maximum number
+    /// of segments or variables set for this router, or duplicates a route already registered
+    /// for this method (routes that only differ by the names of their variables are duplicates).
+    pub fn try_insert(&mut self, method: Method, path: &str, callback: TypedCallback<T>) -> result::Result<(), String> {
         self.insert_callback(method, path, Callback::Instance(Box::new(move |any, req, res| {
             let app = any.downcast_mut::<T>().unwrap();
             callback(app, req, res)
-        })))
+        })), &[])
+    }
+
+    /// Inserts the given callback for the given method and given route, for requests
+    /// whose Content-Type is one of the given media types (e.g. "application/json").
+    ///
+    /// Requests with another Content-Type get a 415 Unsupported Media Type response
+    /// before the callback (or any middleware) runs.
+    pub fn insert_accepting(&mut self, method: Method, path: &str, media_types: &[&str], callback: TypedCallback<T>) {
+        let result = self.insert_callback(method, path, Callback::Instance(Box::new(move |any, req, res| {
+            let app = any.downcast_mut::<T>().unwrap();
+            callback(app, req, res)
+        })), media_types);
+
+        if let Err(e) = result {
+            panic!("could not register route {}: {}", path, e);
+        }
     }
 
     /// Registers a static callback for the given path for GET requests.
     #[inline]
     pub fn insert_static(&mut self, method: Method, path: &str, callback: Static) {
-        self.insert_callback(method, path, Callback::Static(callback))
+        if let Err(e) = self.insert_callback(method, path, Callback::Static(callback), &[]) {
+            panic!("could not register route {}: {}", path, e);
+        }
+    }
+
+    /// Registers a callback for requests with the given method that match no route
+    /// of any router (within the prefix of this router).
+    ///
+    /// For example, a GET fallback can serve the shell of a single-page application,
+    /// while unmatched POST requests get a JSON error from a POST fallback. The unmatched
+    /// path (without the prefix) is available as the "path" parameter.
+    pub fn fallback(&mut self, method: Method, callback: TypedCallback<T>) {
+        let route = Route::new("/*path", Callback::Instance(Box::new(move |any, req, res| {
+            let app = any.downcast_mut::<T>().unwrap();
+            callback(app, req, res)
+        })), DEFAULT_MAX_SEGMENTS, DEFAULT_MAX_PARAMS).unwrap();
+        self.inner.fallbacks.insert(method, route);
+    }
+
+    /// Marks the route registered for the given method and path as high priority,
+    /// so that it is still served when the server sheds requests because too many
+    /// are in flight (see `Edge::max_concurrent_requests`), e.g. for health checks.
+    ///
+    /// Panics if no route is registered for this method and path.
+    pub fn prioritize(&mut self, method: Method, path: &str) {
+        let route = self.inner.routes.get_mut(&method)
+            .and_then(|routes| routes.iter_mut().find(|route| route.pattern == path));
+        match route {
+            Some(route) => route.priority = true,
+            None => panic!("could not prioritize route {} {}: no such route", method, path)
+        }
+    }
+
+    /// Names the route registered for the given method and path, e.g. after its handler,
+    /// so that logs (for instance when the handler panics) identify it by this name
+    /// rather than by its pattern.
+    ///
+    /// Panics if no route is registered for this method and path.
+    pub fn name(&mut self, method: Method, path: &str, name: &str) {
+        let route = self.inner.routes.get_mut(&method)
+            .and_then(|routes| routes.iter_mut().find(|route| route.pattern == path));
+        match route {
+            Some(route) => route.name = Some(name.to_string()),
+            None => panic!("could not name route {} {}: no such route", method, path)
+        }
     }
 
     /// Inserts the given callback for the given method and given route.
-    fn insert_callback(&mut self, method: Method, path: &str, callback: Callback) {
-        let route = Route::new(path, callback).unwrap();
+    fn insert_callback(&mut self, method: Method, path: &str, callback: Callback, media_types: &[&str]) -> result::Result<(), String> {
+        let mut route = try!(Route::new(path, callback, self.inner.max_segments, self.inner.max_params));
+        route.accepts = media_types.iter().map(|media_type| media_type.to_lowercase()).collect();
+        route.host = self.inner.host.clone();
+        let routes = self.inner.routes.entry(method.clone()).or_insert(Vec::new());
+        if let Some(existing) = routes.iter().find(|existing| existing.same_as(&route)) {
+            return Err(format!("duplicate route, {} {} is already registered as {}", method, path, existing.pattern));
+        }
+
         info!("registered callback for {} (parsed as {:?})", path, route);
+        routes.push(route);
+        Ok(())
+    }
+}
 
-        self.inner.routes.entry(method).or_insert(Vec::new()).push(route)
+/// Registers the given closure for GET requests to the given path.
+///
+/// Panics if the route is invalid.
+pub fn get_service<F>(router: &mut Router<()>, path: &str, service: F)
+    where F: Fn(&Request, &mut Response) -> Result + Sync + 'static {
+    if let Err(e) = router.insert_callback(Get, path, Callback::Service(Box::new(service)), &[]) {
+        panic!("could not register route {}: {}", path, e);
     }
 }
 
@@ -171,17 +517,25 @@ pub fn get_inner<T>(router: Router<T>) -> RouterAny {
 /// Signature for a callback method
 pub enum Callback {
     Instance(Box<Fn(&mut Any, &Request, &mut Response) -> Result + Sync>),
-    Static(Static)
+    Static(Static),
+    Service(Box<Fn(&Request, &mut Response) -> Result + Sync>)
 }
 
-pub type Middleware = Box<Fn(&mut Any, &mut Request, &mut Response) + Sync>;
+pub type Middleware = Box<Fn(&mut Any, &mut Request, &mut Response) -> result::Result<(), Error> + Sync>;
 
 /// Router structure
 pub struct RouterAny {
     init: fn() -> Box<Any + Send>,
     prefix: Vec<Segment>,
     middleware: Vec<Middleware>,
-    routes: HashMap<Method, Vec<Route>>
+    routes: HashMap<Method, Vec<Route>>,
+    services: Vec<Route>,
+    fallbacks: HashMap<Method, Route>,
+    max_segments: usize,
+    max_params: usize,
+
+    /// host to which routes are restricted when they are registered (see `Router::host`)
+    host: Option<String>
 }
 
 impl RouterAny {
@@ -190,12 +544,18 @@ impl RouterAny {
             init: Router::<T>::create,
             prefix: Vec::new(),
             middleware: Vec::new(),
-            routes: HashMap::new()
+            routes: HashMap::new(),
+            services: Vec::new(),
+            fallbacks: HashMap::new(),
+            max_segments: DEFAULT_MAX_SEGMENTS,
+            max_params: DEFAULT_MAX_PARAMS,
+            host: None
         }
     }
 
-    /// Finds the first route (if any) that matches the given path, and returns the associated callback.
-    pub fn find_callback(&self, req: &mut Request) -> Option<&Callback> {
+    /// Finds the first route (if any) that matches the given path, along with whether
+    /// it is a GET route handling a HEAD request automatically.
+    pub fn find_route(&self, req: &mut Request, auto_head: bool) -> Option<(&Route, bool)> {
         if self.match_prefix(req.path()) {
             debug!("{} {:?} matches prefix {:?}", req.method(), req.path(), self.prefix);
         } else {
@@ -203,38 +563,109 @@ impl RouterAny {
             return None;
         }
 
-        if let Some(routes) = self.routes.get(req.method()) {
-            let mut params = BTreeMap::new();
-            let prefix_len = self.prefix.len();
-
-            'top: for ref route in routes {
-                let mut it_route = route.segments.iter();
-                for actual in &req.path()[prefix_len..] {
-                    match it_route.next() {
-                        Some(&Segment::Fixed(ref fixed)) if fixed != actual => continue 'top,
-                        Some(&Segment::Variable(ref name)) => {
-                            params.insert(name.to_owned(), actual.to_string());
-                        },
-                        _ => ()
-                    }
-                }
+        let host = req.host().map(|host| host.to_lowercase());
+        let method = req.method().clone();
+        if let Some(route) = self.find_method_route(&method, req, host.as_ref()) {
+            return Some((route, false));
+        }
+
+        // a HEAD request is handled by the GET route, unless a HEAD route is registered
+        if auto_head && method == Head {
+            if let Some(route) = self.find_method_route(&Get, req, host.as_ref()) {
+                return Some((route, true));
+            }
+        }
 
-                if it_route.next().is_none() {
+        let mut params = BTreeMap::new();
+        let prefix_len = self.prefix.len();
+        for pass in &[host.as_ref(), None] {
+            for service in self.services.iter().filter(|service| service.host.as_ref() == *pass) {
+                if service.matches(&req.path()[prefix_len..], &mut params) {
                     request::set_params(req, params);
-                    return Some(&route.callback);
+                    return Some((service, false));
                 }
 
                 params.clear();
             }
+        }
+
+        warn!("no route matching method {} path {:?}", req.method(), req.path());
+        None
+    }
 
-            warn!("no route matching method {} path {:?}", req.method(), req.path());
+    /// Returns the fallback registered for the method of the given request (if any),
+    /// provided that its path matches the prefix of this router.
+    pub fn find_fallback(&self, req: &mut Request) -> Option<&Route> {
+        if !self.match_prefix(req.path()) {
+            return None;
+        }
+
+        let fallback = match self.fallbacks.get(req.method()) {
+            Some(fallback) => fallback,
+            None => return None
+        };
+
+        let mut params = BTreeMap::new();
+        if fallback.matches(&req.path()[self.prefix.len()..], &mut params) {
+            request::set_params(req, params);
+            Some(fallback)
         } else {
-            warn!("no routes registered for method {}", req.method());
+            None
+        }
+    }
+
+    /// Finds the route registered for the given method that matches the path of the given request.
+    fn find_method_route(&self, method: &Method, req: &mut Request, host: Option<&String>) -> Option<&Route> {
+        let routes = match self.routes.get(method) {
+            Some(routes) => routes,
+            None => return None
+        };
+
+        // routes registered for the host of the request first, then routes for any host
+        let mut params = BTreeMap::new();
+        let prefix_len = self.prefix.len();
+        for pass in &[host, None] {
+            for route in routes.iter().filter(|route| route.host.as_ref() == *pass) {
+                if route.matches(&req.path()[prefix_len..], &mut params) {
+                    request::set_params(req, params);
+                    return Some(route);
+                }
+
+                params.clear();
+            }
         }
 
         None
     }
 
+    /// Returns the methods of the routes that match the path of the given request.
+    pub fn allowed_methods(&self, req: &Request) -> Vec<Method> {
+        if !self.match_prefix(req.path()) {
+            return Vec::new();
+        }
+
+        let host = req.host().map(|host| host.to_lowercase());
+        let path = &req.path()[self.prefix.len()..];
+        let mut params = BTreeMap::new();
+        self.routes.iter().filter(|&(_, routes)| routes.iter().any(|route|
+            (route.host.is_none() || route.host == host) && route.matches(path, &mut params)
+        )).map(|(method, _)| method.clone()).collect()
+    }
+
+    /// Returns `true` if the given request matches a high-priority route (see `Router::prioritize`).
+    pub fn is_priority(&self, req: &Request) -> bool {
+        if !self.match_prefix(req.path()) {
+            return false;
+        }
+
+        let host = req.host().map(|host| host.to_lowercase());
+        let path = &req.path()[self.prefix.len()..];
+        let mut params = BTreeMap::new();
+        self.routes.iter().filter(|&(method, _)| method == req.method() || (*req.method() == Head && *method == Get))
+            .flat_map(|(_, routes)| routes.iter())
+            .any(|route| route.priority && (route.host.is_none() || route.host == host) && route.matches(path, &mut params))
+    }
+
     /// Returns `true` if the given path matches this router's prefix.
     fn match_prefix(&self, path: &[String]) -> bool {
         if path.len() >= self.prefix.len() {
@@ -253,10 +684,31 @@ impl RouterAny {
         (self.init)()
     }
 
-    pub fn run_middleware(&self, app: &mut Any, req: &mut Request, res: &mut Response) {
+    /// Runs the middleware of this router in order, stopping at the first error.
+    pub fn run_middleware(&self, app: &mut Any, req: &mut Request, res: &mut Response) -> result::Result<(), Error> {
         for middleware in &self.middleware {
-            middleware(app, req, res);
+            try!(middleware(app, req, res));
         }
+        Ok(())
+    }
+
+    /// Returns the methods for which routes are registered in this router.
+    pub fn methods(&self) -> Vec<Method> {
+        self.routes.iter().filter(|&(_, routes)| !routes.is_empty()).map(|(method, _)| method.clone()).collect()
+    }
+
+    /// Returns the method, full pattern (including the prefix), and host of each route
+    /// of this router, services having no method.
+    pub fn describe(&self) -> Vec<(Option<Method>, String, Option<String>)> {
+        let prefix: String = self.prefix.iter().map(|segment| "/".to_string() + &segment.to_string()).collect();
+        let mut routes: Vec<_> = self.routes.iter().flat_map(|(method, routes)|
+            routes.iter().map(move |route| (Some(method.clone()), route))
+        ).chain(self.services.iter().map(|service| (None, service))).map(|(method, route)|
+            (method, prefix.clone() + &route.pattern, route.host.clone())
+        ).collect();
+
+        routes.sort_by(|a, b| (&a.1, a.0.as_ref().map(ToString::to_string)).cmp(&(&b.1, b.0.as_ref().map(ToString::to_string))));
+        routes
     }
 
     pub fn set_prefix(&mut self, prefix: &str) {
@@ -0,0 +1,104 @@
+use std::sync::{Arc, Condvar, Mutex};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::thread;
+use std::time::{Duration, Instant};
+
+/// Default time to wait for in-flight requests to complete on shutdown.
+pub const DEFAULT_DRAIN_TIMEOUT: u64 = 30;
+
+struct State {
+    requested: Mutex<bool>,
+    condvar: Condvar,
+    in_flight: AtomicUsize
+}
+
+/// A handle to shut down a running Edge application gracefully.
+///
+/// Get it with `Edge::shutdown_handle` before calling `start`, and call `shutdown`
+/// from another thread (for instance a signal handler) to stop the server.
+/// The handle can be cloned and sent between threads.
+#[derive(Clone)]
+pub struct Shutdown {
+    state: Arc<State>
+}
+
+/// Marks a request as in flight for as long as it lives.
+pub struct InFlight {
+    state: Arc<State>
+}
+
+impl Shutdown {
+    pub fn new() -> Shutdown {
+        Shutdown {
+            state: Arc::new(State {
+                requested: Mutex::new(false),
+                condvar: Condvar::new(),
+                in_flight: AtomicUsize::new(0)
+            })
+        }
+    }
+
+    /// Asks the server to shut down.
+    ///
+    /// The server stops accepting new requests, waits for in-flight requests to complete
+    /// (up to the drain timeout), then closes the remaining connections and `start` returns.
+    pub fn shutdown(&self) {
+        let mut requested = self.state.requested.lock().unwrap();
+        *requested = true;
+        self.state.condvar.notify_all();
+    }
+
+    /// Returns `true` if shutdown has been requested.
+    pub fn is_requested(&self) -> bool {
+        *self.state.requested.lock().unwrap()
+    }
+
+    /// Blocks the current thread until shutdown is requested.
+    pub fn wait(&self) {
+        let mut requested = self.state.requested.lock().unwrap();
+        while !*requested {
+            requested = self.state.condvar.wait(requested).unwrap();
+        }
+    }
+
+    /// Returns the number of requests currently in flight.
+    ///
+    /// This can be polled during shutdown to watch the server drain.
+    pub fn in_flight(&self) -> usize {
+        self.state.in_flight.load(Ordering::SeqCst)
+    }
+
+    /// Waits until no request is in flight, or the timeout elapses.
+    ///
+    /// Returns the number of requests still in flight.
+    pub fn drain(&self, timeout: Duration) -> usize {
+        let start = Instant::now();
+        loop {
+            let in_flight = self.in_flight();
+            if in_flight == 0 || start.elapsed() >= timeout {
+                return in_flight;
+            }
+            thread::sleep(Duration::from_millis(10));
+        }
+    }
+
+    /// Marks a new request as in flight, unless `limit` requests are already in flight.
+    pub fn try_enter(&self, limit: Option<usize>) -> Option<InFlight> {
+        let previous = self.state.in_flight.fetch_add(1, Ordering::SeqCst);
+        let in_flight = InFlight {
+            state: self.state.clone()
+        };
+
+        match limit {
+            // dropping the guard decrements the counter again
+            Some(limit) if previous >= limit => None,
+            _ => Some(in_flight)
+        }
+    }
+}
+
+impl Drop for InFlight {
+    fn drop(&mut self) {
+        self.state.in_flight.fetch_sub(1, Ordering::SeqCst);
+    }
+}
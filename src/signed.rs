@@ -0,0 +1,117 @@
+//! Signatures with the secret key of the application, e.g. for expiring links.
+
+use crypto::hmac::Hmac;
+use crypto::mac::{Mac, MacResult};
+use crypto::sha2::Sha256;
+
+use std::sync::Arc;
+
+/// Name of the query parameter holding the expiry time of a signed URL (in seconds since the epoch).
+const EXPIRES: &'static str = "expires";
+
+/// Name of the query parameter holding the signature of a signed URL, which must be the last one.
+const SIGNATURE: &'static str = "signature";
+
+/// Secret key used to sign data with HMAC-SHA256.
+#[derive(Clone)]
+pub struct Secret {
+    key: Arc<Vec<u8>>
+}
+
+/// Reason why a signed URL is rejected.
+#[derive(Debug, PartialEq)]
+pub enum Invalid {
+    /// the URL has no signature or expiry time
+    Unsigned,
+
+    /// the signature does not match the URL
+    Tampered,
+
+    /// the expiry time has passed
+    Expired
+}
+
+impl Secret {
+    pub fn new(key: &[u8]) -> Secret {
+        Secret {
+            key: Arc::new(key.to_vec())
+        }
+    }
+
+    /// Returns the signature of the given data, encoded in hexadecimal.
+    pub fn sign(&self, data: &[u8]) -> String {
+        let mut hmac = Hmac::new(Sha256::new(), &self.key);
+        hmac.input(data);
+        hmac.result().code().iter().map(|byte| format!("{:02x}", byte)).collect()
+    }
+
+    /// Returns `true` if the given signature (in hexadecimal) is the signature of the given data.
+    ///
+    /// The comparison takes a constant time, so as not to leak the expected signature.
+    pub fn verify(&self, data: &[u8], signature: &str) -> bool {
+        let expected = self.sign(data);
+        MacResult::new(expected.as_bytes()) == MacResult::new(signature.as_bytes())
+    }
+
+    /// Appends an expiry time and a signature to the query of the given URL (path and query).
+    pub fn sign_url(&self, url: &str, expires: u64) -> String {
+        let separator = if url.contains('?') { '&' } else { '?' };
+        let unsigned = format!("{}{}{}={}", url, separator, EXPIRES, expires);
+        let signature = self.sign(unsigned.as_bytes());
+        format!("{}&{}={}", unsigned, SIGNATURE, signature)
+    }
+
+    /// Checks the signature and expiry time of the given URL (path and query, as received), at time `now`.
+    pub fn verify_url(&self, path: &str, query: Option<&str>, now: u64) -> Result<(), Invalid> {
+        let query = try!(query.ok_or(Invalid::Unsigned));
+
+        // the signature covers the whole URL up to the signature parameter
+        let marker = format!("&{}=", SIGNATURE);
+        let index = try!(query.rfind(&marker).ok_or(Invalid::Unsigned));
+        let signed = format!("{}?{}", path, &query[..index]);
+        let signature = &query[index + marker.len()..];
+        if !self.verify(signed.as_bytes(), signature) {
+            return Err(Invalid::Tampered);
+        }
+
+        let prefix = format!("{}=", EXPIRES);
+        let expires = query[..index].split('&')
+            .filter(|pair| pair.starts_with(&prefix))
+            .filter_map(|pair| pair[prefix.len()..].parse::<u64>().ok())
+            .last();
+        match expires {
+            Some(expires) if now <= expires => Ok(()),
+            Some(_) => Err(Invalid::Expired),
+            None => Err(Invalid::Unsigned)
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Invalid, Secret};
+
+    fn split(url: &str) -> (&str, Option<&str>) {
+        let mut parts = url.splitn(2, '?');
+        (parts.next().unwrap(), parts.next())
+    }
+
+    #[test]
+    fn signed_url_verifies_until_expiry() {
+        let secret = Secret::new(b"key");
+        let url = secret.sign_url("/files/a%20b?name=x+y", 100);
+        let (path, query) = split(&url);
+        assert!(secret.verify_url(path, query, 100).is_ok());
+        assert!(match secret.verify_url(path, query, 101) { Err(Invalid::Expired) => true, _ => false });
+    }
+
+    #[test]
+    fn modified_url_is_rejected() {
+        let secret = Secret::new(b"key");
+        let url = secret.sign_url("/files/a?name=x", 100);
+        let (path, query) = split(&url);
+        assert!(match secret.verify_url("/files/b", query, 0) { Err(Invalid::Tampered) => true, _ => false });
+        assert!(match secret.verify_url(path, Some("name=x"), 0) { Err(Invalid::Unsigned) => true, _ => false });
+        assert!(match Secret::new(b"other").verify_url(path, query, 0) { Err(Invalid::Tampered) => true, _ => false });
+    }
+}
@@ -0,0 +1,78 @@
+//! Deserialization of URL-encoded pairs (such as a query string) into typed values.
+//!
+//! Values are strings in URL-encoded data, so they are parsed according to the type
+//! expected by the target, e.g. "2" is parsed as a number for an integer field.
+
+use serde::de::{self, Deserialize, Deserializer, Visitor};
+use serde::de::value::{Error, MapDeserializer, ValueDeserializer};
+
+/// Deserializes a value of type `T` (usually a struct) from the given pairs of names and values.
+pub fn from_pairs<T: Deserialize>(pairs: Vec<(String, String)>) -> Result<T, Error> {
+    let len = pairs.len();
+    let iter = pairs.into_iter().map(|(name, value)| (name, Text(value)));
+    let mut deserializer: MapDeserializer<_, String, Text, Error> = MapDeserializer::new(iter, len);
+    T::deserialize(&mut deserializer)
+}
+
+/// A value of a pair, deserialized according to the type expected by the target.
+struct Text(String);
+
+impl ValueDeserializer<Error> for Text {
+    type Deserializer = TextDeserializer;
+
+    fn into_deserializer(self) -> TextDeserializer {
+        TextDeserializer(Some(self.0))
+    }
+}
+
+struct TextDeserializer(Option<String>);
+
+impl TextDeserializer {
+    fn take(&mut self) -> Result<String, Error> {
+        self.0.take().ok_or_else(de::Error::end_of_stream)
+    }
+}
+
+macro_rules! deserialize_parsed {
+    ($($method:ident => $visit:ident,)*) => {
+        $(
+            fn $method<V>(&mut self, mut visitor: V) -> Result<V::Value, Error> where V: Visitor {
+                let text = try!(self.take());
+                match text.parse() {
+                    Ok(value) => visitor.$visit(value),
+                    Err(_) => Err(de::Error::invalid_value(&format!("cannot parse \"{}\"", text)))
+                }
+            }
+        )*
+    }
+}
+
+impl Deserializer for TextDeserializer {
+    type Error = Error;
+
+    fn deserialize<V>(&mut self, mut visitor: V) -> Result<V::Value, Error> where V: Visitor {
+        let text = try!(self.take());
+        visitor.visit_string(text)
+    }
+
+    fn deserialize_option<V>(&mut self, mut visitor: V) -> Result<V::Value, Error> where V: Visitor {
+        visitor.visit_some(self)
+    }
+
+    deserialize_parsed! {
+        deserialize_bool => visit_bool,
+        deserialize_char => visit_char,
+        deserialize_usize => visit_usize,
+        deserialize_u8 => visit_u8,
+        deserialize_u16 => visit_u16,
+        deserialize_u32 => visit_u32,
+        deserialize_u64 => visit_u64,
+        deserialize_isize => visit_isize,
+        deserialize_i8 => visit_i8,
+        deserialize_i16 => visit_i16,
+        deserialize_i32 => visit_i32,
+        deserialize_i64 => visit_i64,
+        deserialize_f32 => visit_f32,
+        deserialize_f64 => visit_f64,
+    }
+}
@@ -0,0 +1,53 @@
+extern crate edge;
+
+mod common;
+
+use edge::{Assets, CachePolicy, Edge};
+
+use common::{get, with_server};
+
+fn app() -> Edge {
+    let mut assets = Assets::new();
+    assets.insert("css/app.css", b"body { margin: 0 }", "text/css");
+    assets.insert("/app.js", b"alert(1)", "application/javascript");
+    assets.cache_control(CachePolicy::new().public().max_age(3600));
+
+    let mut edge = Edge::new("127.0.0.1:0");
+    edge.mount_assets("/static", assets);
+    edge
+}
+
+#[test]
+fn assets_are_served_from_memory() {
+    let (css, js, missing) = with_server(app(), |addr|
+        (get(addr, "/static/css/app.css", ""), get(addr, "/static/app.js", ""), get(addr, "/static/none.css", "")));
+    assert_eq!(css.status, 200);
+    assert_eq!(css.header("Content-Type"), Some("text/css"));
+    assert_eq!(css.header("Cache-Control"), Some("public, max-age=3600"));
+    assert_eq!(css.text(), "body { margin: 0 }");
+    assert_eq!(js.text(), "alert(1)");
+    assert_eq!(missing.status, 404);
+}
+
+#[test]
+fn assets_are_revalidated_with_etag() {
+    let (first, second) = with_server(app(), |addr| {
+        let first = get(addr, "/static/css/app.css", "");
+        let etag = first.header("ETag").unwrap().to_string();
+        (first, get(addr, "/static/css/app.css", &format!("If-None-Match: {}\r\n", etag)))
+    });
+    assert_eq!(first.status, 200);
+    assert_eq!(second.status, 304);
+    assert!(second.body.is_empty());
+}
+
+#[test]
+fn favicon_is_served_with_cache_headers() {
+    let mut edge = app();
+    edge.favicon(b"\x00\x00\x01\x00icon");
+    let reply = with_server(edge, |addr| get(addr, "/favicon.ico", ""));
+    assert_eq!(reply.status, 200);
+    assert_eq!(reply.header("Content-Type"), Some("image/x-icon"));
+    assert_eq!(reply.header("Cache-Control"), Some("public, max-age=604800"));
+    assert_eq!(reply.body, b"\x00\x00\x01\x00icon".to_vec());
+}
@@ -0,0 +1,43 @@
+extern crate edge;
+
+mod common;
+
+use edge::{Edge, Router};
+
+use common::{send, with_server};
+
+fn app(limit: Option<usize>) -> Edge {
+    let mut edge = Edge::new("127.0.0.1:0");
+    if let Some(limit) = limit {
+        edge.max_body_size(limit);
+    }
+    let mut router = Router::<()>::new();
+    router.mount_service("/headroom", |req, res| {
+        res.body(format!("{:?}", req.body_limit_headroom()))
+    });
+    edge.mount("/", router);
+    edge
+}
+
+fn post(addr: ::std::net::SocketAddr, body: &str) -> common::Reply {
+    send(addr, &format!("POST /headroom/x HTTP/1.1\r\nHost: localhost\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{}", body.len(), body))
+}
+
+#[test]
+fn headroom_is_limit_minus_body() {
+    let (empty, ten) = with_server(app(Some(100)), |addr| (post(addr, ""), post(addr, "0123456789")));
+    assert_eq!(empty.text(), "Some(100)");
+    assert_eq!(ten.text(), "Some(90)");
+}
+
+#[test]
+fn no_headroom_without_limit() {
+    let reply = with_server(app(None), |addr| post(addr, "abc"));
+    assert_eq!(reply.text(), "None");
+}
+
+#[test]
+fn body_over_limit_is_rejected() {
+    let reply = with_server(app(Some(4)), |addr| post(addr, "0123456789"));
+    assert_eq!(reply.status, 413);
+}
@@ -0,0 +1,62 @@
+extern crate edge;
+
+mod common;
+
+use edge::{Edge, Router};
+
+use common::{connect, read_reply, with_server};
+
+use std::io::Write;
+use std::iter;
+
+fn app(max_buffers: usize) -> Edge {
+    let mut edge = Edge::new("127.0.0.1:0");
+    edge.buffer_pool(max_buffers);
+    edge.buffer_sizes(512, 512);
+    let mut router = Router::<()>::new();
+    router.mount_service("/echo", |req, res| {
+        let body = req.body().map(|body| body.to_vec()).unwrap_or(Vec::new());
+        res.body(body)
+    });
+    edge.mount("/", router);
+    edge
+}
+
+/// Posts bodies of decreasing sizes on one connection, so that reused buffers would show stale bytes.
+fn echo_all(edge: Edge) -> Vec<String> {
+    with_server(edge, |addr| {
+        let mut stream = connect(addr);
+        ["a long body that fills the buffer", "short", "", "mid-size"].iter().map(|body| {
+            stream.write_all(format!("POST /echo/x HTTP/1.1\r\nHost: localhost\r\nContent-Length: {}\r\n\r\n{}", body.len(), body).as_bytes()).unwrap();
+            read_reply(&mut stream).text().to_string()
+        }).collect()
+    })
+}
+
+#[test]
+fn pooled_buffers_do_not_leak_between_requests() {
+    let expected = vec!["a long body that fills the buffer", "short", "", "mid-size"];
+    assert_eq!(echo_all(app(64)), expected);
+    assert_eq!(echo_all(app(0)), expected);
+}
+
+#[test]
+fn bodies_larger_than_buffers_are_read_and_written_whole() {
+    let body = iter::repeat("0123456789").take(1000).collect::<String>();
+    let chunked = body.as_bytes().chunks(3000)
+        .map(|chunk| format!("{:x}\r\n{}\r\n", chunk.len(), String::from_utf8(chunk.to_vec()).unwrap()))
+        .collect::<String>();
+
+    let echoed = with_server(app(64), move |addr| {
+        let mut stream = connect(addr);
+        stream.write_all(format!("POST /echo/x HTTP/1.1\r\nHost: localhost\r\nTransfer-Encoding: chunked\r\n\r\n{}0\r\n\r\n", chunked).as_bytes()).unwrap();
+        read_reply(&mut stream).text().to_string()
+    });
+    assert_eq!(echoed, body);
+}
+
+#[test]
+#[should_panic(expected = "read buffer size must be between 512 and 1048576 bytes, got 100")]
+fn buffer_sizes_are_bounded() {
+    Edge::new("127.0.0.1:0").buffer_sizes(100, 4096);
+}
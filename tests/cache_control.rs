@@ -0,0 +1,47 @@
+extern crate edge;
+
+mod common;
+
+use edge::{CachePolicy, Edge, Router};
+
+use common::{get, with_server};
+
+fn app() -> Edge {
+    let mut edge = Edge::new("127.0.0.1:0");
+    let mut router = Router::<()>::new();
+    router.mount_service("/cached", |_req, res| {
+        res.cache_control(CachePolicy::new().public().max_age(60).private().max_age(10).no_transform());
+        res.content_type("text/html").body("cached")
+    });
+    router.mount_service("/page", |_req, res| res.content_type("text/html; charset=UTF-8").body("<p>page</p>"));
+    router.mount_service("/text", |_req, res| res.content_type("text/plain").body("text"));
+    edge.mount("/", router);
+    edge
+}
+
+#[test]
+fn cache_control_is_sent() {
+    let reply = with_server(app(), |addr| get(addr, "/cached/x", ""));
+    assert_eq!(reply.header("Cache-Control"), Some("private, max-age=10, no-transform"));
+}
+
+#[test]
+fn html_gets_default_policy() {
+    let (page, text, cached) = with_server(app(), |addr| (get(addr, "/page/x", ""), get(addr, "/text/x", ""), get(addr, "/cached/x", "")));
+    assert_eq!(page.header("Cache-Control"), Some("private, no-cache"));
+    assert_eq!(text.header("Cache-Control"), None);
+    assert_eq!(cached.header("Cache-Control"), Some("private, max-age=10, no-transform"));
+}
+
+#[test]
+fn html_default_policy_can_be_changed_or_removed() {
+    let mut edge = app();
+    edge.html_cache_policy(Some(CachePolicy::new().no_store()));
+    let changed = with_server(edge, |addr| get(addr, "/page/x", ""));
+    let mut edge = app();
+    edge.html_cache_policy(None);
+    let removed = with_server(edge, |addr| get(addr, "/page/x", ""));
+
+    assert_eq!(changed.header("Cache-Control"), Some("no-store"));
+    assert_eq!(removed.header("Cache-Control"), None);
+}
@@ -0,0 +1,167 @@
+//! Helpers shared by the integration tests: run an application on a free port,
+//! and talk to it over raw sockets.
+
+#![allow(dead_code)]
+
+use edge::Edge;
+
+use std::env;
+use std::fs::{self, File};
+use std::io::{Read, Write};
+use std::net::{SocketAddr, TcpStream};
+use std::path::PathBuf;
+use std::panic::{self, AssertUnwindSafe};
+use std::str;
+use std::thread;
+use std::time::Duration;
+
+/// A response read from a socket.
+pub struct Reply {
+    pub status: u16,
+    pub head: String,
+    pub body: Vec<u8>
+}
+
+impl Reply {
+    /// Returns the value of the given header (case-insensitive), if any.
+    pub fn header(&self, name: &str) -> Option<&str> {
+        let prefix = name.to_lowercase() + ":";
+        self.head.lines().skip(1)
+            .find(|line| line.to_lowercase().starts_with(&prefix))
+            .map(|line| line[prefix.len()..].trim())
+    }
+
+    pub fn text(&self) -> &str {
+        str::from_utf8(&self.body).unwrap()
+    }
+}
+
+/// Binds the application to a free port, runs `client` in another thread with the address,
+/// then shuts the application down and returns what `client` returned.
+pub fn with_server<F, R>(mut edge: Edge, client: F) -> R
+    where F: FnOnce(SocketAddr) -> R + Send + 'static, R: Send + 'static {
+    let addr = edge.bind().unwrap();
+    let shutdown = edge.shutdown_handle();
+    let client = thread::spawn(move || {
+        let result = panic::catch_unwind(AssertUnwindSafe(|| client(addr)));
+        shutdown.shutdown();
+        result
+    });
+
+    edge.start().unwrap();
+    match client.join().unwrap() {
+        Ok(result) => result,
+        Err(e) => panic::resume_unwind(e)
+    }
+}
+
+/// Creates an empty temporary directory with the given name (unique per test),
+/// containing the given files (relative paths and contents).
+pub fn temp_dir(name: &str, files: &[(&str, &str)]) -> PathBuf {
+    let dir = env::temp_dir().join(format!("edge-test-{}", name));
+    let _ = fs::remove_dir_all(&dir);
+    for &(path, contents) in files {
+        let path = dir.join(path);
+        fs::create_dir_all(path.parent().unwrap()).unwrap();
+        File::create(path).unwrap().write_all(contents.as_bytes()).unwrap();
+    }
+    fs::create_dir_all(&dir).unwrap();
+    dir
+}
+
+/// Opens a connection to the given address.
+pub fn connect(addr: SocketAddr) -> TcpStream {
+    let stream = TcpStream::connect(addr).unwrap();
+    stream.set_read_timeout(Some(Duration::from_secs(10))).unwrap();
+    stream
+}
+
+/// Returns `true` if the server closed the given connection (after its last response).
+pub fn is_closed(stream: &mut TcpStream) -> bool {
+    stream.read(&mut [0; 1]).map(|n| n == 0).unwrap_or(true)
+}
+
+/// Sends the given raw request on a new connection and reads the response.
+pub fn send(addr: SocketAddr, request: &str) -> Reply {
+    let mut stream = connect(addr);
+    stream.write_all(request.as_bytes()).unwrap();
+    read_reply(&mut stream)
+}
+
+/// Sends a GET request for the given path with the given extra header lines and reads the response.
+pub fn get(addr: SocketAddr, path: &str, headers: &str) -> Reply {
+    send(addr, &format!("GET {} HTTP/1.1\r\nHost: localhost\r\n{}Connection: close\r\n\r\n", path, headers))
+}
+
+/// Sends a HEAD request for the given path with the given extra header lines and reads the response,
+/// whose body is whatever the server sent after the headers (i.e. should be empty).
+pub fn head(addr: SocketAddr, path: &str, headers: &str) -> Reply {
+    let mut stream = connect(addr);
+    stream.write_all(format!("HEAD {} HTTP/1.1\r\nHost: localhost\r\n{}Connection: close\r\n\r\n", path, headers).as_bytes()).unwrap();
+    let mut reply = read_head(&mut stream);
+    let _ = stream.read_to_end(&mut reply.body);
+    reply
+}
+
+/// Reads one response from the given stream, using Content-Length or chunked encoding to find its end,
+/// or reading until the connection is closed otherwise.
+pub fn read_reply(stream: &mut TcpStream) -> Reply {
+    let mut reply = read_head(stream);
+    let length = reply.header("content-length").map(|len| len.parse::<usize>().unwrap());
+    let chunked = reply.header("transfer-encoding").map_or(false, |te| te.contains("chunked"));
+    if let Some(length) = length {
+        let mut body = vec![0; length];
+        stream.read_exact(&mut body).unwrap();
+        reply.body = body;
+    } else if chunked {
+        reply.body = read_chunked(stream);
+    } else if reply.status != 204 && reply.status != 304 && reply.status >= 200 {
+        let _ = stream.read_to_end(&mut reply.body);
+    }
+    reply
+}
+
+/// Reads the status line and headers of one response from the given stream.
+pub fn read_head(stream: &mut TcpStream) -> Reply {
+    let mut head = Vec::new();
+    let mut byte = [0u8; 1];
+    while !head.ends_with(b"\r\n\r\n") {
+        match stream.read(&mut byte) {
+            Ok(1) => head.push(byte[0]),
+            _ => break
+        }
+    }
+    let head = String::from_utf8(head).unwrap();
+    let status = head.split(' ').nth(1).and_then(|code| code.parse().ok()).unwrap_or(0);
+
+    Reply { status: status, head: head, body: Vec::new() }
+}
+
+fn read_line(stream: &mut TcpStream) -> String {
+    let mut line = Vec::new();
+    let mut byte = [0u8; 1];
+    while !line.ends_with(b"\r\n") {
+        match stream.read(&mut byte) {
+            Ok(1) => line.push(byte[0]),
+            _ => break
+        }
+    }
+    String::from_utf8(line).unwrap().trim().to_string()
+}
+
+fn read_chunked(stream: &mut TcpStream) -> Vec<u8> {
+    let mut body = Vec::new();
+    loop {
+        let size = read_line(stream);
+        let size = usize::from_str_radix(size.split(';').next().unwrap(), 16).unwrap_or(0);
+        if size == 0 {
+            // trailers, if any, until the empty line
+            while !read_line(stream).is_empty() {}
+            return body;
+        }
+        let mut chunk = vec![0; size];
+        stream.read_exact(&mut chunk).unwrap();
+        body.extend_from_slice(&chunk);
+        read_line(stream);
+    }
+}
@@ -0,0 +1,78 @@
+extern crate edge;
+extern crate flate2;
+
+mod common;
+
+use edge::{Edge, Router};
+
+use flate2::Compression;
+use flate2::write::{GzEncoder, ZlibEncoder};
+
+use common::{connect, read_reply, with_server, Reply};
+
+use std::io::Write;
+use std::iter;
+use std::net::SocketAddr;
+
+fn app() -> Edge {
+    let mut edge = Edge::new("127.0.0.1:0");
+    edge.max_body_size(1024);
+    let mut router = Router::<()>::new();
+    router.mount_service("/echo", |req, res| {
+        let body = try!(req.body()).to_vec();
+        res.body(body)
+    });
+    edge.mount("/", router);
+    edge
+}
+
+fn gzip(data: &str) -> Vec<u8> {
+    let mut encoder = GzEncoder::new(Vec::new(), Compression::Default);
+    encoder.write_all(data.as_bytes()).unwrap();
+    encoder.finish().unwrap()
+}
+
+fn deflate(data: &str) -> Vec<u8> {
+    let mut encoder = ZlibEncoder::new(Vec::new(), Compression::Default);
+    encoder.write_all(data.as_bytes()).unwrap();
+    encoder.finish().unwrap()
+}
+
+fn post(addr: SocketAddr, encoding: &str, body: &[u8]) -> Reply {
+    let mut stream = connect(addr);
+    let head = format!("POST /echo/x HTTP/1.1\r\nHost: localhost\r\nContent-Encoding: {}\r\nContent-Length: {}\r\nConnection: close\r\n\r\n",
+        encoding, body.len());
+    stream.write_all(head.as_bytes()).unwrap();
+    stream.write_all(body).unwrap();
+    read_reply(&mut stream)
+}
+
+#[test]
+fn compressed_bodies_are_decoded() {
+    let (gzipped, deflated) = with_server(app(), |addr| (
+        post(addr, "gzip", &gzip("hello gzip")),
+        post(addr, "deflate", &deflate("hello deflate"))
+    ));
+    assert_eq!(gzipped.text(), "hello gzip");
+    assert_eq!(deflated.text(), "hello deflate");
+}
+
+#[test]
+fn limit_applies_to_decoded_size() {
+    let large = iter::repeat("a").take(2048).collect::<String>();
+    let compressed = gzip(&large);
+    assert!(compressed.len() < 1024);
+
+    let reply = with_server(app(), move |addr| post(addr, "gzip", &compressed));
+    assert_eq!(reply.status, 413);
+}
+
+#[test]
+fn invalid_or_unknown_encodings_are_rejected() {
+    let (invalid, unknown) = with_server(app(), |addr| (
+        post(addr, "gzip", b"not gzip"),
+        post(addr, "br", b"whatever")
+    ));
+    assert_eq!(invalid.status, 400);
+    assert_eq!(unknown.status, 415);
+}
@@ -0,0 +1,62 @@
+extern crate edge;
+
+mod common;
+
+use edge::{Edge, Router};
+
+use std::io::Write;
+
+use common::{connect, read_reply, with_server};
+
+#[derive(Clone)]
+struct Visits(usize);
+
+fn app() -> Edge {
+    let mut edge = Edge::new("127.0.0.1:0");
+    let mut router = Router::<()>::new();
+    router.mount_service("/visit", |req, res| {
+        let visits = req.connection().get::<Visits>().map_or(1, |visits| visits.0 + 1);
+        req.connection().set(Visits(visits));
+        res.body(visits.to_string())
+    });
+    edge.mount("/", router);
+    edge
+}
+
+#[test]
+fn state_is_shared_by_requests_on_same_connection() {
+    let replies = with_server(app(), |addr| {
+        let mut stream = connect(addr);
+        let mut replies = Vec::new();
+        for _ in 0..2 {
+            stream.write_all(b"GET /visit/x HTTP/1.1\r\nHost: localhost\r\n\r\n").unwrap();
+            replies.push(read_reply(&mut stream).text().to_string());
+        }
+        replies
+    });
+    assert_eq!(replies, vec!["1", "2"]);
+}
+
+#[test]
+fn state_is_not_shared_between_connections() {
+    let replies = with_server(app(), |addr| {
+        let mut streams = vec![connect(addr), connect(addr)];
+        let mut replies = Vec::new();
+        for &i in &[0, 1, 0] {
+            streams[i].write_all(b"GET /visit/x HTTP/1.1\r\nHost: localhost\r\n\r\n").unwrap();
+            replies.push(read_reply(&mut streams[i]).text().to_string());
+        }
+        replies
+    });
+    assert_eq!(replies, vec!["1", "1", "2"]);
+}
+
+#[test]
+fn state_is_shared_by_pipelined_requests() {
+    let replies = with_server(app(), |addr| {
+        let mut stream = connect(addr);
+        stream.write_all(b"GET /visit/x HTTP/1.1\r\nHost: localhost\r\n\r\nGET /visit/x HTTP/1.1\r\nHost: localhost\r\n\r\n").unwrap();
+        vec![read_reply(&mut stream).text().to_string(), read_reply(&mut stream).text().to_string()]
+    });
+    assert_eq!(replies, vec!["1", "2"]);
+}
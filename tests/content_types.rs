@@ -0,0 +1,56 @@
+extern crate edge;
+
+mod common;
+
+use edge::{Edge, Request, Response, Result, Router};
+
+use std::sync::atomic::{AtomicUsize, ATOMIC_USIZE_INIT, Ordering};
+
+use common::{send, with_server, Reply};
+
+static MIDDLEWARE_CALLS: AtomicUsize = ATOMIC_USIZE_INIT;
+
+#[derive(Default)]
+struct App;
+
+impl App {
+    fn count(&mut self, _req: &mut Request, _res: &mut Response) {
+        MIDDLEWARE_CALLS.fetch_add(1, Ordering::SeqCst);
+    }
+
+    fn create(&mut self, _req: &Request, res: &mut Response) -> Result {
+        res.body("created")
+    }
+}
+
+fn app() -> Edge {
+    let mut edge = Edge::new("127.0.0.1:0");
+    let mut router = Router::<App>::new();
+    router.add_middleware(App::count);
+    router.post_json("/items", App::create);
+    router.insert_accepting("PUT".parse().unwrap(), "/items", &["text/csv", "Text/Plain"], App::create);
+    edge.mount("/", router);
+    edge
+}
+
+fn request(addr: ::std::net::SocketAddr, method: &str, content_type: Option<&str>) -> Reply {
+    let header = content_type.map_or(String::new(), |value| format!("Content-Type: {}\r\n", value));
+    send(addr, &format!("{} /items HTTP/1.1\r\nHost: localhost\r\n{}Content-Length: 2\r\nConnection: close\r\n\r\n{{}}", method, header))
+}
+
+#[test]
+fn only_accepted_content_types_reach_handler() {
+    let statuses: Vec<u16> = with_server(app(), |addr| vec![
+        request(addr, "POST", Some("application/json")),
+        request(addr, "POST", Some("Application/JSON; charset=utf-8")),
+        request(addr, "POST", Some("text/plain")),
+        request(addr, "POST", None),
+        request(addr, "PUT", Some("text/csv")),
+        request(addr, "PUT", Some("text/plain")),
+        request(addr, "PUT", Some("application/json"))
+    ]).iter().map(|reply| reply.status).collect();
+    assert_eq!(statuses, vec![200, 200, 415, 415, 200, 200, 415]);
+
+    // middleware only ran for accepted requests
+    assert_eq!(MIDDLEWARE_CALLS.load(Ordering::SeqCst), 4);
+}
@@ -0,0 +1,62 @@
+extern crate edge;
+
+mod common;
+
+use edge::{CookieBuilder, Edge, Router, SameSite};
+
+use common::{get, with_server};
+
+use std::time::Duration;
+
+fn app() -> Edge {
+    let mut edge = Edge::new("127.0.0.1:0");
+    let mut router = Router::<()>::new();
+    router.mount_service("/login", |_req, res| {
+        res.cookie(CookieBuilder::new("session", "42")
+            .max_age(Duration::from_secs(60))
+            .same_site(SameSite::Strict)
+            .http_only()
+            .build());
+        res.body("welcome")
+    });
+    router.mount_service("/whoami", |req, res| {
+        let names: Vec<String> = req.cookies().map(|cookie| format!("{}={}", cookie.name, cookie.value)).collect();
+        res.body(names.join(","))
+    });
+    edge.mount("/", router);
+    edge
+}
+
+#[test]
+fn cookie_attributes_are_sent() {
+    let reply = with_server(app(), |addr| get(addr, "/login/x", ""));
+    let cookie = reply.header("Set-Cookie").unwrap();
+    assert!(cookie.starts_with("session=42; HttpOnly; Max-Age=60; Expires="), "{}", cookie);
+    assert!(cookie.ends_with("; SameSite=Strict"), "{}", cookie);
+}
+
+#[test]
+fn request_cookies_are_parsed() {
+    let reply = with_server(app(), |addr| get(addr, "/whoami/x", "Cookie: session=42; theme=dark\r\n"));
+    assert_eq!(reply.text(), "session=42,theme=dark");
+}
+
+#[test]
+fn prefixed_cookies_get_required_attributes() {
+    assert_eq!(CookieBuilder::host_prefixed("id", "1").build().to_string(), "__Host-id=1; Secure; Path=/");
+    assert_eq!(CookieBuilder::secure_prefixed("id", "1").build().to_string(), "__Secure-id=1; Secure");
+}
+
+#[test]
+fn prefixed_cookies_without_required_attributes_are_rejected() {
+    assert!(CookieBuilder::new("__Secure-id", "1").try_build().is_err());
+    assert!(CookieBuilder::new("__Host-id", "1").secure().try_build().is_err());
+    assert!(CookieBuilder::host_prefixed("id", "1").path("/app").try_build().is_err());
+    assert!(CookieBuilder::new("__Host-id", "1").secure().path("/").try_build().is_ok());
+}
+
+#[test]
+#[should_panic(expected = "cookie __Host-id must not have a Domain")]
+fn building_invalid_prefixed_cookie_panics() {
+    CookieBuilder::host_prefixed("id", "1").domain("example.com").build();
+}
@@ -0,0 +1,41 @@
+extern crate edge;
+
+mod common;
+
+use edge::{Csp, Edge, Router};
+
+use common::{get, with_server};
+
+fn app() -> Edge {
+    let mut edge = Edge::new("127.0.0.1:0");
+    edge.default_csp(Csp::new().default_src("'self'").object_src("'none'"));
+    let mut router = Router::<()>::new();
+    router.mount_service("/page", |_req, res| res.content_type("text/html").body("<p>page</p>"));
+    router.mount_service("/embed", |_req, res| {
+        res.csp(&Csp::new().frame_ancestors("https://example.com").report_only());
+        res.content_type("text/html").body("<p>embed</p>")
+    });
+    router.mount_service("/data", |_req, res| res.content_type("text/plain").body("data"));
+    edge.mount("/", router);
+    edge
+}
+
+#[test]
+fn default_csp_is_sent_with_html() {
+    let (page, data) = with_server(app(), |addr| (get(addr, "/page/x", ""), get(addr, "/data/x", "")));
+    assert_eq!(page.header("Content-Security-Policy"), Some("default-src 'self'; object-src 'none'"));
+    assert_eq!(data.header("Content-Security-Policy"), None);
+}
+
+#[test]
+fn policy_of_handler_replaces_default() {
+    let reply = with_server(app(), |addr| get(addr, "/embed/x", ""));
+    assert_eq!(reply.header("Content-Security-Policy"), None);
+    assert_eq!(reply.header("Content-Security-Policy-Report-Only"), Some("frame-ancestors https://example.com"));
+}
+
+#[test]
+fn sources_are_merged_per_directive() {
+    let csp = Csp::new().script_src("'self' https://a.example").directive("Script-Src", "https://b.example 'self'");
+    assert_eq!(csp.to_string(), "script-src 'self' https://a.example https://b.example");
+}
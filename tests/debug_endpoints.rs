@@ -0,0 +1,47 @@
+extern crate edge;
+
+mod common;
+
+use edge::{json, Edge, Mode, Request, Response, Result, Router};
+
+use common::{get, with_server};
+
+#[derive(Default)]
+struct App;
+
+impl App {
+    fn user(&mut self, _req: &Request, res: &mut Response) -> Result {
+        res.body("user")
+    }
+}
+
+fn app(mode: Mode) -> Edge {
+    let mut edge = Edge::new("127.0.0.1:0");
+    edge.mode(mode);
+    edge.max_body_size(1024);
+    edge.enable_debug_endpoints();
+    let mut router = Router::<App>::new();
+    router.get("/users/:id", App::user);
+    edge.mount("/", router);
+    edge
+}
+
+#[test]
+fn debug_endpoints_describe_routes_and_config() {
+    let (routes, config) = with_server(app(Mode::Development), |addr| (get(addr, "/debug/routes", ""), get(addr, "/debug/config", "")));
+    let routes: json::Value = json::from_slice(&routes.body).unwrap();
+    let users = routes.as_array().unwrap().iter()
+        .find(|route| route.find("path").and_then(|path| path.as_string()) == Some("/users/:id"))
+        .expect("route /users/:id is listed");
+    assert_eq!(users.find("method").and_then(|method| method.as_string()), Some("GET"));
+
+    let config: json::Value = json::from_slice(&config.body).unwrap();
+    assert_eq!(config.find("mode").and_then(|mode| mode.as_string()), Some("development"));
+    assert_eq!(config.find("max_body_size").and_then(|max| max.as_u64()), Some(1024));
+}
+
+#[test]
+fn debug_endpoints_are_not_mounted_in_production() {
+    let reply = with_server(app(Mode::Production), |addr| get(addr, "/debug/routes", ""));
+    assert_eq!(reply.status, 404);
+}
@@ -0,0 +1,47 @@
+extern crate edge;
+
+mod common;
+
+use edge::{defer, Edge, Router, Status};
+
+use std::thread;
+
+use common::{get, with_server};
+
+fn app() -> Edge {
+    let mut edge = Edge::new("127.0.0.1:0");
+    let mut router = Router::<()>::new();
+    router.mount_service("/later", |_req, _res| defer(|deferred| {
+        thread::spawn(move || deferred.send("done"));
+    }));
+    router.mount_service("/dropped", |_req, _res| defer(|deferred| drop(deferred)));
+    router.mount_service("/headers", |_req, res| {
+        res.status(Status::Accepted).content_type("text/plain").header_raw("X-Job", "42");
+        defer(|deferred| {
+            thread::spawn(move || deferred.end());
+        })
+    });
+    edge.mount("/", router);
+    edge
+}
+
+#[test]
+fn deferred_response_is_sent_once() {
+    let reply = with_server(app(), |addr| get(addr, "/later/x", ""));
+    assert_eq!(reply.status, 200);
+    assert_eq!(reply.text(), "done");
+}
+
+#[test]
+fn dropped_deferred_response_is_an_error() {
+    let reply = with_server(app(), |addr| get(addr, "/dropped/x", ""));
+    assert_eq!(reply.status, 500);
+}
+
+#[test]
+fn deferred_response_keeps_headers_set_before() {
+    let reply = with_server(app(), |addr| get(addr, "/headers/x", ""));
+    assert_eq!(reply.status, 202);
+    assert_eq!(reply.header("X-Job"), Some("42"));
+    assert_eq!(reply.header("Content-Type"), Some("text/plain"));
+}
@@ -0,0 +1,62 @@
+extern crate edge;
+
+mod common;
+
+use edge::{stream, Edge, Router};
+
+use std::io::{self, Read, Write};
+use std::sync::atomic::{AtomicUsize, ATOMIC_USIZE_INIT, Ordering};
+use std::thread;
+use std::time::Duration;
+
+use common::{connect, get, with_server};
+
+const CHUNKS: usize = 500;
+
+/// 1 once the slow stream wrote all its chunks, 2 if a write failed before.
+static ENDED: AtomicUsize = ATOMIC_USIZE_INIT;
+
+fn write_slowly(writer: &mut Write) -> io::Result<()> {
+    for _ in 0..CHUNKS {
+        try!(writer.write_all(&[b'x'; 1024]));
+        thread::sleep(Duration::from_millis(10));
+    }
+    Ok(())
+}
+
+fn app() -> Edge {
+    let mut edge = Edge::new("127.0.0.1:0");
+    let mut router = Router::<()>::new();
+    router.mount_service("/slow", |_req, _res| stream(|_app: &mut (), writer: &mut Write| -> io::Result<()> {
+        let result = write_slowly(writer);
+        ENDED.store(if result.is_ok() { 1 } else { 2 }, Ordering::SeqCst);
+        result
+    }));
+    router.mount_service("/ping", |_req, res| res.body("pong"));
+    edge.mount("/", router);
+    edge
+}
+
+#[test]
+fn stream_stops_when_client_disconnects() {
+    let reply = with_server(app(), |addr| {
+        {
+            let mut stream = connect(addr);
+            stream.write_all(b"GET /slow/x HTTP/1.1\r\nHost: localhost\r\n\r\n").unwrap();
+            stream.read_exact(&mut [0; 256]).unwrap();
+        }
+
+        for _ in 0..200 {
+            if ENDED.load(Ordering::SeqCst) != 0 {
+                break;
+            }
+            thread::sleep(Duration::from_millis(10));
+        }
+
+        // the server is still up
+        get(addr, "/ping/x", "")
+    });
+
+    assert_eq!(ENDED.load(Ordering::SeqCst), 2);
+    assert_eq!(reply.text(), "pong");
+}
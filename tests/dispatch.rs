@@ -0,0 +1,84 @@
+extern crate edge;
+
+mod common;
+
+use edge::{defer, stream, Dispatch, Edge, Router};
+
+use common::{get, with_server};
+
+use std::io::{self, Write};
+use std::sync::{Arc, Barrier};
+use std::sync::atomic::{AtomicUsize, ATOMIC_USIZE_INIT, Ordering};
+use std::thread;
+
+static NEXT_THREAD: AtomicUsize = ATOMIC_USIZE_INIT;
+
+thread_local!(static THREAD: usize = NEXT_THREAD.fetch_add(1, Ordering::SeqCst));
+
+fn app(dispatch: Dispatch) -> Edge {
+    let mut edge = Edge::new("127.0.0.1:0");
+    edge.handler_dispatch(dispatch);
+    // rewrite hooks run on the event loop, whatever the dispatch
+    edge.rewrite(|req| req.set_param("loop", THREAD.with(|thread| thread.to_string())));
+    let mut router = Router::<()>::new();
+    router.mount_service("/thread", |req, res| {
+        let handler = THREAD.with(|thread| thread.to_string());
+        res.body(if req.param("loop") == Some(&handler[..]) { "event loop" } else { "other thread" })
+    });
+    router.mount_service("/stream", |_req, _res| stream(|_app: &mut (), writer: &mut Write| -> io::Result<()> {
+        try!(writer.write_all(b"streamed "));
+        writer.write_all(b"inline")
+    }));
+    router.mount_service("/defer", |_req, _res| defer(|deferred| {
+        thread::spawn(move || deferred.send("deferred"));
+    }));
+    edge.mount("/", router);
+    edge
+}
+
+#[test]
+fn inline_handlers_run_on_event_loop_thread() {
+    let (inline, on_pool) = (
+        with_server(app(Dispatch::Inline), |addr| get(addr, "/thread/x", "")),
+        with_server(app(Dispatch::OnPool), |addr| get(addr, "/thread/x", ""))
+    );
+    assert_eq!(inline.text(), "event loop");
+    assert_eq!(on_pool.text(), "other thread");
+}
+
+#[test]
+fn blocking_handler_on_pool_does_not_hold_other_requests() {
+    let started = Arc::new(Barrier::new(2));
+    let release = Arc::new(Barrier::new(2));
+
+    let mut edge = Edge::new("127.0.0.1:0");
+    edge.handler_dispatch(Dispatch::OnPool);
+    let mut router = Router::<()>::new();
+    let (block_started, block_release) = (started.clone(), release.clone());
+    router.mount_service("/block", move |_req, res| {
+        block_started.wait();
+        block_release.wait();
+        res.body("released")
+    });
+    router.mount_service("/quick", |_req, res| res.body("quick"));
+    edge.mount("/", router);
+
+    let (quick, blocked) = with_server(edge, |addr| {
+        let blocked = thread::spawn(move || get(addr, "/block/x", ""));
+        started.wait();
+        let quick = get(addr, "/quick/x", "");
+        release.wait();
+        (quick, blocked.join().unwrap())
+    });
+    assert_eq!(quick.text(), "quick");
+    assert_eq!(blocked.text(), "released");
+}
+
+#[test]
+fn all_responses_are_sent_whatever_the_dispatch() {
+    for &dispatch in &[Dispatch::OnPool, Dispatch::Inline] {
+        let (streamed, deferred) = with_server(app(dispatch), |addr| (get(addr, "/stream/x", ""), get(addr, "/defer/x", "")));
+        assert_eq!(streamed.text(), "streamed inline");
+        assert_eq!(deferred.text(), "deferred");
+    }
+}
@@ -0,0 +1,60 @@
+extern crate edge;
+
+mod common;
+
+use edge::{Edge, Mode, Router, Status};
+
+use common::{get, send, with_server, Reply};
+
+use std::net::SocketAddr;
+
+fn app(mode: Mode) -> Edge {
+    let mut edge = Edge::new("127.0.0.1:0");
+    edge.mode(mode);
+    let mut router = Router::<()>::new();
+    router.mount_service("/failing", |_req, _res| Err((Status::ServiceUnavailable, "database password rejected").into()));
+    router.mount_service("/invalid", |_req, _res| Err((Status::UnprocessableEntity, "name is required").into()));
+    edge.mount("/", router);
+    edge
+}
+
+fn malformed(addr: SocketAddr) -> Reply {
+    send(addr, "POST /failing/x HTTP/1.1\r\nHost: localhost\r\nContent-Encoding: gzip\r\nContent-Length: 3\r\nConnection: close\r\n\r\nabc")
+}
+
+#[test]
+fn development_sends_details() {
+    let (failing, bad_request) = with_server(app(Mode::Development), |addr| (get(addr, "/failing/x", ""), malformed(addr)));
+    assert_eq!(failing.status, 503);
+    assert_eq!(failing.text(), "database password rejected");
+    assert_eq!(bad_request.status, 400);
+    assert_eq!(bad_request.text(), "invalid gzip body");
+}
+
+#[test]
+fn production_sends_correlation_id_instead_of_details() {
+    let (failing, bad_request) = with_server(app(Mode::Production), |addr| (get(addr, "/failing/x", ""), malformed(addr)));
+    for reply in &[failing, bad_request] {
+        let id = reply.header("X-Request-Id").unwrap();
+        assert!(reply.text().ends_with(&format!("(request id {})", id)), "{}", reply.text());
+        assert!(!reply.text().contains("password") && !reply.text().contains("gzip"), "{}", reply.text());
+    }
+}
+
+#[test]
+fn production_uses_valid_request_id_of_client() {
+    let (given, invalid) = with_server(app(Mode::Production), |addr| (
+        get(addr, "/failing/x", "X-Request-Id: abc-123\r\n"),
+        get(addr, "/failing/x", "X-Request-Id: not valid!\r\n")
+    ));
+    assert_eq!(given.text(), "Service Unavailable (request id abc-123)");
+    assert_eq!(given.header("X-Request-Id"), Some("abc-123"));
+    assert!(invalid.header("X-Request-Id").unwrap() != "not valid!");
+}
+
+#[test]
+fn production_sends_details_of_client_errors() {
+    let reply = with_server(app(Mode::Production), |addr| get(addr, "/invalid/x", ""));
+    assert_eq!(reply.status, 422);
+    assert_eq!(reply.text(), "name is required");
+}
@@ -0,0 +1,59 @@
+extern crate edge;
+
+mod common;
+
+use edge::{Edge, Extensions, Request, Response, Result, Router};
+
+use common::{get, with_server};
+
+#[derive(Debug, PartialEq)]
+struct CurrentUser(String);
+
+struct Visits(u32);
+
+#[derive(Default)]
+struct App;
+
+impl App {
+    fn authenticate(&mut self, req: &mut Request, _res: &mut Response) {
+        if let Some(user) = req.query("user").map(str::to_string) {
+            req.extensions_mut().insert(CurrentUser(user));
+        }
+    }
+
+    fn greet(&mut self, req: &Request, res: &mut Response) -> Result {
+        match req.extensions().get::<CurrentUser>() {
+            Some(user) => res.body(format!("hello {}", user.0)),
+            None => res.body("hello stranger")
+        }
+    }
+}
+
+fn app() -> Edge {
+    let mut edge = Edge::new("127.0.0.1:0");
+    let mut router = Router::<App>::new();
+    router.add_middleware(App::authenticate);
+    router.get("/greet", App::greet);
+    edge.mount("/", router);
+    edge
+}
+
+#[test]
+fn middleware_passes_values_to_handler() {
+    let (known, unknown) = with_server(app(), |addr| (get(addr, "/greet?user=ann", ""), get(addr, "/greet", "")));
+    assert_eq!(known.text(), "hello ann");
+    assert_eq!(unknown.text(), "hello stranger");
+}
+
+#[test]
+fn extensions_are_keyed_by_type() {
+    let mut extensions = Extensions::new();
+    assert!(extensions.insert(CurrentUser("ann".to_string())).is_none());
+    extensions.insert(Visits(1));
+    assert_eq!(extensions.insert(CurrentUser("bob".to_string())), Some(CurrentUser("ann".to_string())));
+
+    extensions.get_mut::<Visits>().unwrap().0 += 1;
+    assert_eq!(extensions.get::<Visits>().map(|visits| visits.0), Some(2));
+    assert_eq!(extensions.remove::<CurrentUser>(), Some(CurrentUser("bob".to_string())));
+    assert!(extensions.get::<CurrentUser>().is_none());
+}
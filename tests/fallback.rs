@@ -0,0 +1,45 @@
+extern crate edge;
+
+mod common;
+
+use edge::{Edge, Request, Response, Result, Router};
+
+use common::{get, send, with_server};
+
+#[derive(Default)]
+struct App;
+
+impl App {
+    fn about(&mut self, _req: &Request, res: &mut Response) -> Result {
+        res.body("about")
+    }
+
+    fn shell(&mut self, req: &Request, res: &mut Response) -> Result {
+        res.body(format!("shell {}", req.param("path").unwrap()))
+    }
+
+    fn unknown_action(&mut self, req: &Request, res: &mut Response) -> Result {
+        res.body(format!("no action {}", req.param("path").unwrap()))
+    }
+}
+
+#[test]
+fn fallbacks_handle_unmatched_requests_by_method() {
+    let mut edge = Edge::new("127.0.0.1:0");
+    let mut router = Router::<App>::new();
+    router.get("/about", App::about);
+    router.fallback("GET".parse().unwrap(), App::shell);
+    router.fallback("POST".parse().unwrap(), App::unknown_action);
+    edge.mount("/app", router);
+
+    let replies = with_server(edge, |addr| vec![
+        get(addr, "/app/about", ""),
+        get(addr, "/app/users/42", ""),
+        send(addr, "POST /app/save HTTP/1.1\r\nHost: localhost\r\nContent-Length: 0\r\nConnection: close\r\n\r\n"),
+        get(addr, "/elsewhere", "")
+    ]);
+    assert_eq!(replies[0].text(), "about");
+    assert_eq!(replies[1].text(), "shell users/42");
+    assert_eq!(replies[2].text(), "no action save");
+    assert_eq!(replies[3].status, 404);
+}
@@ -0,0 +1,29 @@
+extern crate edge;
+
+mod common;
+
+use edge::{Edge, Router};
+
+use common::{get, with_server};
+
+#[test]
+fn fetch_metadata_headers_are_parsed() {
+    let mut edge = Edge::new("127.0.0.1:0");
+    let mut router = Router::<()>::new();
+    router.mount_service("/metadata", |req, res|
+        res.body(format!("{:?} {:?} {:?} {}", req.fetch_site(), req.fetch_mode(), req.fetch_dest(), req.fetch_user())));
+    edge.mount("/", router);
+
+    let found = with_server(edge, |addr| vec![
+        get(addr, "/metadata/x", "Sec-Fetch-Site: cross-site\r\nSec-Fetch-Mode: navigate\r\nSec-Fetch-Dest: document\r\nSec-Fetch-User: ?1\r\n"),
+        get(addr, "/metadata/x", "Sec-Fetch-Site: none\r\nSec-Fetch-Mode: no-cors\r\nSec-Fetch-Dest: image\r\n"),
+        get(addr, "/metadata/x", "Sec-Fetch-Site: same-origin, cross-site\r\nSec-Fetch-Mode: teleport\r\n"),
+        get(addr, "/metadata/x", "")
+    ].iter().map(|reply| reply.text().to_string()).collect::<Vec<_>>());
+    assert_eq!(found, vec![
+        r#"Some(CrossSite) Some(Navigate) Some("document") true"#,
+        r#"Some(None) Some(NoCors) Some("image") false"#,
+        "None None None false",
+        "None None None false"
+    ]);
+}
@@ -0,0 +1,115 @@
+extern crate edge;
+
+mod common;
+
+use edge::{Action, CachePolicy, Edge, FileOptions, Router};
+
+use common::{get, head, temp_dir, with_server};
+
+fn app(name: &str) -> Edge {
+    let dir = temp_dir(name, &[("digits.txt", "0123456789")]);
+    let path = dir.join("digits.txt").to_str().unwrap().to_string();
+
+    let mut edge = Edge::new("127.0.0.1:0");
+    let mut router = Router::<()>::new();
+    let weak = path.clone();
+    let report = path.clone();
+    router.mount_service("/file", move |_req, _res| Ok(Action::SendFile(path.clone())));
+    router.mount_service("/weak", move |_req, res| {
+        res.weak_etag();
+        Ok(Action::SendFile(weak.clone()))
+    });
+    router.mount_service("/report", move |_req, res| res.serve_file(report.clone(), FileOptions::new()
+        .content_type("text/csv")
+        .attachment("résumé \"final\".csv")
+        .cache_control(CachePolicy::new().no_cache())));
+    edge.mount("/", router);
+    edge
+}
+
+#[test]
+fn byte_ranges_are_served() {
+    let replies = with_server(app("ranges"), |addr| vec![
+        get(addr, "/file/x", "Range: bytes=2-4\r\n"),
+        get(addr, "/file/x", "Range: bytes=-3\r\n"),
+        get(addr, "/file/x", "Range: bytes=7-100\r\n"),
+        get(addr, "/file/x", "Range: bytes=20-\r\n")
+    ]);
+    assert_eq!(replies[0].status, 206);
+    assert_eq!(replies[0].text(), "234");
+    assert_eq!(replies[0].header("Content-Range"), Some("bytes 2-4/10"));
+    assert_eq!(replies[1].text(), "789");
+    assert_eq!(replies[2].text(), "789");
+    assert_eq!(replies[3].status, 416);
+    assert_eq!(replies[3].header("Content-Range"), Some("bytes */10"));
+}
+
+#[test]
+fn if_range_must_match_etag() {
+    let (full, matching, stale) = with_server(app("if-range"), |addr| {
+        let full = get(addr, "/file/x", "");
+        let etag = full.header("ETag").unwrap().to_string();
+        let matching = get(addr, "/file/x", &format!("Range: bytes=0-1\r\nIf-Range: {}\r\n", etag));
+        let stale = get(addr, "/file/x", "Range: bytes=0-1\r\nIf-Range: \"stale\"\r\n");
+        (full, matching, stale)
+    });
+    assert_eq!(full.status, 200);
+    assert_eq!(full.header("Accept-Ranges"), Some("bytes"));
+    assert_eq!(matching.status, 206);
+    assert_eq!(matching.text(), "01");
+    assert_eq!(stale.status, 200);
+    assert_eq!(stale.text(), "0123456789");
+}
+
+#[test]
+fn head_request_gets_headers_of_full_file() {
+    let reply = with_server(app("head"), |addr| head(addr, "/file/x", "Range: bytes=2-4\r\n"));
+    assert_eq!(reply.status, 200);
+    assert_eq!(reply.header("Content-Length"), Some("10"));
+    assert_eq!(reply.header("Accept-Ranges"), Some("bytes"));
+    assert!(reply.body.is_empty());
+}
+
+#[test]
+fn conditional_get_is_not_modified() {
+    let replies = with_server(app("conditional"), |addr| {
+        let full = get(addr, "/file/x", "");
+        let etag = full.header("ETag").unwrap().to_string();
+        let modified = full.header("Last-Modified").unwrap().to_string();
+        vec![
+            get(addr, "/file/x", &format!("If-None-Match: {}\r\n", etag)),
+            get(addr, "/file/x", &format!("If-None-Match: \"other\", W/{}\r\n", etag)),
+            get(addr, "/file/x", "If-None-Match: \"other\"\r\n"),
+            get(addr, "/file/x", &format!("If-Modified-Since: {}\r\n", modified)),
+            get(addr, "/file/x", "If-Modified-Since: Sat, 01 Jan 2000 00:00:00 GMT\r\n")
+        ]
+    });
+    let statuses: Vec<u16> = replies.iter().map(|reply| reply.status).collect();
+    assert_eq!(statuses, vec![304, 304, 200, 304, 200]);
+}
+
+#[test]
+fn weak_etag_is_not_used_for_ranges() {
+    let (full, not_modified, range) = with_server(app("weak-etag"), |addr| {
+        let full = get(addr, "/weak/x", "");
+        let etag = full.header("ETag").unwrap().to_string();
+        let not_modified = get(addr, "/weak/x", &format!("If-None-Match: {}\r\n", etag));
+        let range = get(addr, "/weak/x", &format!("Range: bytes=0-1\r\nIf-Range: {}\r\n", etag));
+        (full, not_modified, range)
+    });
+    assert!(full.header("ETag").unwrap().starts_with("W/"));
+    assert_eq!(not_modified.status, 304);
+    assert_eq!(range.status, 200);
+    assert_eq!(range.text(), "0123456789");
+}
+
+#[test]
+fn file_is_served_with_options() {
+    let reply = with_server(app("serve-file"), |addr| get(addr, "/report/x", ""));
+    assert_eq!(reply.status, 200);
+    assert_eq!(reply.header("Content-Type"), Some("text/csv"));
+    assert_eq!(reply.header("Content-Disposition"),
+        Some("attachment; filename=\"r_sum_ _final_.csv\"; filename*=UTF-8''r%C3%A9sum%C3%A9%20%22final%22.csv"));
+    assert_eq!(reply.header("Cache-Control"), Some("no-cache"));
+    assert_eq!(reply.text(), "0123456789");
+}
@@ -0,0 +1,99 @@
+extern crate edge;
+
+mod common;
+
+use edge::{Edge, Router};
+
+use common::{get, send, with_server};
+
+fn app<F: FnOnce(&mut Edge)>(configure: F) -> Edge {
+    let mut edge = Edge::new("127.0.0.1:0");
+    configure(&mut edge);
+    let mut router = Router::<()>::new();
+    router.mount_service("/client", |req, res| {
+        res.body(format!("{:?} {}", req.real_ip(), req.scheme()))
+    });
+    router.mount_service("/host", |req, res| {
+        res.body(format!("{:?} {:?}", req.host(), req.host_with_port()))
+    });
+    router.mount_service("/forwarded", |req, res| {
+        let clients: Vec<String> = req.forwarded().iter().map(|forwarded| format!("{:?}", forwarded.client_ip())).collect();
+        res.body(clients.join(" "))
+    });
+    edge.mount("/", router);
+    edge
+}
+
+fn clients<F: FnOnce(&mut Edge)>(configure: F, headers: &'static [&'static str]) -> Vec<String> {
+    with_server(app(configure), move |addr| headers.iter().map(|header|
+        get(addr, "/client/x", header).text().to_string()
+    ).collect())
+}
+
+#[test]
+fn forwarded_elements_are_parsed() {
+    let reply = with_server(app(|_| ()), |addr| get(addr, "/forwarded/x",
+        "Forwarded: for=\"_gazonk\", For=\"[2001:db8:cafe::17]:4711\"\r\nForwarded: for=192.0.2.60;proto=http;by=203.0.113.43\r\n"));
+    assert_eq!(reply.text(), "None Some(V6(2001:db8:cafe::17)) Some(V4(192.0.2.60))");
+}
+
+#[test]
+fn forwarding_headers_are_ignored_without_trust() {
+    let found = clients(|_| (), &["Forwarded: for=192.0.2.60;proto=https\r\n", "X-Forwarded-For: 192.0.2.60\r\nX-Forwarded-Proto: https\r\n"]);
+    assert_eq!(found, vec!["None http", "None http"]);
+}
+
+#[test]
+fn trusted_forwarding_headers_identify_client() {
+    let found = clients(|edge| edge.trust_proxy(true), &[
+        "Forwarded: for=192.0.2.60;proto=https, for=198.51.100.17\r\n",
+        "X-Forwarded-For: 192.0.2.61, 198.51.100.17\r\nX-Forwarded-Proto: HTTPS\r\n",
+        "Forwarded: for=unknown\r\nX-Forwarded-For: 192.0.2.62\r\n"
+    ]);
+    assert_eq!(found, vec!["Some(V4(192.0.2.60)) https", "Some(V4(192.0.2.61)) https", "None http"]);
+}
+
+#[test]
+fn trusted_proxies_ignore_entries_added_by_client() {
+    let found = clients(|edge| edge.trusted_proxies(1), &[
+        "X-Forwarded-For: 10.0.0.1, 192.0.2.61\r\nX-Forwarded-Proto: evil, https\r\n",
+        "Forwarded: for=10.0.0.1;proto=http, for=192.0.2.60;proto=https\r\n",
+        "X-Forwarded-For: 192.0.2.62\r\n"
+    ]);
+    assert_eq!(found, vec!["Some(V4(192.0.2.61)) https", "Some(V4(192.0.2.60)) https", "Some(V4(192.0.2.62)) http"]);
+
+    let found = clients(|edge| edge.trusted_proxies(2), &["X-Forwarded-For: 10.0.0.1, 192.0.2.61, 172.16.0.1\r\n"]);
+    assert_eq!(found, vec!["Some(V4(192.0.2.61)) http"]);
+}
+
+fn hosts<F: FnOnce(&mut Edge)>(configure: F, requests: &'static [&'static str]) -> Vec<String> {
+    with_server(app(configure), move |addr| requests.iter().map(|request|
+        send(addr, request).text().to_string()
+    ).collect())
+}
+
+#[test]
+fn host_comes_from_host_header() {
+    let found = hosts(|_| (), &[
+        "GET /host/x HTTP/1.1\r\nHost: Example.com:8080\r\nConnection: close\r\n\r\n",
+        "GET /host/x HTTP/1.1\r\nHost: localhost\r\nX-Forwarded-Host: forged.com\r\nConnection: close\r\n\r\n",
+        "GET /host/x HTTP/1.0\r\n\r\n"
+    ]);
+    assert_eq!(found, vec![
+        r#"Some("Example.com") Some("Example.com:8080")"#,
+        r#"Some("localhost") Some("localhost")"#,
+        "None None"
+    ]);
+}
+
+#[test]
+fn trusted_proxies_give_host() {
+    let found = hosts(|edge| edge.trust_proxy(true), &[
+        "GET /host/x HTTP/1.1\r\nHost: backend:3000\r\nX-Forwarded-Host: example.com\r\nConnection: close\r\n\r\n",
+        "GET /host/x HTTP/1.1\r\nHost: backend:3000\r\nForwarded: host=example.org:8443\r\nConnection: close\r\n\r\n"
+    ]);
+    assert_eq!(found, vec![
+        r#"Some("example.com") Some("example.com")"#,
+        r#"Some("example.org") Some("example.org:8443")"#
+    ]);
+}
@@ -0,0 +1,70 @@
+extern crate edge;
+
+mod common;
+
+use edge::{Edge, Router};
+
+use common::{connect, read_reply, with_server, Reply};
+
+use std::io::{Read, Write};
+use std::net::SocketAddr;
+
+fn app() -> Edge {
+    let mut edge = Edge::new("127.0.0.1:0");
+    let mut router = Router::<()>::new();
+    router.mount_service("/echo", |req, res| {
+        let body = req.body().map(|body| body.to_vec()).unwrap_or(Vec::new());
+        res.body(body)
+    });
+    router.mount_service("/length", |req, res| res.body(format!("{:?}", req.content_length())));
+    edge.mount("/", router);
+    edge
+}
+
+/// Sends the given bytes on one connection, returns the first response and what was sent after it.
+fn exchange(addr: SocketAddr, request: &str) -> (Reply, Vec<u8>) {
+    let mut stream = connect(addr);
+    stream.write_all(request.as_bytes()).unwrap();
+    let reply = read_reply(&mut stream);
+    let mut rest = Vec::new();
+    let _ = stream.read_to_end(&mut rest);
+    (reply, rest)
+}
+
+#[test]
+fn chunked_with_content_length_is_rejected() {
+    // the chunked body ends early and smuggles a second request, which must never be handled
+    let request = "POST /echo/x HTTP/1.1\r\nHost: localhost\r\nTransfer-Encoding: chunked\r\nContent-Length: 40\r\n\r\n\
+        0\r\n\r\nGET /echo/smuggled HTTP/1.1\r\nHost: localhost\r\n\r\n";
+    let (reply, rest) = with_server(app(), move |addr| exchange(addr, request));
+    assert_eq!(reply.status, 400);
+    assert!(rest.is_empty(), "unexpected data after rejection: {:?}", String::from_utf8_lossy(&rest));
+}
+
+#[test]
+fn chunked_must_be_last_encoding() {
+    let request = "POST /echo/x HTTP/1.1\r\nHost: localhost\r\nTransfer-Encoding: chunked, gzip\r\n\r\n0\r\n\r\n";
+    let (reply, rest) = with_server(app(), move |addr| exchange(addr, request));
+    assert_eq!(reply.status, 400);
+    assert!(rest.is_empty());
+}
+
+#[test]
+fn chunked_body_is_read() {
+    let request = "POST /echo/x HTTP/1.1\r\nHost: localhost\r\nTransfer-Encoding: chunked\r\nConnection: close\r\n\r\n\
+        5\r\nhello\r\n0\r\n\r\n";
+    let (reply, _) = with_server(app(), move |addr| exchange(addr, request));
+    assert_eq!(reply.status, 200);
+    assert_eq!(reply.text(), "hello");
+}
+
+#[test]
+fn declared_length_is_known_unless_chunked() {
+    let sized = "POST /length/x HTTP/1.1\r\nHost: localhost\r\nContent-Length: 5\r\nConnection: close\r\n\r\nhello";
+    let chunked = "POST /length/x HTTP/1.1\r\nHost: localhost\r\nTransfer-Encoding: chunked\r\nConnection: close\r\n\r\n\
+        5\r\nhello\r\n0\r\n\r\n";
+    let missing = "GET /length/x HTTP/1.1\r\nHost: localhost\r\nConnection: close\r\n\r\n";
+    let lengths = with_server(app(), move |addr|
+        [sized, chunked, missing].iter().map(|request| exchange(addr, request).0.text().to_string()).collect::<Vec<_>>());
+    assert_eq!(lengths, vec!["Some(5)", "None", "None"]);
+}
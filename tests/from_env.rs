@@ -0,0 +1,22 @@
+extern crate edge;
+
+use edge::Edge;
+
+use std::env;
+use std::panic;
+
+// a single test, since the environment is shared by the threads of the test process
+#[test]
+fn port_comes_from_environment() {
+    env::set_var("PORT", " 0 ");
+    let addr = Edge::from_env("127.0.0.1:0").bind().unwrap();
+    assert!(addr.ip().is_unspecified());
+
+    env::remove_var("PORT");
+    let addr = Edge::from_env("127.0.0.1:0").bind().unwrap();
+    assert_eq!(addr.ip().to_string(), "127.0.0.1");
+
+    env::set_var("PORT", "http");
+    assert!(panic::catch_unwind(|| Edge::from_env("127.0.0.1:0")).is_err());
+    env::remove_var("PORT");
+}
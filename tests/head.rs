@@ -0,0 +1,79 @@
+extern crate edge;
+
+mod common;
+
+use edge::{Edge, Request, Response, Result, Router};
+
+use common::{head, send, with_server};
+
+#[derive(Default)]
+struct App;
+
+impl App {
+    fn page(&mut self, _req: &Request, res: &mut Response) -> Result {
+        res.header_raw("X-Page", "yes").body("hello")
+    }
+
+    fn head(&mut self, _req: &Request, res: &mut Response) -> Result {
+        res.header_raw("X-Head", "yes").body("")
+    }
+
+    fn track(&mut self, _req: &mut Request, res: &mut Response) {
+        res.header_raw("X-Tracked", "yes");
+    }
+}
+
+fn app(auto_head: bool) -> Edge {
+    let mut edge = Edge::new("127.0.0.1:0");
+    edge.auto_head(auto_head);
+    let mut router = Router::<App>::new();
+    router.add_middleware(App::track);
+    router.get("/page", App::page);
+    router.get("/both", App::page);
+    router.head("/both", App::head);
+    edge.mount("/", router);
+    edge
+}
+
+#[test]
+fn head_is_handled_by_get_route() {
+    let (page, both) = with_server(app(true), |addr| (head(addr, "/page", ""), head(addr, "/both", "")));
+    assert_eq!(page.status, 200);
+    assert_eq!(page.header("X-Page"), Some("yes"));
+    assert_eq!(page.header("Content-Length"), Some("5"));
+    assert!(page.body.is_empty());
+
+    // an explicit HEAD route takes precedence
+    assert_eq!(both.header("X-Head"), Some("yes"));
+    assert_eq!(both.header("X-Page"), None);
+}
+
+#[test]
+fn head_needs_explicit_route_without_auto_head() {
+    let (page, both) = with_server(app(false), |addr| (head(addr, "/page", ""), head(addr, "/both", "")));
+    assert_eq!(page.status, 405);
+    assert_eq!(page.header("Allow"), Some("GET"));
+    assert_eq!(both.status, 200);
+}
+
+#[test]
+fn other_methods_are_not_allowed() {
+    let reply = with_server(app(true), |addr|
+        send(addr, "POST /page HTTP/1.1\r\nHost: localhost\r\nContent-Length: 0\r\nConnection: close\r\n\r\n"));
+    assert_eq!(reply.status, 405);
+    assert_eq!(reply.header("Allow"), Some("GET, HEAD"));
+}
+
+#[test]
+fn middleware_can_be_skipped_for_automatic_head() {
+    let mut edge = app(true);
+    edge.middleware_on_auto_head(false);
+    let (page, both) = with_server(edge, |addr| (head(addr, "/page", ""), head(addr, "/both", "")));
+    assert_eq!(page.header("X-Page"), Some("yes"));
+    assert_eq!(page.header("X-Tracked"), None);
+    // explicit HEAD routes still run middleware
+    assert_eq!(both.header("X-Tracked"), Some("yes"));
+
+    let page = with_server(app(true), |addr| head(addr, "/page", ""));
+    assert_eq!(page.header("X-Tracked"), Some("yes"));
+}
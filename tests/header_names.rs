@@ -0,0 +1,34 @@
+extern crate edge;
+
+mod common;
+
+use edge::{Edge, Router};
+
+use common::{get, with_server};
+
+fn app(verbatim: bool) -> Edge {
+    let mut edge = Edge::new("127.0.0.1:0");
+    edge.verbatim_header_names(verbatim);
+    let mut router = Router::<()>::new();
+    router.mount_service("/headers", |_req, res| {
+        res.header_raw("x-custom-id", "1").header_raw("X-UPPER", "2").header_raw("www-authenticate", "Basic");
+        res.body("headers")
+    });
+    edge.mount("/", router);
+    edge
+}
+
+#[test]
+fn lowercase_names_are_canonicalized() {
+    let reply = with_server(app(false), |addr| get(addr, "/headers/x", ""));
+    assert!(reply.head.contains("\r\nX-Custom-Id: 1\r\n"), "{}", reply.head);
+    assert!(reply.head.contains("\r\nX-UPPER: 2\r\n"), "{}", reply.head);
+    assert!(reply.head.contains("\r\nWww-Authenticate: Basic\r\n"), "{}", reply.head);
+}
+
+#[test]
+fn verbatim_names_are_kept() {
+    let reply = with_server(app(true), |addr| get(addr, "/headers/x", ""));
+    assert!(reply.head.contains("\r\nx-custom-id: 1\r\n"), "{}", reply.head);
+    assert!(reply.head.contains("\r\nX-UPPER: 2\r\n"), "{}", reply.head);
+}
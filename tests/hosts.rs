@@ -0,0 +1,47 @@
+extern crate edge;
+
+mod common;
+
+use edge::{Edge, Request, Response, Result, Router};
+
+use common::{send, with_server};
+
+fn api(_req: &Request, res: &mut Response) -> Result {
+    res.body("api")
+}
+
+fn site(_req: &Request, res: &mut Response) -> Result {
+    res.body("site")
+}
+
+fn admin(_req: &Request, res: &mut Response) -> Result {
+    res.body("admin")
+}
+
+fn app() -> Edge {
+    let mut edge = Edge::new("127.0.0.1:0");
+    let mut router = Router::<()>::new();
+    router.host("API.example.com", |r| r.get_static("/", api));
+    router.get_static("/", site);
+    router.host("admin.example.com", |r| r.get_static("/admin", admin));
+    edge.mount("/", router);
+    edge
+}
+
+fn get_host(addr: ::std::net::SocketAddr, host: &str, path: &str) -> common::Reply {
+    send(addr, &format!("GET {} HTTP/1.1\r\nHost: {}\r\nConnection: close\r\n\r\n", path, host))
+}
+
+#[test]
+fn host_routes_are_tried_first() {
+    let replies = with_server(app(), |addr| vec![
+        get_host(addr, "api.example.com:8080", "/"),
+        get_host(addr, "www.example.com", "/"),
+        get_host(addr, "admin.example.com", "/admin"),
+        get_host(addr, "www.example.com", "/admin")
+    ]);
+    assert_eq!(replies[0].text(), "api");
+    assert_eq!(replies[1].text(), "site");
+    assert_eq!(replies[2].text(), "admin");
+    assert_eq!(replies[3].status, 404);
+}
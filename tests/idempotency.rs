@@ -0,0 +1,119 @@
+extern crate edge;
+
+mod common;
+
+use edge::{Edge, Error, Request, Response, Result, Router, Status};
+
+use std::result;
+use std::sync::atomic::{AtomicUsize, ATOMIC_USIZE_INIT, Ordering};
+use std::time::Duration;
+
+use common::{send, with_server, Reply};
+
+static CALLS: AtomicUsize = ATOMIC_USIZE_INIT;
+
+struct User(String);
+
+#[derive(Default)]
+struct App;
+
+impl App {
+    /// Identifies the caller with the Authorization header, rejects requests without it.
+    fn authenticate(&mut self, req: &mut Request, _res: &mut Response) -> result::Result<(), Error> {
+        let user = match req.headers().get_raw("Authorization") {
+            Some(values) => String::from_utf8(values[0].clone()).unwrap(),
+            None => return Err(Status::Unauthorized.into())
+        };
+        req.extensions_mut().insert(User(user));
+        Ok(())
+    }
+
+    fn create(&mut self, req: &Request, res: &mut Response) -> Result {
+        let call = CALLS.fetch_add(1, Ordering::SeqCst);
+        res.header_raw("Set-Cookie", "session=abc");
+        if req.headers().get_raw("X-Fail").is_some() {
+            return Err(Status::InternalServerError.into());
+        }
+        res.status(Status::Created).body(format!("call {}", call))
+    }
+}
+
+fn app() -> Edge {
+    let mut edge = Edge::new("127.0.0.1:0");
+    edge.idempotency(100, Duration::from_secs(60), |req| {
+        req.extensions().get::<User>().map(|user| user.0.clone())
+    });
+
+    let mut router = Router::<App>::new();
+    router.add_fallible_middleware(App::authenticate);
+    router.post("/items", App::create);
+    router.post_json("/json", App::create);
+    edge.mount("/", router);
+    edge
+}
+
+fn post(addr: ::std::net::SocketAddr, path: &str, headers: &str) -> Reply {
+    send(addr, &format!("POST {} HTTP/1.1\r\nHost: localhost\r\n{}Content-Length: 2\r\nConnection: close\r\n\r\n{{}}", path, headers))
+}
+
+#[test]
+fn replays_for_same_caller() {
+    let (first, second) = with_server(app(), |addr| {
+        let headers = "Authorization: alice\r\nIdempotency-Key: replay\r\n";
+        (post(addr, "/items", headers), post(addr, "/items", headers))
+    });
+    assert_eq!(first.status, 201);
+    assert_eq!(second.status, 201);
+    assert_eq!(first.text(), second.text());
+}
+
+#[test]
+fn does_not_replay_for_other_caller() {
+    let (alice, bob) = with_server(app(), |addr| {
+        (post(addr, "/items", "Authorization: alice\r\nIdempotency-Key: shared\r\n"),
+         post(addr, "/items", "Authorization: bob\r\nIdempotency-Key: shared\r\n"))
+    });
+    assert_eq!(bob.status, 201);
+    assert!(alice.text() != bob.text());
+}
+
+#[test]
+fn middleware_runs_before_replay() {
+    let (first, anonymous) = with_server(app(), |addr| {
+        (post(addr, "/items", "Authorization: carol\r\nIdempotency-Key: auth\r\n"),
+         post(addr, "/items", "Idempotency-Key: auth\r\n"))
+    });
+    assert_eq!(first.status, 201);
+    assert_eq!(anonymous.status, 401);
+}
+
+#[test]
+fn media_type_checked_before_replay() {
+    let (first, second) = with_server(app(), |addr| {
+        (post(addr, "/json", "Authorization: dave\r\nContent-Type: application/json\r\nIdempotency-Key: media\r\n"),
+         post(addr, "/json", "Authorization: dave\r\nContent-Type: text/plain\r\nIdempotency-Key: media\r\n"))
+    });
+    assert_eq!(first.status, 201);
+    assert_eq!(second.status, 415);
+}
+
+#[test]
+fn errors_are_not_recorded() {
+    let (failed, retried) = with_server(app(), |addr| {
+        (post(addr, "/items", "Authorization: erin\r\nIdempotency-Key: retry\r\nX-Fail: 1\r\n"),
+         post(addr, "/items", "Authorization: erin\r\nIdempotency-Key: retry\r\n"))
+    });
+    assert_eq!(failed.status, 500);
+    assert_eq!(retried.status, 201);
+}
+
+#[test]
+fn cookies_are_not_replayed() {
+    let (first, second) = with_server(app(), |addr| {
+        let headers = "Authorization: frank\r\nIdempotency-Key: cookie\r\n";
+        (post(addr, "/items", headers), post(addr, "/items", headers))
+    });
+    assert_eq!(first.header("Set-Cookie"), Some("session=abc"));
+    assert_eq!(first.text(), second.text());
+    assert_eq!(second.header("Set-Cookie"), None);
+}
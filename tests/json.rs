@@ -0,0 +1,58 @@
+#[macro_use]
+extern crate edge;
+
+mod common;
+
+use edge::{json, Edge, Router};
+
+use common::{get, with_server};
+
+use std::collections::BTreeMap;
+
+fn app(decimals: Option<usize>) -> Edge {
+    let mut edge = Edge::new("127.0.0.1:0");
+    if let Some(decimals) = decimals {
+        edge.json_float_decimals(decimals);
+    }
+    let mut router = Router::<()>::new();
+    router.mount_service("/json", |_req, _res| {
+        let mut map = BTreeMap::new();
+        map.insert("name".to_string(), json::Value::String("edge".to_string()));
+        ok!(json::Value::Object(map))
+    });
+    router.mount_service("/ld", |_req, res| {
+        res.content_type("application/ld+json");
+        ok!(json::Value::Bool(true))
+    });
+    router.mount_service("/floats", |_req, _res| {
+        let mut point = BTreeMap::new();
+        point.insert("x".to_string(), json::Value::F64(0.5));
+        ok!(json::Value::Array(vec![json::Value::F64(1.0), json::Value::U64(3), json::Value::F64(::std::f64::NAN), json::Value::Object(point)]))
+    });
+    edge.mount("/", router);
+    edge
+}
+
+#[test]
+fn json_has_canonical_content_type() {
+    let reply = with_server(app(None), |addr| get(addr, "/json/x", ""));
+    assert_eq!(reply.header("Content-Type"), Some("application/json"));
+    assert_eq!(reply.text(), r#"{"name":"edge"}"#);
+}
+
+#[test]
+fn json_keeps_explicit_content_type() {
+    let reply = with_server(app(None), |addr| get(addr, "/ld/x", ""));
+    assert_eq!(reply.header("Content-Type"), Some("application/ld+json"));
+    assert_eq!(reply.text(), "true");
+}
+
+#[test]
+fn floats_are_written_with_fixed_decimals() {
+    let shortest = with_server(app(None), |addr| get(addr, "/floats/x", ""));
+    let fixed = with_server(app(Some(2)), |addr| get(addr, "/floats/x", ""));
+
+    assert_eq!(fixed.text(), r#"[1.00,3,null,{"x":0.50}]"#);
+    assert!(shortest.text().starts_with("[1"), "{}", shortest.text());
+    assert!(!shortest.text().contains("1.00"), "{}", shortest.text());
+}
@@ -0,0 +1,71 @@
+extern crate edge;
+
+mod common;
+
+use edge::{Edge, Router};
+
+use common::{connect, is_closed, read_reply, with_server, Reply};
+
+use std::io::Write;
+use std::net::SocketAddr;
+
+fn app() -> Edge {
+    let mut edge = Edge::new("127.0.0.1:0");
+    let mut router = Router::<()>::new();
+    router.mount_service("/hello", |_req, res| res.body("hello"));
+    router.mount_service("/bye", |_req, res| {
+        res.close_connection();
+        res.body("bye")
+    });
+    edge.mount("/", router);
+    edge
+}
+
+/// Sends the given requests one after the other on the same connection, and returns
+/// the responses along with whether the connection was closed after the last one.
+fn exchange(addr: SocketAddr, requests: &[&str]) -> (Vec<Reply>, bool) {
+    let mut stream = connect(addr);
+    let replies = requests.iter().map(|request| {
+        stream.write_all(request.as_bytes()).unwrap();
+        read_reply(&mut stream)
+    }).collect();
+    (replies, is_closed(&mut stream))
+}
+
+#[test]
+fn http11_keeps_connection_alive() {
+    let (replies, closed) = with_server(app(), |addr| exchange(addr, &[
+        "GET /hello/1 HTTP/1.1\r\nHost: localhost\r\n\r\n",
+        "GET /hello/2 HTTP/1.1\r\nHost: localhost\r\nConnection: close\r\n\r\n"
+    ]));
+    assert_eq!(replies[0].text(), "hello");
+    assert_eq!(replies[1].text(), "hello");
+    assert_eq!(replies[1].header("Connection"), Some("close"));
+    assert!(closed);
+}
+
+#[test]
+fn http10_closes_connection_by_default() {
+    let (replies, closed) = with_server(app(), |addr| exchange(addr, &["GET /hello/1 HTTP/1.0\r\n\r\n"]));
+    assert_eq!(replies[0].text(), "hello");
+    assert_eq!(replies[0].header("Connection"), Some("close"));
+    assert!(closed);
+}
+
+#[test]
+fn http10_keep_alive_is_acknowledged() {
+    let (replies, closed) = with_server(app(), |addr| exchange(addr, &[
+        "GET /hello/1 HTTP/1.0\r\nConnection: keep-alive\r\n\r\n",
+        "GET /hello/2 HTTP/1.0\r\n\r\n"
+    ]));
+    assert_eq!(replies[0].header("Connection"), Some("keep-alive"));
+    assert_eq!(replies[1].text(), "hello");
+    assert!(closed);
+}
+
+#[test]
+fn handler_can_close_connection() {
+    let (replies, closed) = with_server(app(), |addr| exchange(addr, &["GET /bye/x HTTP/1.1\r\nHost: localhost\r\n\r\n"]));
+    assert_eq!(replies[0].text(), "bye");
+    assert!(closed);
+}
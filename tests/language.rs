@@ -0,0 +1,36 @@
+extern crate edge;
+
+mod common;
+
+use edge::{Edge, Router};
+
+use common::{get, with_server};
+
+fn app() -> Edge {
+    let mut edge = Edge::new("127.0.0.1:0");
+    let mut router = Router::<()>::new();
+    router.mount_service("/lang", |req, res| {
+        res.body(format!("{:?}", req.preferred_language(&["en", "fr", "de-CH"])))
+    });
+    edge.mount("/", router);
+    edge
+}
+
+fn languages(headers: &'static [&'static str]) -> Vec<String> {
+    with_server(app(), move |addr| headers.iter().map(|header|
+        get(addr, "/lang/x", header).text().to_string()
+    ).collect())
+}
+
+#[test]
+fn preferred_language_follows_quality() {
+    let found = languages(&[
+        "Accept-Language: fr;q=0.5, en;q=0.8\r\n",
+        "Accept-Language: de, fr;q=0.1\r\n",
+        "Accept-Language: EN-us\r\n",
+        "Accept-Language: it, *;q=0.1\r\n",
+        "Accept-Language: it, en;q=0\r\n",
+        ""
+    ]);
+    assert_eq!(found, vec![r#"Some("en")"#, r#"Some("de-CH")"#, r#"Some("en")"#, r#"Some("en")"#, "None", "None"]);
+}
@@ -0,0 +1,44 @@
+extern crate edge;
+
+mod common;
+
+use edge::{Edge, Router};
+
+use common::get;
+
+use std::net::TcpListener;
+use std::thread;
+
+#[test]
+fn server_runs_on_given_listener() {
+    // the address given to new is not used
+    let mut edge = Edge::new("127.0.0.1:1");
+    let mut router = Router::<()>::new();
+    router.mount_service("/hello", |_req, res| res.body("hello"));
+    edge.mount("/", router);
+
+    let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+    let addr = listener.local_addr().unwrap();
+    let shutdown = edge.shutdown_handle();
+    let client = thread::spawn(move || {
+        let reply = get(addr, "/hello/x", "");
+        shutdown.shutdown();
+        reply
+    });
+
+    edge.start_with_listener(listener).unwrap();
+    assert_eq!(client.join().unwrap().text(), "hello");
+}
+
+#[test]
+fn bind_gives_port_chosen_by_system() {
+    let mut edge = Edge::new("127.0.0.1:0");
+    let addr = edge.bind().unwrap();
+    assert!(addr.port() != 0);
+
+    // the port is taken until the application is dropped
+    let mut other = Edge::new(&format!("127.0.0.1:{}", addr.port()));
+    assert!(other.bind().is_err());
+    drop(edge);
+    assert!(TcpListener::bind(addr).is_ok());
+}
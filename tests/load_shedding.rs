@@ -0,0 +1,74 @@
+extern crate edge;
+
+mod common;
+
+use edge::{Edge, Request, Response, Result, Router};
+
+use common::{get, with_server};
+
+use std::sync::{Arc, Barrier};
+use std::thread;
+
+fn health(_app: &mut (), _req: &Request, res: &mut Response) -> Result {
+    res.body("healthy")
+}
+
+/// `/slow` waits on `started` once it runs, then on `release` before it replies.
+fn app(started: &Arc<Barrier>, release: &Arc<Barrier>) -> Edge {
+    let mut edge = Edge::new("127.0.0.1:0");
+    edge.max_concurrent_requests(1);
+    let mut router = Router::<()>::new();
+    let (started, release) = (started.clone(), release.clone());
+    router.mount_service("/slow", move |_req, res| {
+        started.wait();
+        release.wait();
+        res.body("slow")
+    });
+    router.mount_service("/fast", |_req, res| res.body("fast"));
+    edge.mount("/", router);
+
+    let mut checks = Router::<()>::new();
+    checks.get("/health", health);
+    checks.get("/ready", health);
+    checks.prioritize("GET".parse().unwrap(), "/health");
+    edge.mount("/checks", checks);
+    edge
+}
+
+#[test]
+fn requests_over_limit_are_shed() {
+    let (started, release) = (Arc::new(Barrier::new(2)), Arc::new(Barrier::new(2)));
+    let (slow, shed, after) = with_server(app(&started, &release), move |addr| {
+        let slow = thread::spawn(move || get(addr, "/slow/x", ""));
+        started.wait();
+        let shed = get(addr, "/fast/x", "");
+        release.wait();
+        let slow = slow.join().unwrap();
+        (slow, shed, get(addr, "/fast/x", ""))
+    });
+    assert_eq!(slow.text(), "slow");
+    assert_eq!(shed.status, 503);
+    assert_eq!(shed.header("Retry-After"), Some("1"));
+    assert_eq!(after.text(), "fast");
+}
+
+#[test]
+fn priority_routes_are_served_when_shedding() {
+    let (started, release) = (Arc::new(Barrier::new(2)), Arc::new(Barrier::new(2)));
+    let (health, ready) = with_server(app(&started, &release), move |addr| {
+        let slow = thread::spawn(move || get(addr, "/slow/x", ""));
+        started.wait();
+        let replies = (get(addr, "/checks/health", ""), get(addr, "/checks/ready", ""));
+        release.wait();
+        slow.join().unwrap();
+        replies
+    });
+    assert_eq!(health.text(), "healthy");
+    assert_eq!(ready.status, 503);
+}
+
+#[test]
+#[should_panic(expected = "could not prioritize route GET /missing: no such route")]
+fn prioritize_needs_existing_route() {
+    Router::<()>::new().prioritize("GET".parse().unwrap(), "/missing");
+}
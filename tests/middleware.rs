@@ -0,0 +1,81 @@
+extern crate edge;
+
+mod common;
+
+use edge::{Edge, Error, Request, Response, Result, Router, Status};
+
+use common::{get, send, with_server};
+
+use std::sync::atomic::{AtomicUsize, ATOMIC_USIZE_INIT, Ordering};
+
+/// Number of times the middleware and handler after authentication ran.
+static AUTHENTICATED_CALLS: AtomicUsize = ATOMIC_USIZE_INIT;
+
+#[derive(Default)]
+struct App {
+    marked: bool
+}
+
+impl App {
+    fn mark(&mut self, _req: &mut Request, _res: &mut Response) {
+        self.marked = true;
+    }
+
+    fn authenticate(&mut self, req: &mut Request, _res: &mut Response) -> ::std::result::Result<(), Error> {
+        match req.headers().get_raw("Authorization") {
+            Some(_) => Ok(()),
+            None => Err((Status::Unauthorized, "missing credentials").into())
+        }
+    }
+
+    fn after_authentication(&mut self, _req: &mut Request, _res: &mut Response) {
+        AUTHENTICATED_CALLS.fetch_add(1, Ordering::SeqCst);
+    }
+
+    fn secret(&mut self, _req: &Request, res: &mut Response) -> Result {
+        AUTHENTICATED_CALLS.fetch_add(1, Ordering::SeqCst);
+        res.body("secret")
+    }
+
+    fn show(&mut self, _req: &Request, res: &mut Response) -> Result {
+        res.body(format!("marked: {}", self.marked))
+    }
+}
+
+fn app() -> Edge {
+    let mut edge = Edge::new("127.0.0.1:0");
+    let mut router = Router::<App>::new();
+    router.add_middleware_if(|req| !req.method().safe(), App::mark);
+    router.get("/item", App::show);
+    router.post("/item", App::show);
+    edge.mount("/", router);
+
+    let mut secure = Router::<App>::new();
+    secure.add_fallible_middleware(App::authenticate);
+    secure.add_middleware(App::after_authentication);
+    secure.get("/data", App::secret);
+    edge.mount("/secure", secure);
+    edge
+}
+
+#[test]
+fn conditional_middleware_runs_for_matching_requests() {
+    let (read, write) = with_server(app(), |addr| (
+        get(addr, "/item", ""),
+        send(addr, "POST /item HTTP/1.1\r\nHost: localhost\r\nContent-Length: 0\r\nConnection: close\r\n\r\n")
+    ));
+    assert_eq!(read.text(), "marked: false");
+    assert_eq!(write.text(), "marked: true");
+}
+
+#[test]
+fn failing_middleware_skips_the_rest() {
+    let (denied, allowed) = with_server(app(), |addr| (
+        get(addr, "/secure/data", ""),
+        get(addr, "/secure/data", "Authorization: Bearer 42\r\n")
+    ));
+    assert_eq!(denied.status, 401);
+    assert_eq!(denied.text(), "missing credentials");
+    assert_eq!(allowed.text(), "secret");
+    assert_eq!(AUTHENTICATED_CALLS.load(Ordering::SeqCst), 2);
+}
@@ -0,0 +1,48 @@
+extern crate edge;
+
+mod common;
+
+use edge::{Edge, Request, Response, Result, Router};
+
+use common::{get, send, with_server};
+
+#[derive(Default)]
+struct App;
+
+impl App {
+    fn hello(&mut self, _req: &Request, res: &mut Response) -> Result {
+        res.body("hello")
+    }
+}
+
+fn app() -> Edge {
+    let mut edge = Edge::new("127.0.0.1:0");
+    let mut router = Router::<App>::new();
+    router.get("/proxy/hello", App::hello);
+    router.mount_service("/proxy", |req, res| {
+        res.body(format!("{} {}", req.method(), req.param("path").unwrap()))
+    });
+    edge.mount("/", router);
+    edge
+}
+
+#[test]
+fn service_handles_all_sub_paths() {
+    let (one, nested) = with_server(app(), |addr| (get(addr, "/proxy/anything", ""), get(addr, "/proxy/a/b/c", "")));
+    assert_eq!(one.status, 200);
+    assert_eq!(one.text(), "GET anything");
+    assert_eq!(nested.text(), "GET a/b/c");
+}
+
+#[test]
+fn service_handles_any_method() {
+    let reply = with_server(app(), |addr|
+        send(addr, "DELETE /proxy/x HTTP/1.1\r\nHost: localhost\r\nContent-Length: 0\r\nConnection: close\r\n\r\n"));
+    assert_eq!(reply.text(), "DELETE x");
+}
+
+#[test]
+fn routes_take_precedence_over_service() {
+    let reply = with_server(app(), |addr| get(addr, "/proxy/hello", ""));
+    assert_eq!(reply.text(), "hello");
+}
@@ -0,0 +1,53 @@
+extern crate edge;
+
+mod common;
+
+use edge::{Edge, Router, Status};
+
+use common::{send, with_server, Reply};
+
+use std::io::Read;
+use std::net::SocketAddr;
+
+fn app() -> Edge {
+    let mut edge = Edge::new("127.0.0.1:0");
+    let mut router = Router::<()>::new();
+    router.mount_service("/upload", |req, res| {
+        let mut multipart = try!(req.multipart());
+        let mut found = Vec::new();
+        while let Some(mut part) = try!(multipart.next_part().map_err(|e| (Status::BadRequest, e.to_string()))) {
+            let mut content = String::new();
+            try!(part.read_to_string(&mut content).map_err(|e| (Status::BadRequest, e.to_string())));
+            found.push(format!("{:?} {:?} {:?} {}", part.name(), part.filename(), part.content_type(), content));
+        }
+        res.body(found.join("\n"))
+    });
+    edge.mount("/", router);
+    edge
+}
+
+fn post(addr: SocketAddr, content_type: &str, body: &str) -> Reply {
+    send(addr, &format!("POST /upload/x HTTP/1.1\r\nHost: localhost\r\nContent-Type: {}\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{}",
+        content_type, body.len(), body))
+}
+
+#[test]
+fn parts_are_read_in_order() {
+    let body = "--XYZ\r\nContent-Disposition: form-data; name=\"title\"\r\n\r\nhello\r\n\
+        --XYZ\r\nContent-Disposition: form-data; name=\"file\"; filename=\"a.txt\"\r\nContent-Type: text/plain\r\n\r\nline 1\r\nline 2\r\n\
+        --XYZ--\r\n";
+    let reply = with_server(app(), move |addr| post(addr, "multipart/form-data; boundary=XYZ", body));
+    assert_eq!(reply.status, 200);
+    assert_eq!(reply.text(), "Some(\"title\") None None hello\n\
+        Some(\"file\") Some(\"a.txt\") Some(\"text/plain\") line 1\r\nline 2");
+}
+
+#[test]
+fn other_bodies_are_rejected() {
+    let (other, no_boundary) = with_server(app(), |addr| (
+        post(addr, "text/plain", "hello"),
+        post(addr, "multipart/form-data", "--XYZ--\r\n")
+    ));
+    assert_eq!(other.status, 415);
+    assert_eq!(no_boundary.status, 400);
+}
@@ -0,0 +1,40 @@
+extern crate edge;
+
+mod common;
+
+use edge::{Edge, Router};
+use edge::header::{EntityTag, HttpDate};
+
+use common::{get, with_server};
+
+use std::sync::atomic::{AtomicUsize, ATOMIC_USIZE_INIT, Ordering};
+
+/// Number of times the body of the article was computed.
+static RENDERED: AtomicUsize = ATOMIC_USIZE_INIT;
+
+#[test]
+fn body_is_skipped_when_client_copy_is_valid() {
+    let mut edge = Edge::new("127.0.0.1:0");
+    let mut router = Router::<()>::new();
+    router.mount_service("/article", |req, res| {
+        let modified: HttpDate = "Sat, 01 Jan 2000 12:00:00 GMT".parse().unwrap();
+        try!(res.not_modified_if(req, Some(EntityTag::strong("v42".to_string())), Some(modified)));
+        RENDERED.fetch_add(1, Ordering::SeqCst);
+        res.body("article")
+    });
+    edge.mount("/", router);
+
+    let replies = with_server(edge, |addr| vec![
+        get(addr, "/article/x", ""),
+        get(addr, "/article/x", "If-None-Match: \"v42\"\r\n"),
+        get(addr, "/article/x", "If-Modified-Since: Sat, 01 Jan 2000 12:00:00 GMT\r\n"),
+        get(addr, "/article/x", "If-None-Match: \"v41\"\r\n")
+    ]);
+    let statuses: Vec<u16> = replies.iter().map(|reply| reply.status).collect();
+    assert_eq!(statuses, vec![200, 304, 304, 200]);
+    assert_eq!(replies[0].header("ETag"), Some("\"v42\""));
+    assert_eq!(replies[0].header("Last-Modified"), Some("Sat, 01 Jan 2000 12:00:00 GMT"));
+    assert_eq!(replies[1].header("ETag"), Some("\"v42\""));
+    assert!(replies[1].body.is_empty());
+    assert_eq!(RENDERED.load(Ordering::SeqCst), 2);
+}
@@ -0,0 +1,37 @@
+extern crate edge;
+
+use edge::{json, Edge, Request, Response, Result, Router};
+
+#[derive(Default)]
+struct App;
+
+impl App {
+    fn handle(&mut self, _req: &Request, res: &mut Response) -> Result {
+        res.body("ok")
+    }
+}
+
+fn app() -> Edge {
+    let mut edge = Edge::new("127.0.0.1:0");
+    let mut router = Router::<App>::new();
+    router.get("/users", App::handle);
+    router.post("/users", App::handle);
+    router.get("/users/:id", App::handle);
+    router.mount_service("/static", |_req, res| res.body("file"));
+    edge.mount("/api", router);
+    edge
+}
+
+#[test]
+fn routes_are_described_by_path_and_method() {
+    let document = app().openapi("Users", "1.0");
+    assert_eq!(json::to_string(document.find("info").unwrap()).unwrap(), r#"{"title":"Users","version":"1.0"}"#);
+
+    let paths = document.find("paths").unwrap().as_object().unwrap();
+    assert_eq!(paths.keys().collect::<Vec<_>>(), vec!["/api/users", "/api/users/{id}"]);
+    assert_eq!(paths["/api/users"].as_object().unwrap().keys().collect::<Vec<_>>(), vec!["get", "post"]);
+
+    let parameters = paths["/api/users/{id}"].find_path(&["get", "parameters"]).unwrap();
+    assert_eq!(json::to_string(parameters).unwrap(),
+        r#"[{"in":"path","name":"id","required":true,"schema":{"type":"string"}}]"#);
+}
@@ -0,0 +1,69 @@
+extern crate edge;
+
+mod common;
+
+use edge::{Edge, Request, Response, Result, Router};
+
+use common::{send, with_server, Reply};
+
+use std::net::SocketAddr;
+
+#[derive(Default)]
+struct App;
+
+impl App {
+    fn handle(&mut self, _req: &Request, res: &mut Response) -> Result {
+        res.body("handled")
+    }
+
+    fn authenticate(&mut self, _req: &mut Request, res: &mut Response) {
+        res.header_raw("X-Authenticated", "yes");
+    }
+}
+
+fn app(auto_options: bool) -> Edge {
+    let mut edge = Edge::new("127.0.0.1:0");
+    edge.auto_options(auto_options);
+    let mut router = Router::<App>::new();
+    router.add_middleware(App::authenticate);
+    router.get("/items", App::handle);
+    router.post("/items", App::handle);
+    router.delete("/items/:id", App::handle);
+    edge.mount("/", router);
+    edge
+}
+
+fn options(addr: SocketAddr, target: &str) -> Reply {
+    send(addr, &format!("OPTIONS {} HTTP/1.1\r\nHost: localhost\r\nConnection: close\r\n\r\n", target))
+}
+
+/// Returns the methods of the Allow header, sorted.
+fn allowed(reply: &Reply) -> Vec<String> {
+    let mut methods: Vec<String> = reply.header("Allow").unwrap_or("").split(',').map(|method| method.trim().to_string()).collect();
+    methods.sort();
+    methods
+}
+
+#[test]
+fn options_star_lists_server_methods() {
+    let reply = with_server(app(true), |addr| options(addr, "*"));
+    assert_eq!(reply.status, 200);
+    assert_eq!(allowed(&reply), vec!["DELETE", "GET", "HEAD", "OPTIONS", "POST"]);
+    assert!(reply.body.is_empty());
+}
+
+#[test]
+fn options_lists_methods_of_resource_without_middleware() {
+    let (items, item) = with_server(app(true), |addr| (options(addr, "/items"), options(addr, "/items/1")));
+    assert_eq!(items.status, 200);
+    assert_eq!(allowed(&items), vec!["GET", "HEAD", "OPTIONS", "POST"]);
+    assert_eq!(items.header("X-Authenticated"), None);
+    assert_eq!(allowed(&item), vec!["DELETE", "OPTIONS"]);
+}
+
+#[test]
+fn options_is_not_allowed_without_auto_options() {
+    let reply = with_server(app(false), |addr| options(addr, "/items"));
+    assert_eq!(reply.status, 405);
+    assert_eq!(allowed(&reply), vec!["GET", "HEAD", "POST"]);
+}
@@ -0,0 +1,83 @@
+extern crate edge;
+
+mod common;
+
+use edge::{defer, stream, Edge, Mode, Request, Response, Result, Router};
+
+use std::io::{self, Write};
+
+use common::{get, with_server};
+
+fn explode(_app: &mut (), _req: &Request, _res: &mut Response) -> Result {
+    panic!("user exploded")
+}
+
+fn app(mode: Mode) -> Edge {
+    let mut edge = Edge::new("127.0.0.1:0");
+    edge.mode(mode);
+    let mut router = Router::<()>::new();
+    router.mount_service("/handler", |_req, _res| panic!("handler exploded"));
+    router.mount_service("/stream", |_req, _res| stream(|_app: &mut (), writer: &mut Write| -> io::Result<()> {
+        try!(writer.write_all(b"partial"));
+        panic!("stream exploded")
+    }));
+    router.mount_service("/defer", |_req, _res| defer(|_deferred| panic!("defer exploded")));
+    router.mount_service("/ok", |_req, res| res.body("ok"));
+    edge.mount("/", router);
+
+    let mut users = Router::<()>::new();
+    users.get("/users/:id", explode);
+    users.get("/users/:id/edit", explode);
+    users.name("GET".parse().unwrap(), "/users/:id", "users::show");
+    edge.mount("/", users);
+    edge
+}
+
+#[test]
+fn development_shows_panic_and_backtrace() {
+    let reply = with_server(app(Mode::Development), |addr| get(addr, "/handler/x", ""));
+    assert_eq!(reply.status, 500);
+    assert!(reply.text().contains("handler exploded"));
+    assert!(reply.text().lines().count() > 1, "expected a backtrace in {:?}", reply.text());
+}
+
+#[test]
+fn production_hides_panic() {
+    let reply = with_server(app(Mode::Production), |addr| get(addr, "/handler/x", ""));
+    assert_eq!(reply.status, 500);
+    assert!(!reply.text().contains("exploded"));
+    assert!(reply.text().contains("request id"));
+    assert!(reply.header("X-Request-Id").is_some());
+}
+
+#[test]
+fn panic_in_stream_closes_connection_and_server_keeps_working() {
+    let (streamed, after) = with_server(app(Mode::Production), |addr| {
+        (get(addr, "/stream/x", ""), get(addr, "/ok/x", ""))
+    });
+    // the headers were sent before the panic, the chunked body is never terminated
+    assert_eq!(streamed.status, 200);
+    assert_eq!(after.text(), "ok");
+}
+
+#[test]
+fn panic_in_deferred_closure_is_an_error() {
+    let (deferred, after) = with_server(app(Mode::Production), |addr| {
+        (get(addr, "/defer/x", ""), get(addr, "/ok/x", ""))
+    });
+    assert_eq!(deferred.status, 500);
+    assert_eq!(after.text(), "ok");
+}
+
+#[test]
+fn panic_message_names_route() {
+    let (named, unnamed) = with_server(app(Mode::Development), |addr| (get(addr, "/users/1", ""), get(addr, "/users/1/edit", "")));
+    assert!(named.text().contains("handler for route users::show panicked"), "{}", named.text());
+    assert!(unnamed.text().contains("handler for route /users/:id/edit panicked"), "{}", unnamed.text());
+}
+
+#[test]
+#[should_panic(expected = "could not name route GET /missing: no such route")]
+fn name_needs_existing_route() {
+    Router::<()>::new().name("GET".parse().unwrap(), "/missing", "missing");
+}
@@ -0,0 +1,42 @@
+extern crate edge;
+
+mod common;
+
+use edge::{defer, Edge, Request, Response, Result, Router};
+
+use std::thread;
+
+use common::{get, with_server};
+
+#[derive(Default)]
+struct App;
+
+impl App {
+    fn borrowed(&mut self, req: &Request, res: &mut Response) -> Result {
+        res.body(format!("{} {}", req.param("id").unwrap(), req.param("missing").is_none()))
+    }
+
+    fn owned(&mut self, req: &Request, _res: &mut Response) -> Result {
+        // the value outlives the request
+        let id = req.param_owned("id").unwrap();
+        defer(move |deferred| {
+            thread::spawn(move || deferred.send(format!("later {}", id)));
+        })
+    }
+}
+
+fn app() -> Edge {
+    let mut edge = Edge::new("127.0.0.1:0");
+    let mut router = Router::<App>::new();
+    router.get("/users/:id", App::borrowed);
+    router.get("/users/:id/later", App::owned);
+    edge.mount("/", router);
+    edge
+}
+
+#[test]
+fn params_are_borrowed_or_owned() {
+    let (borrowed, owned) = with_server(app(), |addr| (get(addr, "/users/42", ""), get(addr, "/users/7/later", "")));
+    assert_eq!(borrowed.text(), "42 true");
+    assert_eq!(owned.text(), "later 7");
+}
@@ -0,0 +1,87 @@
+extern crate edge;
+
+mod common;
+
+use edge::{Edge, Router};
+
+use common::{send, with_server, Reply};
+
+use std::collections::BTreeMap;
+use std::net::SocketAddr;
+
+fn app(empty_json_as_null: bool) -> Edge {
+    let mut edge = Edge::new("127.0.0.1:0");
+    edge.empty_json_as_null(empty_json_as_null);
+    let mut router = Router::<()>::new();
+    router.mount_service("/person", |req, res| {
+        let person: BTreeMap<String, String> = try!(req.parse_body());
+        res.body(format!("{:?}", person))
+    });
+    router.mount_service("/form", |req, res| {
+        let form = try!(req.form());
+        res.body(form.get("name").map_or(String::new(), |name| name.to_string()))
+    });
+    router.mount_service("/optional", |req, res| {
+        let person: Option<BTreeMap<String, String>> = try!(req.parse_body());
+        res.body(format!("{:?}", person))
+    });
+    edge.mount("/", router);
+    edge
+}
+
+fn post(addr: SocketAddr, content_type: &str, body: &str) -> Reply {
+    post_to(addr, "/person/x", content_type, body)
+}
+
+fn post_to(addr: SocketAddr, path: &str, content_type: &str, body: &str) -> Reply {
+    send(addr, &format!("POST {} HTTP/1.1\r\nHost: localhost\r\nContent-Type: {}\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{}",
+        path, content_type, body.len(), body))
+}
+
+#[test]
+fn json_and_form_bodies_are_deserialized_alike() {
+    let (json, form) = with_server(app(false), |addr| (
+        post(addr, "application/json", r#"{"name": "Ann Lee"}"#),
+        post(addr, "application/x-www-form-urlencoded", "name=Ann+Lee")
+    ));
+    assert_eq!(json.text(), r#"{"name": "Ann Lee"}"#);
+    assert_eq!(form.text(), json.text());
+}
+
+#[test]
+fn invalid_bodies_are_rejected() {
+    let statuses: Vec<u16> = with_server(app(false), |addr| vec![
+        post(addr, "text/plain", "name=Ann"),
+        post(addr, "application/json", "{name"),
+        post(addr, "application/json", "")
+    ]).iter().map(|reply| reply.status).collect();
+    assert_eq!(statuses, vec![415, 400, 400]);
+}
+
+#[test]
+fn empty_body_can_be_read_as_null() {
+    let replies = with_server(app(true), |addr| vec![
+        post_to(addr, "/optional/x", "application/json", ""),
+        post_to(addr, "/optional/x", "application/json", r#"{"name": "Ann"}"#),
+        post_to(addr, "/person/x", "application/json", ""),
+        post_to(addr, "/optional/x", "text/plain", "")
+    ]);
+    assert_eq!(replies[0].text(), "None");
+    assert_eq!(replies[1].text(), r#"Some({"name": "Ann"})"#);
+    // null is not a map
+    assert_eq!(replies[2].status, 400);
+    // whatever the Content-Type
+    assert_eq!(replies[3].text(), "None");
+}
+
+#[test]
+fn latin1_forms_are_decoded_by_charset() {
+    let replies = with_server(app(false), |addr| vec![
+        post_to(addr, "/form/x", "application/x-www-form-urlencoded; charset=ISO-8859-1", "name=Fran%E7ois+R%E9my"),
+        post_to(addr, "/person/x", "application/x-www-form-urlencoded; charset=latin1", "name=Fran%E7ois+R%E9my"),
+        post_to(addr, "/form/x", "application/x-www-form-urlencoded", "name=Fran%C3%A7ois+R%C3%A9my")
+    ]);
+    assert_eq!(replies[0].text(), "François Rémy");
+    assert!(replies[1].text().contains("François Rémy"), "{}", replies[1].text());
+    assert_eq!(replies[2].text(), "François Rémy");
+}
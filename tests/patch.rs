@@ -0,0 +1,72 @@
+extern crate edge;
+
+mod common;
+
+use edge::{json, Edge, Patch, Router, Status};
+
+use common::{send, with_server};
+
+fn value(text: &str) -> json::Value {
+    json::from_str(text).unwrap()
+}
+
+#[test]
+fn merge_patch_follows_rfc_7386() {
+    let mut doc = value(r#"{"title": "Goodbye!", "author": {"givenName": "John", "familyName": "Doe"}, "tags": ["example", "sample"]}"#);
+    let patch = Patch::Merge(value(r#"{"title": "Hello!", "phoneNumber": "+01-123-456-7890", "author": {"familyName": null}, "tags": ["example"]}"#));
+    patch.apply(&mut doc).unwrap();
+    assert_eq!(doc, value(r#"{"title": "Hello!", "author": {"givenName": "John"}, "tags": ["example"], "phoneNumber": "+01-123-456-7890"}"#));
+}
+
+#[test]
+fn json_patch_is_atomic() {
+    let mut doc = value(r#"{"a": 1, "b": [1, 2]}"#);
+    let patch = Patch::json_patch(&value(r#"[
+        {"op": "add", "path": "/b/1", "value": 5},
+        {"op": "copy", "from": "/a", "path": "/c"},
+        {"op": "remove", "path": "/a"},
+        {"op": "test", "path": "/c", "value": 1}
+    ]"#)).unwrap();
+    patch.apply(&mut doc).unwrap();
+    assert_eq!(doc, value(r#"{"b": [1, 5, 2], "c": 1}"#));
+
+    let failing = Patch::json_patch(&value(r#"[{"op": "remove", "path": "/b"}, {"op": "test", "path": "/c", "value": 2}]"#)).unwrap();
+    assert!(failing.apply(&mut doc).is_err());
+    assert_eq!(doc, value(r#"{"b": [1, 5, 2], "c": 1}"#));
+}
+
+#[test]
+fn json_patch_rejects_unknown_operations() {
+    assert!(Patch::json_patch(&value(r#"[{"op": "frobnicate", "path": "/a"}]"#)).is_err());
+    assert!(Patch::json_patch(&value(r#"{"op": "add"}"#)).is_err());
+}
+
+fn app() -> Edge {
+    let mut edge = Edge::new("127.0.0.1:0");
+    let mut router = Router::<()>::new();
+    router.mount_service("/doc", |req, res| {
+        let patch = try!(req.patch().map_err(|e| (Status::BadRequest, e.to_string())));
+        let mut doc = value(r#"{"name": "edge", "version": 1}"#);
+        try!(patch.apply(&mut doc).map_err(|e| (Status::UnprocessableEntity, e.to_string())));
+        res.body(doc.to_string())
+    });
+    edge.mount("/", router);
+    edge
+}
+
+fn patch(addr: ::std::net::SocketAddr, content_type: &str, body: &str) -> common::Reply {
+    send(addr, &format!("PATCH /doc/x HTTP/1.1\r\nHost: localhost\r\nContent-Type: {}\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{}",
+        content_type, body.len(), body))
+}
+
+#[test]
+fn request_patch_depends_on_content_type() {
+    let replies = with_server(app(), |addr| vec![
+        patch(addr, "application/merge-patch+json", r#"{"version": 2}"#),
+        patch(addr, "application/json-patch+json", r#"[{"op": "replace", "path": "/name", "value": "edge-rs"}]"#),
+        patch(addr, "application/json", r#"{"version": 2}"#)
+    ]);
+    assert_eq!(replies[0].text(), r#"{"name":"edge","version":2}"#);
+    assert_eq!(replies[1].text(), r#"{"name":"edge-rs","version":1}"#);
+    assert_eq!(replies[2].status, 400);
+}
@@ -0,0 +1,38 @@
+extern crate edge;
+
+mod common;
+
+use edge::{Edge, Router};
+use edge::header::HttpDate;
+
+use common::{send, with_server};
+
+use std::net::SocketAddr;
+
+fn app() -> Edge {
+    let mut edge = Edge::new("127.0.0.1:0");
+    let mut router = Router::<()>::new();
+    router.mount_service("/doc", |req, res| {
+        let modified: HttpDate = "Sat, 01 Jan 2000 12:00:00 GMT".parse().unwrap();
+        try!(req.check_unmodified_since(&modified));
+        res.body("updated")
+    });
+    edge.mount("/", router);
+    edge
+}
+
+fn put(addr: SocketAddr, headers: &str) -> u16 {
+    send(addr, &format!("PUT /doc/x HTTP/1.1\r\nHost: localhost\r\n{}Content-Length: 0\r\nConnection: close\r\n\r\n", headers)).status
+}
+
+#[test]
+fn write_fails_if_modified_since_given_date() {
+    let statuses = with_server(app(), |addr| vec![
+        put(addr, "If-Unmodified-Since: Sat, 01 Jan 2000 12:00:00 GMT\r\n"),
+        put(addr, "If-Unmodified-Since: Sun, 02 Jan 2000 00:00:00 GMT\r\n"),
+        put(addr, "If-Unmodified-Since: Sat, 01 Jan 2000 00:00:00 GMT\r\n"),
+        put(addr, "If-Unmodified-Since: Sat, 01 Jan 2000 00:00:00 GMT\r\nIf-Match: *\r\n"),
+        put(addr, "")
+    ]);
+    assert_eq!(statuses, vec![200, 200, 412, 200, 200]);
+}
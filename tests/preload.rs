@@ -0,0 +1,21 @@
+extern crate edge;
+
+mod common;
+
+use edge::{Edge, Router};
+
+use common::{get, with_server};
+
+#[test]
+fn preload_links_are_appended() {
+    let mut edge = Edge::new("127.0.0.1:0");
+    let mut router = Router::<()>::new();
+    router.mount_service("/page", |_req, res| {
+        res.preload("/app.css", "style").preload("/font.woff2", "font");
+        res.body("<html></html>")
+    });
+    edge.mount("/", router);
+
+    let reply = with_server(edge, |addr| get(addr, "/page/x", ""));
+    assert_eq!(reply.header("Link"), Some("</app.css>; rel=preload; as=style, </font.woff2>; rel=preload; as=font; crossorigin"));
+}
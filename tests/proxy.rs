@@ -0,0 +1,95 @@
+extern crate edge;
+
+mod common;
+
+use edge::{proxy, Client, Edge, Router};
+
+use std::io::{Read, Write};
+use std::net::TcpListener;
+use std::thread;
+use std::time::{Duration, Instant};
+
+use common::{get, with_server};
+
+fn upstream() -> String {
+    // a server that answers any request with the request line it got
+    let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+    let addr = listener.local_addr().unwrap();
+    thread::spawn(move || {
+        for stream in listener.incoming() {
+            let mut stream = stream.unwrap();
+            let mut request = Vec::new();
+            let mut buf = [0; 1024];
+            while !request.ends_with(b"\r\n\r\n") {
+                let n = stream.read(&mut buf).unwrap();
+                if n == 0 { break; }
+                request.extend_from_slice(&buf[..n]);
+            }
+            let request = String::from_utf8(request).unwrap();
+            let line = request.lines().next().unwrap().to_string();
+            let _ = write!(stream, "HTTP/1.1 200 OK\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{}", line.len(), line);
+        }
+    });
+    format!("http://{}", addr)
+}
+
+fn app(upstream: String) -> Edge {
+    let mut edge = Edge::new("127.0.0.1:0");
+    let mut router = Router::<()>::new();
+    router.mount_service("/api", move |req, res| {
+        let mut url = format!("{}/{}", upstream, req.param("path").unwrap_or(""));
+        if let Some(query) = req.raw_query() {
+            url.push('?');
+            url.push_str(query);
+        }
+        proxy(req, res, &url)
+    });
+    router.mount_service("/invalid", |req, res| proxy(req, res, "not a url"));
+    edge.mount("/", router);
+    edge
+}
+
+#[test]
+fn forwards_path_and_query() {
+    let reply = with_server(app(upstream()), |addr| get(addr, "/api/items?page=2&sort=name", ""));
+    assert_eq!(reply.status, 200);
+    assert_eq!(reply.text(), "GET /items?page=2&sort=name HTTP/1.1");
+}
+
+#[test]
+fn invalid_url_is_bad_gateway() {
+    let reply = with_server(app(upstream()), |addr| get(addr, "/invalid/x", ""));
+    assert_eq!(reply.status, 502);
+}
+
+#[test]
+fn unreachable_upstream_is_bad_gateway() {
+    // bind then drop to get a port where nothing listens
+    let port = TcpListener::bind("127.0.0.1:0").unwrap().local_addr().unwrap().port();
+    let reply = with_server(app(format!("http://127.0.0.1:{}", port)), |addr| get(addr, "/api/x", ""));
+    assert_eq!(reply.status, 502);
+}
+
+#[test]
+fn client_does_not_panic_on_invalid_url() {
+    let mut client = Client::new();
+    assert!(client.request("not a url").is_empty());
+    assert!(!client.has_response());
+}
+
+#[test]
+fn client_times_out() {
+    // accepts connections but never answers
+    let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+    let url = format!("http://{}/", listener.local_addr().unwrap());
+    thread::spawn(move || {
+        let _streams: Vec<_> = listener.incoming().collect();
+    });
+
+    let mut client = Client::new();
+    client.timeout(Duration::from_millis(200));
+    let start = Instant::now();
+    assert!(client.request(&url).is_empty());
+    assert!(!client.has_response());
+    assert!(start.elapsed() < Duration::from_secs(5));
+}
@@ -0,0 +1,112 @@
+extern crate edge;
+extern crate serde;
+
+mod common;
+
+use edge::{Edge, Router};
+
+use common::{get, with_server};
+
+use serde::de::{Deserialize, Deserializer, MapVisitor, Visitor};
+use serde::de::impls::IgnoredAny;
+
+use std::collections::BTreeMap;
+
+/// Parameters of a paginated listing, `per_page` defaults to 20.
+struct Page {
+    page: u32,
+    per_page: u32
+}
+
+const PAGE_FIELDS: &'static [&'static str] = &["page", "per_page"];
+
+// what #[derive(Deserialize)] generates with #[serde(default)] on per_page
+impl Deserialize for Page {
+    fn deserialize<D: Deserializer>(deserializer: &mut D) -> Result<Page, D::Error> {
+        deserializer.deserialize_struct("Page", PAGE_FIELDS, PageVisitor)
+    }
+}
+
+struct PageVisitor;
+
+impl Visitor for PageVisitor {
+    type Value = Page;
+
+    fn visit_map<V: MapVisitor>(&mut self, mut visitor: V) -> Result<Page, V::Error> {
+        let (mut page, mut per_page) = (None, None);
+        while let Some(key) = try!(visitor.visit_key::<String>()) {
+            match &key[..] {
+                "page" => page = Some(try!(visitor.visit_value())),
+                "per_page" => per_page = Some(try!(visitor.visit_value())),
+                _ => { try!(visitor.visit_value::<IgnoredAny>()); }
+            }
+        }
+        try!(visitor.end());
+
+        let page = match page {
+            Some(page) => page,
+            None => try!(visitor.missing_field("page"))
+        };
+        Ok(Page { page: page, per_page: per_page.unwrap_or(20) })
+    }
+}
+
+fn app() -> Edge {
+    let mut edge = Edge::new("127.0.0.1:0");
+    let mut router = Router::<()>::new();
+    router.mount_service("/typed", |req, res| {
+        let map: BTreeMap<String, String> = try!(req.query_as());
+        let same = map.iter().all(|(name, value)| req.query(name) == Some(value.as_str()));
+        res.body(format!("{:?} {}", map, same))
+    });
+    router.mount_service("/number", |req, res| {
+        let map: BTreeMap<String, u32> = try!(req.query_as());
+        res.body(format!("{:?}", map))
+    });
+    router.mount_service("/page", |req, res| {
+        let page: Page = try!(req.query_as());
+        res.body(format!("{} {}", page.page, page.per_page))
+    });
+    router.mount_service("/raw", |req, res| res.body(req.raw_query().unwrap_or("(none)").to_string()));
+    edge.mount("/", router);
+    edge
+}
+
+#[test]
+fn query_as_decodes_like_query() {
+    let reply = with_server(app(), |addr| get(addr, "/typed/x?a=%2B+x&b=%E2%82%AC&c=%zz&a=last", ""));
+    assert_eq!(reply.status, 200);
+    assert_eq!(reply.text(), r#"{"a": "last", "b": "€", "c": "%zz"} true"#);
+}
+
+#[test]
+fn query_as_parses_values() {
+    let (ok, bad) = with_server(app(), |addr| (get(addr, "/number/x?n=42", ""), get(addr, "/number/x?n=x", "")));
+    assert_eq!(ok.text(), r#"{"n": 42}"#);
+    assert_eq!(bad.status, 400);
+}
+
+#[test]
+fn query_as_fills_struct_with_defaults() {
+    let replies = with_server(app(), |addr| vec![
+        get(addr, "/page/x?page=3&per_page=50", ""),
+        get(addr, "/page/x?page=3&sort=name", ""),
+        get(addr, "/page/x?per_page=50", ""),
+        get(addr, "/page/x?page=three", "")
+    ]);
+    assert_eq!(replies[0].text(), "3 50");
+    assert_eq!(replies[1].text(), "3 20");
+    assert_eq!(replies[2].status, 400);
+    assert_eq!(replies[3].status, 400);
+}
+
+#[test]
+fn raw_query_is_not_decoded() {
+    let replies = with_server(app(), |addr| vec![
+        get(addr, "/raw/x?b=2&a=%2B+x&a=1", ""),
+        get(addr, "/raw/x?", ""),
+        get(addr, "/raw/x", "")
+    ]);
+    let bodies: Vec<&str> = replies.iter().map(|reply| reply.text()).collect();
+    assert_eq!(bodies, vec!["b=2&a=%2B+x&a=1", "", "(none)"]);
+}
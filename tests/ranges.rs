@@ -0,0 +1,61 @@
+extern crate edge;
+
+mod common;
+
+use edge::{Edge, Router, Status};
+
+use common::{get, head, send, with_server};
+
+fn app() -> Edge {
+    let mut edge = Edge::new("127.0.0.1:0");
+    let mut router = Router::<()>::new();
+    router.mount_service("/letters", |_req, res| res.body("abcdefghij"));
+    router.mount_service("/whole", |_req, res| res.no_ranges().body("abcdefghij"));
+    router.mount_service("/created", |_req, res| res.status(Status::Created).body("abcdefghij"));
+    edge.mount("/", router);
+    edge
+}
+
+#[test]
+fn ranges_of_buffered_bodies_are_served() {
+    let replies = with_server(app(), |addr| vec![
+        get(addr, "/letters/x", "Range: bytes=0-2\r\n"),
+        get(addr, "/letters/x", "Range: bytes=-2\r\n"),
+        get(addr, "/letters/x", "Range: bytes=10-\r\n")
+    ]);
+    assert_eq!(replies[0].status, 206);
+    assert_eq!(replies[0].text(), "abc");
+    assert_eq!(replies[0].header("Content-Range"), Some("bytes 0-2/10"));
+    assert_eq!(replies[1].text(), "ij");
+    assert_eq!(replies[2].status, 416);
+}
+
+#[test]
+fn ranges_only_apply_to_successful_gets() {
+    let (post, created) = with_server(app(), |addr| (
+        send(addr, "POST /letters/x HTTP/1.1\r\nHost: localhost\r\nRange: bytes=0-2\r\nContent-Length: 0\r\nConnection: close\r\n\r\n"),
+        get(addr, "/created/x", "Range: bytes=0-2\r\n")
+    ));
+    assert_eq!(post.status, 200);
+    assert_eq!(post.text(), "abcdefghij");
+    assert_eq!(created.status, 201);
+    assert_eq!(created.text(), "abcdefghij");
+}
+
+#[test]
+fn head_request_ignores_range() {
+    let reply = with_server(app(), |addr| head(addr, "/letters/x", "Range: bytes=0-2\r\n"));
+    assert_eq!(reply.status, 200);
+    assert_eq!(reply.header("Content-Length"), Some("10"));
+    assert_eq!(reply.header("Accept-Ranges"), Some("bytes"));
+    assert_eq!(reply.header("Content-Range"), None);
+    assert!(reply.body.is_empty());
+}
+
+#[test]
+fn ranges_can_be_disabled() {
+    let reply = with_server(app(), |addr| get(addr, "/whole/x", "Range: bytes=0-2\r\n"));
+    assert_eq!(reply.status, 200);
+    assert_eq!(reply.header("Accept-Ranges"), Some("none"));
+    assert_eq!(reply.text(), "abcdefghij");
+}
@@ -0,0 +1,67 @@
+extern crate edge;
+
+mod common;
+
+use edge::{Edge, Router};
+
+use common::{connect, is_closed, read_reply, send, with_server};
+
+use std::io::Write;
+use std::iter;
+use std::net::SocketAddr;
+
+fn app() -> Edge {
+    let mut edge = Edge::new("127.0.0.1:0");
+    edge.max_uri_length(64);
+    let mut router = Router::<()>::new();
+    router.mount_service("/hello", |req, res| {
+        res.body(format!("hello {}", req.param("path").unwrap()))
+    });
+    edge.mount("/", router);
+    edge
+}
+
+/// Sends the given request line (keeping the connection open), and returns the response
+/// along with whether the server closed the connection afterwards.
+fn send_line(addr: SocketAddr, line: &str) -> (common::Reply, bool) {
+    let mut stream = connect(addr);
+    stream.write_all(format!("{}\r\nHost: localhost\r\n\r\n", line).as_bytes()).unwrap();
+    let reply = read_reply(&mut stream);
+    let closed = is_closed(&mut stream);
+    (reply, closed)
+}
+
+#[test]
+fn long_target_is_rejected() {
+    let path = format!("/hello/{}", iter::repeat("a").take(64).collect::<String>());
+    let ((long, closed), short) = with_server(app(), move |addr| (send_line(addr, &format!("GET {} HTTP/1.1", path)), send_line(addr, "GET /hello/a HTTP/1.1")));
+    assert_eq!(long.status, 414);
+    assert!(closed);
+    assert_eq!(short.0.status, 200);
+}
+
+#[test]
+fn authority_form_is_bad_request() {
+    let (reply, closed) = with_server(app(), |addr| send_line(addr, "GET localhost:80 HTTP/1.1"));
+    assert_eq!(reply.status, 400);
+    assert!(closed);
+}
+
+#[test]
+fn absolute_form_is_accepted() {
+    let reply = with_server(app(), |addr| send(addr, "GET http://localhost/hello/world HTTP/1.1\r\nHost: localhost\r\nConnection: close\r\n\r\n"));
+    assert_eq!(reply.status, 200);
+    assert_eq!(reply.text(), "hello world");
+}
+
+#[test]
+fn unparsable_request_line_does_not_break_server() {
+    let replies = with_server(app(), |addr| {
+        // hyper closes the connection when it cannot parse the request line, possibly with a 400
+        let garbage: Vec<u16> = ["GARBAGE", "GET /hello/a", "GET /hello/a HTTP/9.9"].iter()
+            .map(|line| send_line(addr, line).0.status).collect();
+        (garbage, send_line(addr, "GET /hello/a HTTP/1.1").0)
+    });
+    assert!(replies.0.iter().all(|&status| status == 0 || status == 400), "{:?}", replies.0);
+    assert_eq!(replies.1.status, 200);
+}
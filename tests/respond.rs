@@ -0,0 +1,25 @@
+extern crate edge;
+
+mod common;
+
+use edge::{Edge, Response, Router, Status};
+
+use common::{get, with_server};
+
+#[test]
+fn response_built_fluently_is_sent() {
+    let mut edge = Edge::new("127.0.0.1:0");
+    let mut router = Router::<()>::new();
+    router.mount_service("/created", |_req, res| {
+        res.header_raw("X-Kept", "1").header_raw("X-Replaced", "old");
+        Response::with_status(Status::Created).content_type("text/plain").header_raw("X-Replaced", "new").body("made")
+    });
+    edge.mount("/", router);
+
+    let reply = with_server(edge, |addr| get(addr, "/created/x", ""));
+    assert_eq!(reply.status, 201);
+    assert_eq!(reply.header("Content-Type"), Some("text/plain"));
+    assert_eq!(reply.header("X-Kept"), Some("1"));
+    assert_eq!(reply.header("X-Replaced"), Some("new"));
+    assert_eq!(reply.text(), "made");
+}
@@ -0,0 +1,26 @@
+extern crate edge;
+
+mod common;
+
+use edge::{Edge, RetryAfter, Router, Status};
+
+use common::{get, with_server};
+
+use std::time::{Duration, UNIX_EPOCH};
+
+#[test]
+fn retry_after_is_sent_as_delay_or_date() {
+    let mut edge = Edge::new("127.0.0.1:0");
+    let mut router = Router::<()>::new();
+    router.mount_service("/busy", |_req, res|
+        res.status(Status::TooManyRequests).retry_after(RetryAfter::Delay(Duration::from_secs(120))).body("slow down"));
+    router.mount_service("/maintenance", |_req, res|
+        res.status(Status::ServiceUnavailable).retry_after(RetryAfter::At(UNIX_EPOCH + Duration::from_secs(784111777))).body("back soon"));
+    edge.mount("/", router);
+
+    let (busy, maintenance) = with_server(edge, |addr| (get(addr, "/busy/x", ""), get(addr, "/maintenance/x", "")));
+    assert_eq!(busy.status, 429);
+    assert_eq!(busy.header("Retry-After"), Some("120"));
+    assert_eq!(maintenance.status, 503);
+    assert_eq!(maintenance.header("Retry-After"), Some("Sun, 06 Nov 1994 08:49:37 GMT"));
+}
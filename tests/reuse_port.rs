@@ -0,0 +1,29 @@
+#![cfg(unix)]
+
+extern crate edge;
+
+use edge::Edge;
+
+fn edge(addr: &str, reuse: bool) -> Edge {
+    let mut edge = Edge::new(addr);
+    edge.reuse_port(reuse);
+    edge
+}
+
+#[test]
+fn listeners_share_port_with_reuse_port() {
+    let mut first = edge("127.0.0.1:0", true);
+    let addr = first.bind().unwrap();
+
+    let mut second = edge(&addr.to_string(), true);
+    assert_eq!(second.bind().unwrap(), addr);
+}
+
+#[test]
+fn port_is_exclusive_by_default() {
+    let mut first = edge("127.0.0.1:0", false);
+    let addr = first.bind().unwrap();
+
+    let mut second = edge(&addr.to_string(), false);
+    assert!(second.bind().is_err());
+}
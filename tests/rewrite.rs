@@ -0,0 +1,48 @@
+extern crate edge;
+
+mod common;
+
+use edge::{Edge, Request, Response, Result, Router};
+
+use common::{get, with_server};
+
+#[derive(Default)]
+struct App;
+
+impl App {
+    fn page(&mut self, req: &Request, res: &mut Response) -> Result {
+        res.body(format!("{} {}", req.param("name").unwrap(), req.param("lang").unwrap_or("-")))
+    }
+}
+
+fn app() -> Edge {
+    let mut edge = Edge::new("127.0.0.1:0");
+    edge.rewrite(|req| {
+        let lang = req.path().first().cloned().unwrap_or(String::new());
+        if lang == "en" || lang == "fr" {
+            let rest = req.path()[1..].join("/");
+            req.set_path(&format!("/{}", rest));
+            req.set_param("lang", lang);
+            req.set_param("name", "from hook");
+        }
+    });
+
+    let mut router = Router::<App>::new();
+    router.get("/pages/:name", App::page);
+    edge.mount("/", router);
+    edge
+}
+
+#[test]
+fn rewritten_path_is_routed_with_params() {
+    let (rewritten, plain, unknown) = with_server(app(), |addr| (
+        get(addr, "/fr/pages/about", ""),
+        get(addr, "/pages/about", ""),
+        get(addr, "/de/pages/about", "")
+    ));
+
+    // the parameter of the route takes precedence over the one set by the hook
+    assert_eq!(rewritten.text(), "about fr");
+    assert_eq!(plain.text(), "about -");
+    assert_eq!(unknown.status, 404);
+}
@@ -0,0 +1,48 @@
+extern crate edge;
+
+use edge::{Request, Response, Result, Router};
+
+#[derive(Default)]
+struct App;
+
+impl App {
+    fn handle(&mut self, _req: &Request, res: &mut Response) -> Result {
+        res.body("ok")
+    }
+}
+
+#[test]
+fn routes_over_limits_are_rejected() {
+    let mut router = Router::<App>::new();
+    router.max_segments(3);
+    router.max_params(1);
+    assert!(router.try_insert("GET".parse().unwrap(), "/a/b/c", App::handle).is_ok());
+    assert!(router.try_insert("GET".parse().unwrap(), "/a/b/c/d", App::handle).is_err());
+    assert!(router.try_insert("GET".parse().unwrap(), "/users/:id", App::handle).is_ok());
+    assert!(router.try_insert("GET".parse().unwrap(), "/users/:id/:name", App::handle).is_err());
+}
+
+#[test]
+#[should_panic(expected = "could not register route")]
+fn get_panics_over_limits() {
+    let mut router = Router::<App>::new();
+    router.max_segments(1);
+    router.get("/a/b", App::handle);
+}
+
+#[test]
+fn duplicate_routes_are_rejected() {
+    let mut router = Router::<App>::new();
+    assert!(router.try_insert("GET".parse().unwrap(), "/users/:id", App::handle).is_ok());
+    assert!(router.try_insert("GET".parse().unwrap(), "/users/:name", App::handle).is_err());
+    assert!(router.try_insert("POST".parse().unwrap(), "/users/:id", App::handle).is_ok());
+    assert!(router.try_insert("GET".parse().unwrap(), "/users/me", App::handle).is_ok());
+}
+
+#[test]
+#[should_panic(expected = "duplicate route")]
+fn get_panics_on_duplicate_route() {
+    let mut router = Router::<App>::new();
+    router.get("/", App::handle);
+    router.get("/", App::handle);
+}
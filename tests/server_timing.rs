@@ -0,0 +1,34 @@
+extern crate edge;
+
+mod common;
+
+use edge::{Edge, Router};
+
+use std::time::Duration;
+
+use common::{get, with_server};
+
+fn app(enabled: bool) -> Edge {
+    let mut edge = Edge::new("127.0.0.1:0");
+    edge.server_timing(enabled);
+    let mut router = Router::<()>::new();
+    router.mount_service("/query", |req, res| {
+        req.add_timing("db", Duration::from_millis(12));
+        res.body("ok")
+    });
+    edge.mount("/", router);
+    edge
+}
+
+#[test]
+fn timings_are_reported_when_enabled() {
+    let reply = with_server(app(true), |addr| get(addr, "/query/x", ""));
+    let timing = reply.header("Server-Timing").unwrap().to_string();
+    assert!(timing.starts_with("db;dur=12.0, total;dur="), "{}", timing);
+}
+
+#[test]
+fn timings_are_not_reported_by_default() {
+    let reply = with_server(app(false), |addr| get(addr, "/query/x", ""));
+    assert_eq!(reply.header("Server-Timing"), None);
+}
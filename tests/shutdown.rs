@@ -0,0 +1,78 @@
+extern crate edge;
+
+mod common;
+
+use edge::{Edge, Router};
+
+use common::{connect, get, read_reply, with_server};
+
+use std::io::Write;
+use std::sync::{Arc, Barrier};
+use std::thread;
+use std::time::Duration;
+
+/// `/slow` waits on `started` once it runs, then on `release` before it replies.
+fn app(started: &Arc<Barrier>, release: &Arc<Barrier>) -> Edge {
+    let mut edge = Edge::new("127.0.0.1:0");
+    let mut router = Router::<()>::new();
+    let (started, release) = (started.clone(), release.clone());
+    router.mount_service("/slow", move |_req, res| {
+        started.wait();
+        release.wait();
+        res.body("slow")
+    });
+    router.mount_service("/fast", |_req, res| res.body("fast"));
+    edge.mount("/", router);
+    edge
+}
+
+#[test]
+fn shutdown_drains_in_flight_requests_and_refuses_new_ones() {
+    let (started, release) = (Arc::new(Barrier::new(2)), Arc::new(Barrier::new(2)));
+    let edge = app(&started, &release);
+    let shutdown = edge.shutdown_handle();
+    let (slow, refused) = with_server(edge, move |addr| {
+        // a connection opened before shutdown, kept alive
+        let mut idle = connect(addr);
+        idle.write_all(b"GET /fast/x HTTP/1.1\r\nHost: localhost\r\n\r\n").unwrap();
+        assert_eq!(read_reply(&mut idle).text(), "fast");
+
+        let slow = thread::spawn(move || get(addr, "/slow/x", ""));
+        started.wait();
+        shutdown.shutdown();
+
+        idle.write_all(b"GET /fast/x HTTP/1.1\r\nHost: localhost\r\n\r\n").unwrap();
+        let refused = read_reply(&mut idle);
+        release.wait();
+        (slow.join().unwrap(), refused)
+    });
+    assert_eq!(slow.status, 200);
+    assert_eq!(slow.text(), "slow");
+    assert_eq!(refused.status, 503);
+    assert_eq!(refused.header("Connection"), Some("close"));
+}
+
+#[test]
+fn requests_in_flight_are_counted() {
+    let (started, release) = (Arc::new(Barrier::new(2)), Arc::new(Barrier::new(2)));
+    let edge = app(&started, &release);
+    let shutdown = edge.shutdown_handle();
+    let counts = with_server(edge, move |addr| {
+        let before = shutdown.in_flight();
+        let slow = thread::spawn(move || get(addr, "/slow/x", ""));
+        started.wait();
+        let during = shutdown.in_flight();
+        release.wait();
+        slow.join().unwrap();
+
+        // the response may be written just after the client reads it
+        for _ in 0..100 {
+            if shutdown.in_flight() == 0 {
+                break;
+            }
+            thread::sleep(Duration::from_millis(10));
+        }
+        (before, during, shutdown.in_flight())
+    });
+    assert_eq!(counts, (0, 1, 0));
+}
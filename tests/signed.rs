@@ -0,0 +1,42 @@
+extern crate edge;
+
+mod common;
+
+use edge::{Edge, Router};
+
+use common::{get, with_server};
+
+use std::time::Duration;
+
+fn app() -> Edge {
+    let mut edge = Edge::new("127.0.0.1:0");
+    edge.secret_key(b"not so secret");
+    let mut router = Router::<()>::new();
+    router.mount_service("/sign", |req, res| {
+        // characters that are encoded differently once the URL is parsed and serialized again
+        res.body(req.sign_url("/files/{a}/b%20c?name=x+y&q=a'b", Duration::from_secs(60)).unwrap())
+    });
+    router.mount_service("/files", |req, res| {
+        try!(req.verify_signed_url());
+        res.body("ok")
+    });
+    edge.mount("/", router);
+    edge
+}
+
+#[test]
+fn signed_url_is_accepted_as_requested() {
+    let replies = with_server(app(), |addr| {
+        let url = get(addr, "/sign/x", "").text().to_string();
+        (get(addr, &url, ""), get(addr, &url.replace("name=x", "name=z"), ""))
+    });
+    assert_eq!(replies.0.status, 200);
+    assert_eq!(replies.0.text(), "ok");
+    assert_eq!(replies.1.status, 403);
+}
+
+#[test]
+fn unsigned_url_is_forbidden() {
+    let reply = with_server(app(), |addr| get(addr, "/files/a?name=x", ""));
+    assert_eq!(reply.status, 403);
+}
@@ -0,0 +1,37 @@
+extern crate edge;
+
+mod common;
+
+use edge::{Edge, Request, Response, Result, Router, Status};
+
+use common::{get, with_server};
+
+fn forbidden_page(_req: &Request, res: &mut Response) -> Result {
+    res.content_type("text/html");
+    res.body("<h1>Forbidden</h1>")
+}
+
+fn app() -> Edge {
+    let mut edge = Edge::new("127.0.0.1:0");
+    edge.on_status(Status::Forbidden, forbidden_page);
+    let mut router = Router::<()>::new();
+    router.mount_service("/denied", |_req, _res| Err(Status::Forbidden.into()));
+    router.mount_service("/explained", |_req, res| res.status(Status::Forbidden).body("not yours"));
+    edge.mount("/", router);
+    edge
+}
+
+#[test]
+fn status_page_renders_empty_responses() {
+    let reply = with_server(app(), |addr| get(addr, "/denied/x", ""));
+    assert_eq!(reply.status, 403);
+    assert_eq!(reply.header("Content-Type"), Some("text/html"));
+    assert_eq!(reply.text(), "<h1>Forbidden</h1>");
+}
+
+#[test]
+fn status_page_keeps_existing_body() {
+    let reply = with_server(app(), |addr| get(addr, "/explained/x", ""));
+    assert_eq!(reply.status, 403);
+    assert_eq!(reply.text(), "not yours");
+}
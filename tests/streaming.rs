@@ -0,0 +1,101 @@
+extern crate edge;
+
+mod common;
+
+use edge::{append_file, mixed_replace, ndjson, stream, Edge, Router};
+
+use std::io::{self, Write};
+
+use common::{get, temp_dir, with_server};
+
+fn app() -> Edge {
+    let mut edge = Edge::new("127.0.0.1:0");
+    let mut router = Router::<()>::new();
+    router.mount_service("/sized", |_req, res| {
+        res.len(10);
+        stream(|_app: &mut (), writer: &mut Write| -> io::Result<()> {
+            try!(writer.write_all(b"01234"));
+            writer.write_all(b"56789")
+        })
+    });
+    router.mount_service("/chunked", |_req, _res| stream(|_app: &mut (), writer: &mut Write| -> io::Result<()> {
+        try!(writer.write_all(b"abc"));
+        writer.write_all(b"def")
+    }));
+    router.mount_service("/cached", |_req, res| {
+        res.header_raw("Cache-Control", "public, max-age=60").header_raw("X-Accel-Buffering", "yes");
+        stream(|_app: &mut (), writer: &mut Write| writer.write_all(b"abc"))
+    });
+    router.mount_service("/lines", |_req, res| ndjson(res, vec![vec![1, 2], vec![], vec![3]]));
+    router.mount_service("/frames", |_req, res| mixed_replace(res, "frame", |_app: &mut (), parts| {
+        try!(parts.part("text/plain", b"tick"));
+        parts.part("text/html", b"<b>tock</b>")
+    }));
+    edge.mount("/", router);
+    edge
+}
+
+#[test]
+fn stream_with_known_length_is_not_chunked() {
+    let reply = with_server(app(), |addr| get(addr, "/sized/x", ""));
+    assert_eq!(reply.header("Content-Length"), Some("10"));
+    assert_eq!(reply.header("Transfer-Encoding"), None);
+    assert_eq!(reply.text(), "0123456789");
+}
+
+#[test]
+fn stream_without_length_is_chunked() {
+    let reply = with_server(app(), |addr| get(addr, "/chunked/x", ""));
+    assert_eq!(reply.header("Transfer-Encoding"), Some("chunked"));
+    assert_eq!(reply.text(), "abcdef");
+}
+
+#[test]
+fn ndjson_streams_one_value_per_line() {
+    let reply = with_server(app(), |addr| get(addr, "/lines/x", ""));
+    assert_eq!(reply.header("Content-Type"), Some("application/x-ndjson"));
+    assert_eq!(reply.text(), "[1,2]\n[]\n[3]\n");
+}
+
+#[test]
+fn stream_asks_proxies_not_to_buffer() {
+    let reply = with_server(app(), |addr| get(addr, "/chunked/x", ""));
+    assert_eq!(reply.header("X-Accel-Buffering"), Some("no"));
+    assert_eq!(reply.header("Cache-Control"), Some("no-cache, no-transform"));
+}
+
+#[test]
+fn stream_keeps_proxy_headers_set_by_handler() {
+    let reply = with_server(app(), |addr| get(addr, "/cached/x", ""));
+    assert_eq!(reply.header("X-Accel-Buffering"), Some("yes"));
+    assert_eq!(reply.header("Cache-Control"), Some("public, max-age=60"));
+}
+
+#[test]
+fn mixed_replace_streams_parts() {
+    let reply = with_server(app(), |addr| get(addr, "/frames/x", ""));
+    assert_eq!(reply.header("Content-Type"), Some("multipart/x-mixed-replace; boundary=frame"));
+    assert_eq!(reply.text(), "--frame\r\nContent-Type: text/plain\r\nContent-Length: 4\r\n\r\ntick\r\n\
+        --frame\r\nContent-Type: text/html\r\nContent-Length: 11\r\n\r\n<b>tock</b>\r\n");
+}
+
+#[test]
+fn file_is_appended_to_stream() {
+    let dir = temp_dir("append-file", &[("rows.csv", "a,1\nb,2\n")]);
+    let path = dir.join("rows.csv");
+
+    let mut edge = Edge::new("127.0.0.1:0");
+    let mut router = Router::<()>::new();
+    router.mount_service("/report", move |_req, _res| {
+        let path = path.clone();
+        stream(move |_app: &mut (), writer: &mut Write| -> io::Result<()> {
+            try!(writer.write_all(b"name,count\n"));
+            let copied = try!(append_file(writer, &path));
+            writer.write_all(format!("total,{}\n", copied).as_bytes())
+        })
+    });
+    edge.mount("/", router);
+
+    let reply = with_server(edge, |addr| get(addr, "/report/x", ""));
+    assert_eq!(reply.text(), "name,count\na,1\nb,2\ntotal,8\n");
+}
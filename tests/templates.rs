@@ -0,0 +1,167 @@
+#[macro_use]
+extern crate edge;
+
+mod common;
+
+use edge::{Edge, Mode, Request, Response, Result, Router};
+
+use common::{get, temp_dir, with_server};
+
+use std::collections::BTreeMap;
+
+#[derive(Default)]
+struct App;
+
+impl App {
+    fn page(&mut self, req: &Request, _res: &mut Response) -> Result {
+        let mut data = BTreeMap::new();
+        data.insert("name", req.param("name").unwrap_or("").to_string());
+        ok!("page", data)
+    }
+
+    fn markdown(&mut self, _req: &Request, _res: &mut Response) -> Result {
+        let mut data = BTreeMap::new();
+        data.insert("text", "a | b\n--|--\n1 | 2\n");
+        ok!("markdown", data)
+    }
+
+    fn broken(&mut self, _req: &Request, _res: &mut Response) -> Result {
+        ok!("unregistered", BTreeMap::<String, String>::new())
+    }
+
+    fn item(&mut self, req: &Request, res: &mut Response) -> Result {
+        let mut data = BTreeMap::new();
+        data.insert("name", "edge");
+        res.render_json_and_html(req, "item", data)
+    }
+}
+
+fn app(views: &::std::path::Path) -> Edge {
+    let mut edge = Edge::new("127.0.0.1:0");
+    edge.views_dir(views);
+    let mut router = Router::<App>::new();
+    router.get("/page/:name", App::page);
+    router.get("/markdown", App::markdown);
+    router.get("/item", App::item);
+    router.get("/broken", App::broken);
+    edge.mount("/", router);
+    edge
+}
+
+#[test]
+fn templates_and_partials_come_from_views_dir() {
+    let views = temp_dir("views-dir", &[
+        ("page.hbs", "{{> header}}<p>{{name}}</p>"),
+        ("partials/header.hbs", "<h1>title</h1>")
+    ]);
+    let mut edge = app(&views);
+    edge.register_template("page");
+
+    let reply = with_server(edge, |addr| get(addr, "/page/world", ""));
+    assert_eq!(reply.status, 200);
+    assert_eq!(reply.text(), "<h1>title</h1><p>world</p>");
+}
+
+#[test]
+fn markdown_options_can_be_overridden() {
+    let views = temp_dir("markdown", &[("markdown.hbs", "{{markdown text}}@@{{markdown text tables=false}}")]);
+    let mut edge = app(&views);
+    edge.register_template("markdown");
+
+    let reply = with_server(edge, |addr| get(addr, "/markdown", ""));
+    let parts: Vec<&str> = reply.text().split("@@").collect();
+    assert!(parts[0].contains("<table>"), "{}", parts[0]);
+    assert!(!parts[1].contains("<table>"), "{}", parts[1]);
+}
+
+#[test]
+fn item_is_rendered_as_html_or_json() {
+    let views = temp_dir("negotiation", &[("item.hbs", "<p>{{name}}</p>")]);
+    let mut edge = app(&views);
+    edge.register_template("item");
+
+    let replies = with_server(edge, |addr| vec![
+        get(addr, "/item", ""),
+        get(addr, "/item", "Accept: text/html,application/json;q=0.9\r\n"),
+        get(addr, "/item", "Accept: application/json\r\n"),
+        get(addr, "/item", "Accept: text/html;q=0.5, application/json\r\n")
+    ]);
+    let bodies: Vec<&str> = replies.iter().map(|reply| reply.text()).collect();
+    assert_eq!(bodies, vec!["<p>edge</p>", "<p>edge</p>", r#"{"name":"edge"}"#, r#"{"name":"edge"}"#]);
+    assert!(replies.iter().all(|reply| reply.header("Vary") == Some("Accept")));
+    assert_eq!(replies[2].header("Content-Type"), Some("application/json"));
+}
+
+#[test]
+fn render_errors_are_server_errors() {
+    let views = temp_dir("render-error", &[]);
+    let mut development = app(&views);
+    development.mode(Mode::Development);
+    let detailed = with_server(development, |addr| get(addr, "/broken", ""));
+    let hidden = with_server(app(&views), |addr| get(addr, "/broken", ""));
+
+    assert_eq!(detailed.status, 500);
+    assert!(detailed.text().contains("could not render template unregistered"), "{}", detailed.text());
+    assert_eq!(hidden.status, 500);
+    assert!(!hidden.text().contains("unregistered"), "{}", hidden.text());
+}
+
+#[test]
+fn template_globals_do_not_override_data() {
+    let views = temp_dir("globals", &[("page.hbs", "{{site}}: {{name}}")]);
+    let mut edge = app(&views);
+    edge.register_template("page");
+    let mut globals = BTreeMap::new();
+    globals.insert("site", "Edge");
+    globals.insert("name", "global");
+    edge.template_globals(globals);
+
+    let reply = with_server(edge, |addr| get(addr, "/page/world", ""));
+    assert_eq!(reply.text(), "Edge: world");
+}
+
+#[test]
+#[should_panic(expected = "template globals must be a JSON object")]
+fn template_globals_must_be_an_object() {
+    Edge::new("127.0.0.1:0").template_globals(vec!["site"]);
+}
+
+#[test]
+fn prewarmed_templates_need_no_registration() {
+    let views = temp_dir("prewarm", &[("page.hbs", "<p>{{name}}</p>")]);
+    let mut edge = app(&views);
+    edge.prewarm_templates(true);
+
+    let reply = with_server(edge, |addr| get(addr, "/page/world", ""));
+    assert_eq!(reply.text(), "<p>world</p>");
+}
+
+#[test]
+fn prewarm_fails_on_invalid_template() {
+    let views = temp_dir("prewarm-invalid", &[("page.hbs", "{{#if name}}unclosed")]);
+    let mut edge = app(&views);
+    edge.prewarm_templates(true);
+
+    let error = edge.start().unwrap_err();
+    assert!(error.to_string().contains("cannot compile template"), "{}", error);
+}
+
+#[test]
+fn templates_have_configured_extension() {
+    let views = temp_dir("extension", &[
+        ("page.hbs.html", "{{> header}}<p>{{name}}</p>"),
+        ("page.hbs", "wrong extension"),
+        ("partials/header.hbs.html", "<h1>title</h1>")
+    ]);
+    let mut registered = app(&views);
+    registered.template_extension(".hbs.html");
+    registered.register_template("page");
+    let mut prewarmed = app(&views);
+    prewarmed.template_extension("hbs.html");
+    prewarmed.prewarm_templates(true);
+
+    let registered = with_server(registered, |addr| get(addr, "/page/world", ""));
+    let prewarmed = with_server(prewarmed, |addr| get(addr, "/page/world", ""));
+    assert_eq!(registered.text(), "<h1>title</h1><p>world</p>");
+    assert_eq!(prewarmed.text(), "<h1>title</h1><p>world</p>");
+}
@@ -0,0 +1,75 @@
+//! Run with `cargo test --features tracing`.
+#![cfg(feature = "tracing")]
+
+extern crate edge;
+extern crate tracing;
+
+mod common;
+
+use edge::{Edge, Router};
+
+use tracing::{Event, Metadata, Subscriber};
+use tracing::span::{Attributes, Id, Record};
+
+use std::sync::atomic::{AtomicUsize, ATOMIC_USIZE_INIT, Ordering};
+
+use common::{get, with_server};
+
+static SPANS: AtomicUsize = ATOMIC_USIZE_INIT;
+static ENTERED: AtomicUsize = ATOMIC_USIZE_INIT;
+static EXITED: AtomicUsize = ATOMIC_USIZE_INIT;
+static EVENTS: AtomicUsize = ATOMIC_USIZE_INIT;
+
+/// Counts request spans, and how many times they are entered and exited.
+struct Counter;
+
+impl Subscriber for Counter {
+    fn enabled(&self, _metadata: &Metadata) -> bool {
+        true
+    }
+
+    fn new_span(&self, span: &Attributes) -> Id {
+        if span.metadata().name() == "request" {
+            SPANS.fetch_add(1, Ordering::SeqCst);
+        }
+        Id::from_u64(1)
+    }
+
+    fn record(&self, _span: &Id, _values: &Record) {}
+
+    fn record_follows_from(&self, _span: &Id, _follows: &Id) {}
+
+    fn event(&self, _event: &Event) {
+        EVENTS.fetch_add(1, Ordering::SeqCst);
+    }
+
+    fn enter(&self, _span: &Id) {
+        ENTERED.fetch_add(1, Ordering::SeqCst);
+    }
+
+    fn exit(&self, _span: &Id) {
+        EXITED.fetch_add(1, Ordering::SeqCst);
+    }
+}
+
+#[test]
+fn span_per_request() {
+    // the span is entered on a worker thread, so the subscriber must be global
+    tracing::subscriber::set_global_default(Counter).unwrap();
+
+    let mut edge = Edge::new("127.0.0.1:0");
+    let mut router = Router::<()>::new();
+    router.mount_service("/hello", |_req, res| res.body("hello"));
+    edge.mount("/", router);
+
+    with_server(edge, |addr| {
+        for _ in 0..2 {
+            assert_eq!(get(addr, "/hello/x", "").status, 200);
+        }
+    });
+
+    assert_eq!(SPANS.load(Ordering::SeqCst), 2);
+    assert_eq!(ENTERED.load(Ordering::SeqCst), 2);
+    assert_eq!(EXITED.load(Ordering::SeqCst), 2);
+    assert!(EVENTS.load(Ordering::SeqCst) >= 2);
+}
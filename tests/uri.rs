@@ -0,0 +1,28 @@
+extern crate edge;
+
+mod common;
+
+use edge::{Edge, Router};
+
+use common::{get, send, with_server};
+
+fn app() -> Edge {
+    let mut edge = Edge::new("127.0.0.1:0");
+    let mut router = Router::<()>::new();
+    router.mount_service("/uri", |req, res| {
+        let url = req.uri().unwrap();
+        res.body(format!("{} {} {:?}", url.host_str().unwrap_or(""), url.path(), url.query()))
+    });
+    edge.mount("/", router);
+    edge
+}
+
+#[test]
+fn uri_has_path_and_query() {
+    let (origin, absolute) = with_server(app(), |addr| (
+        get(addr, "/uri/a/b?x=1&y", ""),
+        send(addr, "GET http://example.com/uri/c HTTP/1.1\r\nHost: example.com\r\nConnection: close\r\n\r\n")
+    ));
+    assert_eq!(origin.text(), r#"127.0.0.1 /uri/a/b Some("x=1&y")"#);
+    assert_eq!(absolute.text(), "example.com /uri/c None");
+}
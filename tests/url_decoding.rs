@@ -0,0 +1,51 @@
+extern crate edge;
+
+mod common;
+
+use edge::{Edge, Router, UrlDecoding};
+
+use common::{get, with_server};
+
+fn app(decoding: Option<(UrlDecoding, UrlDecoding)>) -> Edge {
+    let mut edge = Edge::new("127.0.0.1:0");
+    if let Some((path, query)) = decoding {
+        edge.url_decoding(path, query);
+    }
+    let mut router = Router::<()>::new();
+    router.mount_service("/files", |req, res|
+        res.body(format!("{} {}", req.param("path").unwrap_or("-"), req.query("q").unwrap_or("-"))));
+    edge.mount("/", router);
+    edge
+}
+
+/// Returns the body of each response, or its status if it is an error.
+fn fetch(edge: Edge, targets: &'static [&'static str]) -> Vec<String> {
+    with_server(edge, move |addr| targets.iter().map(|target| {
+        let reply = get(addr, target, "");
+        if reply.status == 200 { reply.text().to_string() } else { reply.status.to_string() }
+    }).collect())
+}
+
+#[test]
+fn paths_are_decoded_except_slashes() {
+    let found = fetch(app(None), &["/files/r%C3%A9sum%C3%A9/a%2Fb?q=caf%C3%A9+au+lait"]);
+    assert_eq!(found, vec!["résumé/a%2Fb café au lait"]);
+}
+
+#[test]
+fn path_is_strict_and_query_lenient_by_default() {
+    let found = fetch(app(None), &["/files/%zz", "/files/%FF", "/files/x?q=%zz%41"]);
+    assert_eq!(found, vec!["400", "400", "x %zzA"]);
+}
+
+#[test]
+fn strict_decoding_rejects_invalid_escapes() {
+    let found = fetch(app(Some((UrlDecoding::Strict, UrlDecoding::Strict))), &["/files/x?q=%zz", "/files/x?q=%FF", "/files/x?q=ok"]);
+    assert_eq!(found, vec!["400", "400", "x ok"]);
+}
+
+#[test]
+fn lenient_decoding_keeps_what_it_can() {
+    let found = fetch(app(Some((UrlDecoding::Lenient, UrlDecoding::Lenient))), &["/files/%zz", "/files/a%FFb?q=%FF"]);
+    assert_eq!(found, vec!["%zz -", "a\u{fffd}b \u{fffd}"]);
+}
@@ -0,0 +1,30 @@
+extern crate edge;
+
+mod common;
+
+use edge::{Edge, Router};
+
+use common::{get, with_server};
+
+fn app() -> Edge {
+    let mut edge = Edge::new("127.0.0.1:0");
+    let mut router = Router::<()>::new();
+    router.mount_service("/negotiated", |_req, res| {
+        res.vary("Accept").vary("accept-language").vary("ACCEPT");
+        res.body("negotiated")
+    });
+    router.mount_service("/anything", |_req, res| {
+        res.header_raw("Vary", "*");
+        res.vary("Accept");
+        res.body("anything")
+    });
+    edge.mount("/", router);
+    edge
+}
+
+#[test]
+fn vary_accumulates_names_once() {
+    let (negotiated, anything) = with_server(app(), |addr| (get(addr, "/negotiated/x", ""), get(addr, "/anything/x", "")));
+    assert_eq!(negotiated.header("Vary"), Some("Accept, accept-language"));
+    assert_eq!(anything.header("Vary"), Some("*"));
+}
@@ -0,0 +1,26 @@
+extern crate edge;
+
+mod common;
+
+use edge::{Edge, Router};
+
+use common::{get, with_server};
+
+const HANDSHAKE: &'static str = "Connection: keep-alive, Upgrade\r\nUpgrade: WebSocket\r\n\
+    Sec-WebSocket-Version: 13\r\nSec-WebSocket-Key: dGhlIHNhbXBsZSBub25jZQ==\r\n";
+
+#[test]
+fn websocket_upgrade_is_detected() {
+    let mut edge = Edge::new("127.0.0.1:0");
+    let mut router = Router::<()>::new();
+    router.mount_service("/socket", |req, res| res.body(req.is_websocket_upgrade().to_string()));
+    edge.mount("/", router);
+
+    let detected = with_server(edge, |addr| vec![
+        get(addr, "/socket/x", HANDSHAKE),
+        get(addr, "/socket/x", ""),
+        get(addr, "/socket/x", "Connection: Upgrade\r\nUpgrade: websocket\r\nSec-WebSocket-Version: 8\r\nSec-WebSocket-Key: a2V5\r\n"),
+        get(addr, "/socket/x", "Connection: Upgrade\r\nUpgrade: websocket\r\nSec-WebSocket-Version: 13\r\n")
+    ].iter().map(|reply| reply.text().to_string()).collect::<Vec<_>>());
+    assert_eq!(detected, vec!["true", "false", "false", "false"]);
+}